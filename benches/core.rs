@@ -0,0 +1,104 @@
+//! Core throughput on the synthetic instruction mixes, plus any ROMs listed
+//! in `CHIPRUST8_BENCH_ROMS` (separated like `PATH`) in the frame groups. Logging is off
+//! throughout, so only the core is measured.
+//!
+//! `cargo bench -- --save-baseline before` on one commit and
+//! `cargo bench -- --baseline before` on another compares the two.
+
+use chiprust8::core::{
+    bench::Chip8BenchMix, blocks::Chip8Engine, Chip8Core, DEFAULT_INSTRS_PER_FRAME,
+};
+use chiprust8::graphics::graphics_adapter::GraphicsAdapter;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::path::PathBuf;
+
+/// Instructions per measured iteration of the `tick` group.
+const TICKS: u64 = 10_000;
+
+fn workloads() -> Vec<(String, Vec<u8>)> {
+    let mut workloads: Vec<(String, Vec<u8>)> = Chip8BenchMix::all()
+        .into_iter()
+        .map(|mix| (mix.to_string(), mix.rom()))
+        .collect();
+    if let Some(paths) = std::env::var_os("CHIPRUST8_BENCH_ROMS") {
+        for path in std::env::split_paths(&paths) {
+            let name: String = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let rom: Vec<u8> = std::fs::read(&path)
+                .unwrap_or_else(|e| panic!("{}: {}", PathBuf::from(&path).display(), e));
+            workloads.push((name, rom));
+        }
+    }
+    workloads
+}
+
+/// The mixes for the `tick` group. `tick` never runs the timers, so the
+/// game mix, like any real ROM, would spin in its wait on the delay timer;
+/// those are only measured a frame at a time.
+fn tick_workloads() -> Vec<(String, Vec<u8>)> {
+    Chip8BenchMix::all()
+        .into_iter()
+        .filter(|mix| *mix != Chip8BenchMix::Game)
+        .map(|mix| (mix.to_string(), mix.rom()))
+        .collect()
+}
+
+fn core_for(rom: &[u8]) -> Chip8Core {
+    Chip8Core::from_rom(rom.to_vec(), true, &GraphicsAdapter::new())
+}
+
+fn bench_tick(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tick");
+    group.throughput(Throughput::Elements(TICKS));
+    for (name, rom) in tick_workloads() {
+        let mut core: Chip8Core = core_for(&rom);
+        group.bench_function(BenchmarkId::from_parameter(&name), |b| {
+            b.iter(|| {
+                for _ in 0..TICKS {
+                    let _ = core.tick();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame");
+    group.throughput(Throughput::Elements(DEFAULT_INSTRS_PER_FRAME as u64));
+    for (name, rom) in workloads() {
+        let mut core: Chip8Core = core_for(&rom);
+        group.bench_function(BenchmarkId::from_parameter(&name), |b| {
+            b.iter(|| core.run_frame())
+        });
+    }
+    group.finish();
+}
+
+/// `frame` again, on the compiled engine.
+fn bench_compiled(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compiled");
+    group.throughput(Throughput::Elements(DEFAULT_INSTRS_PER_FRAME as u64));
+    for (name, rom) in workloads() {
+        let mut core: Chip8Core = core_for(&rom);
+        core.set_engine(Chip8Engine::Compiled);
+        group.bench_function(BenchmarkId::from_parameter(&name), |b| {
+            b.iter(|| core.run_frame())
+        });
+    }
+    group.finish();
+}
+
+fn setup() -> Criterion {
+    log::set_max_level(log::LevelFilter::Off);
+    Criterion::default()
+}
+
+criterion_group! {
+    name = benches;
+    config = setup();
+    targets = bench_tick, bench_frame, bench_compiled
+}
+criterion_main!(benches);
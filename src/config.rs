@@ -0,0 +1,255 @@
+use crate::core::{quirks::Chip8Quirks, rom_db::Chip8RomInfo, DEFAULT_INSTRS_PER_FRAME};
+use crate::graphics::key_mapping::{KeyMapping, KeyMappingSection};
+use crate::graphics::phosphor::Chip8PersistenceMode;
+use crate::graphics::renderer::{parse_color, DEFAULT_BG_COLOR, DEFAULT_FG_COLOR};
+use eframe::egui::Color32;
+use serde::{Deserialize, Serialize};
+use simple_error::{simple_error, SimpleError};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Chip8AudioConfig {
+    /// Whether frontends sound the buzzer at all.
+    pub enabled: bool,
+}
+
+impl Default for Chip8AudioConfig {
+    fn default() -> Self {
+        Chip8AudioConfig { enabled: true }
+    }
+}
+
+/// Overrides for one ROM, in a `[roms.<sha1>]` table.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Chip8RomConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fg: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bg: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quirks: Option<Chip8Quirks>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, String>,
+}
+
+/// Everything a ROM runs with once the config and ROM database are layered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chip8RomSettings {
+    pub quirks: Chip8Quirks,
+    pub speed: u32,
+    pub fg: Color32,
+    pub bg: Color32,
+}
+
+/// Settings given on the command line, which beat the config file and the
+/// ROM database.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Chip8Overrides {
+    pub quirks: Option<Chip8Quirks>,
+    pub speed: Option<u32>,
+    pub fg: Option<Color32>,
+    pub bg: Option<Color32>,
+}
+
+impl Chip8Overrides {
+    pub fn apply(&self, settings: Chip8RomSettings) -> Chip8RomSettings {
+        Chip8RomSettings {
+            quirks: self.quirks.unwrap_or(settings.quirks),
+            speed: self.speed.unwrap_or(settings.speed),
+            fg: self.fg.unwrap_or(settings.fg),
+            bg: self.bg.unwrap_or(settings.bg),
+        }
+    }
+}
+
+/// Settings file. The top level holds defaults and each `[roms.<sha1>]`
+/// table overrides them for the ROM with that hash:
+///
+/// ```toml
+/// quirks = "cosmac"
+/// speed = 11
+/// fg = "#33FF66"
+/// rom_dirs = ["/home/me/roms"]
+/// preset = "linear"
+///
+/// [audio]
+/// enabled = false
+///
+/// [hotkeys]
+/// pause = "Space"
+///
+/// [roms.0123456789abcdef0123456789abcdef01234567]
+/// speed = 30
+/// keys = { 1 = "W", 4 = "S" }
+/// ```
+///
+/// TOML needs plain values ahead of tables, hence the field order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Chip8Config {
+    /// Instructions per frame.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fg: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bg: Option<String>,
+    /// Screenshot and initial window pixels per CHIP-8 pixel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persistence: Option<Chip8PersistenceMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decay: Option<f32>,
+    /// Directories listed in the ROM browser.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rom_dirs: Vec<PathBuf>,
+    /// Key mapping preset, see `KeyMapping::from_preset`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quirks: Option<Chip8Quirks>,
+    /// CHIP-8 key in hex to keyboard key name, e.g. `A = "Z"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, String>,
+    #[serde(default)]
+    pub audio: Chip8AudioConfig,
+    /// Emulator hotkeys by action name, see `Chip8Hotkeys::apply`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hotkeys: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub roms: BTreeMap<String, Chip8RomConfig>,
+}
+
+impl Chip8Config {
+    /// `~/.config/chiprust8/config.toml`, or under `$XDG_CONFIG_HOME` if set.
+    pub fn default_path() -> Option<PathBuf> {
+        let base: PathBuf = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("chiprust8").join("config.toml"))
+    }
+
+    /// Loads the file at `path`, or an empty config if it doesn't exist.
+    pub fn load(path: &Path) -> Result<Chip8Config, SimpleError> {
+        if !path.exists() {
+            return Ok(Chip8Config::default());
+        }
+        let text: String = fs::read_to_string(path)
+            .map_err(|e| simple_error!("Failed to read {}: {}", path.display(), e))?;
+        let config: Chip8Config = toml::from_str(&text)
+            .map_err(|e| simple_error!("Failed to parse {}: {}", path.display(), e))?;
+        config
+            .validate()
+            .map_err(|e| simple_error!("{}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SimpleError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| simple_error!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let text: String =
+            toml::to_string(self).map_err(|e| simple_error!("Failed to serialize: {}", e))?;
+        fs::write(path, text)
+            .map_err(|e| simple_error!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Catches bad colours and key names at load time rather than when a
+    /// ROM that uses them is opened.
+    fn validate(&self) -> Result<(), SimpleError> {
+        let colors = self.roms.values().flat_map(|r| [&r.fg, &r.bg]);
+        for color in [&self.fg, &self.bg].into_iter().chain(colors).flatten() {
+            parse_color(color).map_err(|e| simple_error!("{}", e))?;
+        }
+        for rom in self.roms.keys() {
+            self.mapping_for(Some(rom))?;
+        }
+        self.mapping_for(None)?;
+        Ok(())
+    }
+
+    /// The default key mapping, with `rom`'s overrides applied if it has any.
+    pub fn mapping_for(&self, rom: Option<&str>) -> Result<KeyMapping, SimpleError> {
+        let mut mapping = KeyMapping::default();
+        mapping.apply(&KeyMappingSection {
+            preset: self.preset.clone(),
+            keys: self.keys.clone(),
+        })?;
+        if let Some(rom_config) = rom.and_then(|r| self.roms.get(r)) {
+            mapping.apply(&KeyMappingSection {
+                preset: rom_config.preset.clone(),
+                keys: rom_config.keys.clone(),
+            })?;
+        }
+        Ok(mapping)
+    }
+
+    /// Stores `mapping` as the default, or as `rom`'s override.
+    pub fn set_mapping(&mut self, rom: Option<&str>, mapping: &KeyMapping) {
+        let section: KeyMappingSection = mapping.to_section();
+        match rom {
+            Some(r) => {
+                let rom_config: &mut Chip8RomConfig = self.roms.entry(String::from(r)).or_default();
+                rom_config.preset = None;
+                rom_config.keys = section.keys;
+            }
+            None => {
+                self.preset = None;
+                self.keys = section.keys;
+            }
+        }
+    }
+
+    /// Whether `rom` has its own key mapping.
+    pub fn has_rom_mapping(&self, rom: &str) -> bool {
+        match self.roms.get(rom) {
+            Some(r) => r.preset.is_some() || !r.keys.is_empty(),
+            None => false,
+        }
+    }
+
+    /// Settings for the ROM with hash `rom`. Its own table wins, then what
+    /// the ROM database says about it, then the defaults here.
+    pub fn settings_for(&self, rom: Option<&str>, info: Option<&Chip8RomInfo>) -> Chip8RomSettings {
+        let rom_config: Option<&Chip8RomConfig> = rom.and_then(|r| self.roms.get(r));
+        let db_colors = info.and_then(|i| i.colors.as_ref());
+        let color = |own: Option<&String>, db: Option<&String>, global: &Option<String>| {
+            own.or(db)
+                .or(global.as_ref())
+                .and_then(|c| parse_color(c).ok())
+        };
+        Chip8RomSettings {
+            quirks: rom_config
+                .and_then(|r| r.quirks)
+                .or_else(|| info.and_then(|i| i.quirks))
+                .or(self.quirks)
+                .unwrap_or_default(),
+            speed: rom_config
+                .and_then(|r| r.speed)
+                .or_else(|| info.and_then(|i| i.tickrate))
+                .or(self.speed)
+                .unwrap_or(DEFAULT_INSTRS_PER_FRAME),
+            fg: color(
+                rom_config.and_then(|r| r.fg.as_ref()),
+                db_colors.map(|(fg, _)| fg),
+                &self.fg,
+            )
+            .unwrap_or(DEFAULT_FG_COLOR),
+            bg: color(
+                rom_config.and_then(|r| r.bg.as_ref()),
+                db_colors.map(|(_, bg)| bg),
+                &self.bg,
+            )
+            .unwrap_or(DEFAULT_BG_COLOR),
+        }
+    }
+}
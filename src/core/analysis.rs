@@ -0,0 +1,330 @@
+use super::instrs::*;
+use super::{DEFAULT_FONT_MEM, PROGRAM_OFFSET};
+use byteorder::{BigEndian, ByteOrder};
+use simple_error::SimpleError;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Write;
+
+const MEM_SIZE: usize = 4096;
+const DATA_BYTES_PER_LINE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8ByteKind {
+    Unknown,
+    Code,
+    Data,
+}
+
+#[derive(Debug, Clone)]
+pub struct Chip8BasicBlock {
+    pub start: u16,
+    /// First address past the block's last instruction.
+    pub end: u16,
+    pub successors: Vec<u16>,
+    pub calls: Vec<u16>,
+    pub computed_jump: bool,
+}
+
+/// Mnemonic for a single opcode, or `???` if it isn't a CHIP-8 instruction.
+pub fn disassemble_opcode(opcode: u16) -> String {
+    match Chip8Instr::from_u16(opcode) {
+        Ok(instr) => instr.to_string(),
+        Err(_) => String::from("???"),
+    }
+}
+
+/// Result of statically walking a ROM from `PROGRAM_OFFSET`, following every
+/// jump, call, skip and return to separate code from sprite data.
+pub struct Chip8Analysis {
+    mem: Vec<u8>,
+    rom_len: usize,
+    kinds: Vec<Chip8ByteKind>,
+    instr_addrs: BTreeSet<u16>,
+    blocks: BTreeMap<u16, Chip8BasicBlock>,
+    computed_jumps: Vec<u16>,
+}
+
+impl Chip8Analysis {
+    pub fn analyze(rom: &[u8]) -> Chip8Analysis {
+        let mut mem: Vec<u8> = vec![0; MEM_SIZE];
+        mem[0..DEFAULT_FONT_MEM.len()].copy_from_slice(&DEFAULT_FONT_MEM[..]);
+        let rom_len: usize = rom.len().min(MEM_SIZE - PROGRAM_OFFSET as usize);
+        mem[PROGRAM_OFFSET as usize..PROGRAM_OFFSET as usize + rom_len]
+            .copy_from_slice(&rom[..rom_len]);
+
+        let mut analysis = Chip8Analysis {
+            mem,
+            rom_len,
+            kinds: vec![Chip8ByteKind::Unknown; MEM_SIZE],
+            instr_addrs: BTreeSet::new(),
+            blocks: BTreeMap::new(),
+            computed_jumps: Vec::new(),
+        };
+        let leaders = analysis.trace_code();
+        analysis.build_blocks(&leaders);
+        analysis
+    }
+
+    fn decode(&self, addr: u16) -> Result<Chip8Instr, SimpleError> {
+        let addr = addr as usize;
+        if addr + 1 >= MEM_SIZE {
+            return Err(SimpleError::new("Instruction runs past end of memory"));
+        }
+        Chip8Instr::from_u16(BigEndian::read_u16(&self.mem[addr..addr + 2]))
+    }
+
+    /// Walks every reachable path, marking instruction bytes as code and
+    /// anything `I` points at when it's drawn, loaded or stored as data.
+    /// Returns the set of basic block leaders.
+    fn trace_code(&mut self) -> BTreeSet<u16> {
+        let mut data: Vec<bool> = vec![false; MEM_SIZE];
+        data[0..DEFAULT_FONT_MEM.len()].fill(true);
+
+        let mut leaders: BTreeSet<u16> = BTreeSet::new();
+        let mut computed: BTreeSet<u16> = BTreeSet::new();
+        let mut work: VecDeque<(u16, Option<u16>)> = VecDeque::new();
+        leaders.insert(PROGRAM_OFFSET);
+        work.push_back((PROGRAM_OFFSET, None));
+
+        while let Some((start, mut index)) = work.pop_front() {
+            let mut pc: u16 = start;
+            loop {
+                if self.instr_addrs.contains(&pc) {
+                    // Fell through into code we've already walked.
+                    if pc != start {
+                        leaders.insert(pc);
+                    }
+                    break;
+                }
+                let instr: Chip8Instr = match self.decode(pc) {
+                    Ok(i) => i,
+                    Err(_) => break,
+                };
+                self.instr_addrs.insert(pc);
+                let next: u16 = pc + 2;
+
+                let mut mark_data = |origin: Option<u16>, len: usize| {
+                    if let Some(origin) = origin {
+                        let origin = origin as usize;
+                        for d in data.iter_mut().skip(origin).take(len) {
+                            *d = true;
+                        }
+                    }
+                };
+                match instr {
+                    Chip8Instr::SetIndex(args) => index = Some(args.imm),
+                    Chip8Instr::Draw(args) => mark_data(index, args.imm as usize),
+                    Chip8Instr::Extra(Chip8ExtraInstr::BcdReg(_)) => mark_data(index, 3),
+                    Chip8Instr::Extra(Chip8ExtraInstr::SaveRegRange(args))
+                    | Chip8Instr::Extra(Chip8ExtraInstr::LoadRegRange(args)) => {
+                        mark_data(index, args.reg as usize + 1)
+                    }
+                    Chip8Instr::Extra(Chip8ExtraInstr::IncrIndex(_))
+                    | Chip8Instr::Extra(Chip8ExtraInstr::SetIndexHex(_)) => index = None,
+                    _ => {}
+                }
+
+                match instr {
+                    Chip8Instr::Jump(args) => {
+                        leaders.insert(args.imm);
+                        work.push_back((args.imm, index));
+                        break;
+                    }
+                    Chip8Instr::Call(args) => {
+                        leaders.insert(args.imm);
+                        leaders.insert(next);
+                        work.push_back((args.imm, index));
+                        // The subroutine may have moved I, so stop trusting it.
+                        work.push_back((next, None));
+                        break;
+                    }
+                    Chip8Instr::Return(_) => break,
+                    Chip8Instr::RelJump(_) => {
+                        computed.insert(pc);
+                        break;
+                    }
+                    _ if instr.is_skip() => {
+                        leaders.insert(next);
+                        leaders.insert(next + 2);
+                        work.push_back((next, index));
+                        work.push_back((next + 2, index));
+                        break;
+                    }
+                    _ => pc = next,
+                }
+            }
+        }
+
+        for (addr, is_data) in data.into_iter().enumerate() {
+            if is_data {
+                self.kinds[addr] = Chip8ByteKind::Data;
+            }
+        }
+        // Code always wins over data if a path both executes and reads a byte.
+        for addr in self.instr_addrs.iter() {
+            self.kinds[*addr as usize] = Chip8ByteKind::Code;
+            self.kinds[*addr as usize + 1] = Chip8ByteKind::Code;
+        }
+        self.computed_jumps = computed.into_iter().collect();
+        leaders
+    }
+
+    fn build_blocks(&mut self, leaders: &BTreeSet<u16>) {
+        for leader in leaders.iter() {
+            if !self.instr_addrs.contains(leader) {
+                continue;
+            }
+            let mut block = Chip8BasicBlock {
+                start: *leader,
+                end: *leader,
+                successors: Vec::new(),
+                calls: Vec::new(),
+                computed_jump: false,
+            };
+            let mut pc: u16 = *leader;
+            while let Ok(instr) = self.decode(pc) {
+                let next: u16 = pc + 2;
+                block.end = next;
+                match instr {
+                    Chip8Instr::Jump(args) => block.successors.push(args.imm),
+                    Chip8Instr::Call(args) => {
+                        block.calls.push(args.imm);
+                        block.successors.push(next);
+                    }
+                    Chip8Instr::Return(_) => {}
+                    Chip8Instr::RelJump(_) => block.computed_jump = true,
+                    _ if instr.is_skip() => {
+                        block.successors.push(next);
+                        block.successors.push(next + 2);
+                    }
+                    _ => {
+                        if self.instr_addrs.contains(&next) && !leaders.contains(&next) {
+                            pc = next;
+                            continue;
+                        }
+                        if self.instr_addrs.contains(&next) {
+                            block.successors.push(next);
+                        }
+                    }
+                }
+                break;
+            }
+            self.blocks.insert(*leader, block);
+        }
+    }
+
+    pub fn kind(&self, addr: u16) -> Chip8ByteKind {
+        match self.kinds.get(addr as usize) {
+            Some(k) => *k,
+            None => Chip8ByteKind::Unknown,
+        }
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &Chip8BasicBlock> {
+        self.blocks.values()
+    }
+
+    /// Addresses of `BNNN` instructions, whose targets can't be known statically.
+    pub fn computed_jumps(&self) -> &[u16] {
+        &self.computed_jumps[..]
+    }
+
+    fn instr_line(&self, addr: u16) -> String {
+        let a = addr as usize;
+        let op: u16 = BigEndian::read_u16(&self.mem[a..a + 2]);
+        let mut line = match self.decode(addr) {
+            Ok(instr) => format!("{:#05X}: {:04X}  {}", addr, op, instr),
+            Err(_) => format!("{:#05X}: {:04X}  ???", addr, op),
+        };
+        if self.computed_jumps.contains(&addr) {
+            line.push_str("  ; computed jump");
+        }
+        line
+    }
+
+    /// Disassembles the ROM, printing bytes that were only ever read as
+    /// data (or never reached at all) as `db` lines instead of instructions.
+    pub fn disassemble(&self) -> String {
+        let mut out = String::new();
+        let end: usize = PROGRAM_OFFSET as usize + self.rom_len;
+        let mut addr: usize = PROGRAM_OFFSET as usize;
+        while addr < end {
+            if self.instr_addrs.contains(&(addr as u16)) {
+                if self.blocks.contains_key(&(addr as u16)) && addr != PROGRAM_OFFSET as usize {
+                    out.push('\n');
+                }
+                let _ = writeln!(out, "{}", self.instr_line(addr as u16));
+                addr += 2;
+                continue;
+            }
+
+            let kind: Chip8ByteKind = self.kinds[addr];
+            let mut bytes: Vec<String> = Vec::new();
+            let line_start: usize = addr;
+            while addr < end
+                && bytes.len() < DATA_BYTES_PER_LINE
+                && self.kinds[addr] == kind
+                && !self.instr_addrs.contains(&(addr as u16))
+            {
+                bytes.push(format!("{:#04X}", self.mem[addr]));
+                addr += 1;
+            }
+            let comment = match kind {
+                Chip8ByteKind::Data => "data",
+                _ => "unknown",
+            };
+            let _ = writeln!(
+                out,
+                "{:#05X}: db {}  ; {}",
+                line_start,
+                bytes.join(", "),
+                comment
+            );
+        }
+        out
+    }
+
+    /// Exports the control-flow graph in Graphviz DOT format. Call edges are
+    /// dashed and blocks ending in a computed jump are outlined in red.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "digraph chip8 {{");
+        let _ = writeln!(out, "    node [shape=box, fontname=\"monospace\"];");
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            let mut pc: u16 = block.start;
+            while pc < block.end {
+                label.push_str(&self.instr_line(pc).replace('"', "\\\""));
+                label.push_str("\\l");
+                pc += 2;
+            }
+            let color = match block.computed_jump {
+                true => ", color=red",
+                false => "",
+            };
+            let _ = writeln!(
+                out,
+                "    \"{:#05X}\" [label=\"{}\"{}];",
+                block.start, label, color
+            );
+        }
+        for block in self.blocks.values() {
+            for succ in block
+                .successors
+                .iter()
+                .filter(|s| self.blocks.contains_key(s))
+            {
+                let _ = writeln!(out, "    \"{:#05X}\" -> \"{:#05X}\";", block.start, succ);
+            }
+            for call in block.calls.iter().filter(|s| self.blocks.contains_key(s)) {
+                let _ = writeln!(
+                    out,
+                    "    \"{:#05X}\" -> \"{:#05X}\" [style=dashed, label=\"call\"];",
+                    block.start, call
+                );
+            }
+        }
+        let _ = writeln!(out, "}}");
+        out
+    }
+}
@@ -0,0 +1,244 @@
+use super::PROGRAM_OFFSET;
+use simple_error::{simple_error, SimpleError};
+use std::collections::HashMap;
+
+/// An operand as written, before labels are resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Chip8Operand {
+    V(u8),
+    I,
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    Value(String),
+}
+
+struct Chip8SourceLine {
+    number: usize,
+    text: String,
+    mnemonic: String,
+    operands: Vec<Chip8Operand>,
+}
+
+/// Assembles `source` into a ROM loaded at `PROGRAM_OFFSET`.
+///
+/// The syntax is the one the disassembler prints: one instruction per line
+/// (`LD V0, 0x0A`, `DRW V0, V1, 5`, `JP V0, 0x300`), `label:` definitions
+/// usable wherever an address or value goes, `db`/`dw` for data and `;`
+/// comments. Numbers are decimal, `0x`/`#` hex or `0b` binary. A leading
+/// `0x200: 00E0` address and opcode column is skipped, so disassembler output
+/// assembles back into the ROM it came from.
+pub fn assemble(source: &str) -> Result<Vec<u8>, SimpleError> {
+    let mut labels: HashMap<String, u16> = HashMap::new();
+    let mut lines: Vec<Chip8SourceLine> = Vec::new();
+    let mut addr: u16 = PROGRAM_OFFSET;
+
+    for (i, raw) in source.lines().enumerate() {
+        let number: usize = i + 1;
+        let err = |msg: String| simple_error!("line {}: {}", number, msg);
+        let mut text: &str = raw.split(';').next().unwrap_or("").trim();
+        let mut opcode_column: Option<&str> = None;
+
+        // Labels, or the address column of disassembler output
+        while let Some((head, rest)) = text.split_once(':') {
+            let head: &str = head.trim();
+            if head.is_empty() || head.contains(char::is_whitespace) {
+                break;
+            }
+            if parse_number(head).is_ok() {
+                let (opcode, rest) = split_opcode_column(rest.trim());
+                opcode_column = opcode;
+                text = rest;
+            } else {
+                if !head.chars().all(|c| c.is_alphanumeric() || c == '_')
+                    || head.starts_with(|c: char| c.is_ascii_digit())
+                {
+                    return Err(err(format!("Bad label {:?}", head)));
+                }
+                if labels.insert(head.to_lowercase(), addr).is_some() {
+                    return Err(err(format!("Label {:?} defined twice", head)));
+                }
+                text = rest.trim();
+            }
+        }
+        if text.is_empty() {
+            continue;
+        }
+        // Opcodes the disassembler couldn't decode go back in as they were
+        let owned: String;
+        if let (Some(opcode), "???") = (opcode_column, text) {
+            owned = format!("dw 0x{}", opcode);
+            text = &owned;
+        }
+
+        let (mnemonic, rest) = match text.split_once(char::is_whitespace) {
+            Some((m, r)) => (m, r.trim()),
+            None => (text, ""),
+        };
+        let mnemonic: String = mnemonic.to_uppercase();
+        let operands: Vec<Chip8Operand> = match rest {
+            "" => Vec::new(),
+            rest => rest.split(',').map(parse_operand).collect(),
+        };
+        let size: usize = match mnemonic.as_str() {
+            "DB" => operands.len(),
+            "DW" => operands.len() * 2,
+            _ => 2,
+        };
+        lines.push(Chip8SourceLine {
+            number,
+            text: String::from(text),
+            mnemonic,
+            operands,
+        });
+        addr = match (addr as usize + size).try_into() {
+            Ok(a) if a <= 0x1000 => a,
+            _ => return Err(err(String::from("Program runs past the end of memory"))),
+        };
+    }
+
+    let mut rom: Vec<u8> = Vec::new();
+    for line in lines.iter() {
+        encode(line, &labels, &mut rom)
+            .map_err(|e| simple_error!("line {}: {}", line.number, e))?;
+    }
+    Ok(rom)
+}
+
+/// Splits off the `00E0` opcode column that follows a disassembler address,
+/// as long as something follows it.
+fn split_opcode_column(text: &str) -> (Option<&str>, &str) {
+    match text.split_once(char::is_whitespace) {
+        Some((word, rest))
+            if word.len() == 4
+                && word.chars().all(|c| c.is_ascii_hexdigit())
+                && !rest.trim().is_empty() =>
+        {
+            (Some(word), rest.trim())
+        }
+        _ => (None, text),
+    }
+}
+
+fn parse_number(s: &str) -> Result<u32, String> {
+    let lower: String = s.to_lowercase();
+    let result = if let Some(hex) = lower.strip_prefix("0x").or_else(|| lower.strip_prefix('#')) {
+        u32::from_str_radix(hex, 16)
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        u32::from_str_radix(bin, 2)
+    } else {
+        lower.parse::<u32>()
+    };
+    result.map_err(|e| format!("Bad number {:?}: {}", s, e))
+}
+
+fn parse_operand(s: &str) -> Chip8Operand {
+    let s: &str = s.trim();
+    let upper: String = s.to_uppercase();
+    match upper.as_str() {
+        "I" => return Chip8Operand::I,
+        "[I]" => return Chip8Operand::IndirectI,
+        "DT" => return Chip8Operand::Dt,
+        "ST" => return Chip8Operand::St,
+        "K" => return Chip8Operand::K,
+        "F" => return Chip8Operand::F,
+        "B" => return Chip8Operand::B,
+        _ => (),
+    }
+    if let Some(reg) = upper.strip_prefix('V') {
+        if reg.len() == 1 {
+            if let Ok(reg) = u8::from_str_radix(reg, 16) {
+                return Chip8Operand::V(reg);
+            }
+        }
+    }
+    Chip8Operand::Value(String::from(s))
+}
+
+fn resolve(value: &str, labels: &HashMap<String, u16>, max: u32) -> Result<u16, String> {
+    let n: u32 = match labels.get(&value.to_lowercase()) {
+        Some(addr) => *addr as u32,
+        None if value.starts_with(|c: char| c.is_ascii_digit() || c == '#') => parse_number(value)?,
+        None => return Err(format!("Unknown label {:?}", value)),
+    };
+    if n > max {
+        return Err(format!("{} doesn't fit in {:#X}", value, max));
+    }
+    Ok(n as u16)
+}
+
+fn encode(
+    line: &Chip8SourceLine,
+    labels: &HashMap<String, u16>,
+    rom: &mut Vec<u8>,
+) -> Result<(), String> {
+    use Chip8Operand::*;
+    let value = |v: &str, max: u32| resolve(v, labels, max);
+    let x = |r: u8| (r as u16) << 8;
+    let y = |r: u8| (r as u16) << 4;
+
+    let ops: &[Chip8Operand] = &line.operands;
+    let opcode: u16 = match (line.mnemonic.as_str(), ops) {
+        ("DB", _) => {
+            for op in ops {
+                match op {
+                    Value(v) => rom.push(value(v, 0xFF)? as u8),
+                    _ => return Err(format!("Expected bytes in {:?}", line.text)),
+                }
+            }
+            return Ok(());
+        }
+        ("DW", _) => {
+            for op in ops {
+                match op {
+                    Value(v) => rom.extend(value(v, 0xFFFF)?.to_be_bytes()),
+                    _ => return Err(format!("Expected words in {:?}", line.text)),
+                }
+            }
+            return Ok(());
+        }
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SYS", [Value(n)]) => value(n, 0xFFF)?,
+        ("JP", [Value(n)]) => 0x1000 | value(n, 0xFFF)?,
+        ("JP", [V(0), Value(n)]) => 0xB000 | value(n, 0xFFF)?,
+        ("CALL", [Value(n)]) => 0x2000 | value(n, 0xFFF)?,
+        ("SE", [V(a), Value(n)]) => 0x3000 | x(*a) | value(n, 0xFF)?,
+        ("SNE", [V(a), Value(n)]) => 0x4000 | x(*a) | value(n, 0xFF)?,
+        ("SE", [V(a), V(b)]) => 0x5000 | x(*a) | y(*b),
+        ("SNE", [V(a), V(b)]) => 0x9000 | x(*a) | y(*b),
+        ("LD", [V(a), Value(n)]) => 0x6000 | x(*a) | value(n, 0xFF)?,
+        ("ADD", [V(a), Value(n)]) => 0x7000 | x(*a) | value(n, 0xFF)?,
+        ("LD", [V(a), V(b)]) => 0x8000 | x(*a) | y(*b),
+        ("OR", [V(a), V(b)]) => 0x8001 | x(*a) | y(*b),
+        ("AND", [V(a), V(b)]) => 0x8002 | x(*a) | y(*b),
+        ("XOR", [V(a), V(b)]) => 0x8003 | x(*a) | y(*b),
+        ("ADD", [V(a), V(b)]) => 0x8004 | x(*a) | y(*b),
+        ("SUB", [V(a), V(b)]) => 0x8005 | x(*a) | y(*b),
+        ("SHR", [V(a)]) => 0x8006 | x(*a) | y(*a),
+        ("SHR", [V(a), V(b)]) => 0x8006 | x(*a) | y(*b),
+        ("SUBN", [V(a), V(b)]) => 0x8007 | x(*a) | y(*b),
+        ("SHL", [V(a)]) => 0x800E | x(*a) | y(*a),
+        ("SHL", [V(a), V(b)]) => 0x800E | x(*a) | y(*b),
+        ("LD", [I, Value(n)]) => 0xA000 | value(n, 0xFFF)?,
+        ("RND", [V(a), Value(n)]) => 0xC000 | x(*a) | value(n, 0xFF)?,
+        ("DRW", [V(a), V(b), Value(n)]) => 0xD000 | x(*a) | y(*b) | value(n, 0xF)?,
+        ("SKP", [V(a)]) => 0xE09E | x(*a),
+        ("SKNP", [V(a)]) => 0xE0A1 | x(*a),
+        ("LD", [V(a), Dt]) => 0xF007 | x(*a),
+        ("LD", [V(a), K]) => 0xF00A | x(*a),
+        ("LD", [Dt, V(a)]) => 0xF015 | x(*a),
+        ("LD", [St, V(a)]) => 0xF018 | x(*a),
+        ("ADD", [I, V(a)]) => 0xF01E | x(*a),
+        ("LD", [F, V(a)]) => 0xF029 | x(*a),
+        ("LD", [B, V(a)]) => 0xF033 | x(*a),
+        ("LD", [IndirectI, V(a)]) => 0xF055 | x(*a),
+        ("LD", [V(a), IndirectI]) => 0xF065 | x(*a),
+        _ => return Err(format!("Can't assemble {:?}", line.text)),
+    };
+    rom.extend(opcode.to_be_bytes());
+    Ok(())
+}
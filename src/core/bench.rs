@@ -0,0 +1,208 @@
+use super::asm::assemble;
+use super::Chip8Core;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Synthetic programs that each lean on one part of the core, plus one shaped
+/// like a game's main loop. Each runs forever, so any number of instructions
+/// can be measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8BenchMix {
+    /// Register arithmetic, logic and shifts.
+    Alu,
+    /// Sprite drawing, including the font.
+    Draw,
+    /// `FX33`, `FX55` and `FX65` against a buffer.
+    Memory,
+    /// Nested subroutine calls and returns.
+    Calls,
+    /// Input, drawing, score keeping and waiting on the delay timer.
+    Game,
+}
+
+const ALU_SOURCE: &str = "
+loop:   ADD V0, 1
+        LD V1, V0
+        OR V1, V2
+        AND V2, V0
+        XOR V3, V1
+        ADD V4, V1
+        SUB V5, V0
+        SUBN V6, V4
+        SHR V7, V5
+        SHL V8, V4
+        SE V0, 0x80
+        JP loop
+        LD V0, 0
+        JP loop
+";
+
+const DRAW_SOURCE: &str = "
+loop:   LD I, sprite
+        DRW V0, V1, 15
+        ADD V0, 3
+        ADD V1, 1
+        LD F, V2
+        DRW V1, V0, 5
+        ADD V2, 1
+        SNE V2, 16
+        CLS
+        LD V3, 0x0F
+        AND V2, V3
+        JP loop
+sprite: db 0xFF, 0x81, 0xBD, 0xA5, 0xA5, 0xBD, 0x81, 0xFF
+        db 0x18, 0x3C, 0x7E, 0xFF, 0x7E, 0x3C, 0x18
+";
+
+const MEMORY_SOURCE: &str = "
+loop:   LD I, buffer
+        LD B, V0
+        LD [I], VF
+        LD I, buffer
+        LD VF, [I]
+        ADD V0, 7
+        ADD I, V0
+        JP loop
+buffer: db 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+";
+
+const CALLS_SOURCE: &str = "
+loop:   CALL outer
+        CALL leaf
+        JP loop
+outer:  ADD V0, 1
+        CALL inner
+        RET
+inner:  CALL leaf
+        SNE V0, 0
+        RET
+        RET
+leaf:   ADD V1, V0
+        RET
+";
+
+const GAME_SOURCE: &str = "
+        CLS
+frame:  LD I, player
+        DRW VA, VB, 4       ; erase the player
+        LD V8, 5
+        SKNP V8
+        ADD VA, 1
+        LD V8, 7
+        SKNP V8
+        ADD VA, 0xFF
+        RND V9, 1
+        ADD VB, V9
+        LD I, player
+        DRW VA, VB, 4       ; and draw it again
+        LD I, score
+        LD B, VC
+        LD V2, [I]
+        LD F, V2
+        DRW V3, V4, 5       ; ones digit of the score
+        ADD VC, 1
+wait:   LD V8, DT
+        SE V8, 0
+        JP wait
+        LD V8, 1
+        LD DT, V8
+        JP frame
+player: db 0x60, 0xF0, 0xF0, 0x60
+score:  db 0, 0, 0
+";
+
+impl Chip8BenchMix {
+    pub fn all() -> [Chip8BenchMix; 5] {
+        [
+            Chip8BenchMix::Alu,
+            Chip8BenchMix::Draw,
+            Chip8BenchMix::Memory,
+            Chip8BenchMix::Calls,
+            Chip8BenchMix::Game,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Chip8BenchMix::Alu => "alu",
+            Chip8BenchMix::Draw => "draw",
+            Chip8BenchMix::Memory => "memory",
+            Chip8BenchMix::Calls => "calls",
+            Chip8BenchMix::Game => "game",
+        }
+    }
+
+    pub fn rom(&self) -> Vec<u8> {
+        let source: &str = match self {
+            Chip8BenchMix::Alu => ALU_SOURCE,
+            Chip8BenchMix::Draw => DRAW_SOURCE,
+            Chip8BenchMix::Memory => MEMORY_SOURCE,
+            Chip8BenchMix::Calls => CALLS_SOURCE,
+            Chip8BenchMix::Game => GAME_SOURCE,
+        };
+        assemble(source).expect("Benchmark source doesn't assemble")
+    }
+}
+
+impl FromStr for Chip8BenchMix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Chip8BenchMix::all()
+            .into_iter()
+            .find(|mix| mix.name() == s.to_lowercase())
+            .ok_or_else(|| {
+                let names: Vec<&str> = Chip8BenchMix::all().iter().map(|m| m.name()).collect();
+                format!("Unknown mix {:?}, expected one of {}", s, names.join(", "))
+            })
+    }
+}
+
+impl Display for Chip8BenchMix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Chip8BenchReport {
+    pub instructions: u64,
+    pub frames: u64,
+    pub elapsed: Duration,
+}
+
+impl Chip8BenchReport {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    pub fn frame_time(&self) -> Duration {
+        match self.frames {
+            0 => Duration::ZERO,
+            frames => Duration::from_secs_f64(self.elapsed.as_secs_f64() / frames as f64),
+        }
+    }
+}
+
+/// Runs `core` a frame at a time, as fast as it will go, until it has
+/// executed at least `cycles` more instructions.
+pub fn run_bench(core: &mut Chip8Core, cycles: u64) -> Chip8BenchReport {
+    let start_cycles: u64 = core.cycles();
+    let mut frames: u64 = 0;
+    let start: Instant = Instant::now();
+    while core.cycles() - start_cycles < cycles {
+        let before: u64 = core.cycles();
+        core.run_frame();
+        frames += 1;
+        if core.cycles() == before {
+            // Zero instructions per frame, or a cycle limit; nothing will change
+            break;
+        }
+    }
+    Chip8BenchReport {
+        instructions: core.cycles() - start_cycles,
+        frames,
+        elapsed: start.elapsed(),
+    }
+}
@@ -0,0 +1,331 @@
+use super::instrs::*;
+use super::quirks::Chip8Quirks;
+use super::Chip8Core;
+use log::error;
+use simple_error::SimpleError;
+use std::fmt::Display;
+use std::str::FromStr;
+
+const MEM_SIZE: usize = 4096;
+/// Most instructions compiled into one block; long straight runs are split.
+const MAX_BLOCK_LEN: usize = 64;
+
+/// How the core runs instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Chip8Engine {
+    /// Fetch, decode and `execute` one instruction at a time.
+    #[default]
+    Interpreter,
+    /// Compile basic blocks into chains of closures and run those, going
+    /// back to the interpreter for code the ROM has written over.
+    Compiled,
+}
+
+impl FromStr for Chip8Engine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "interpreter" => Ok(Chip8Engine::Interpreter),
+            "compiled" => Ok(Chip8Engine::Compiled),
+            _ => Err(format!("Expected interpreter or compiled, got {:?}", s)),
+        }
+    }
+}
+
+impl Display for Chip8Engine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Chip8Engine::Interpreter => write!(f, "interpreter"),
+            Chip8Engine::Compiled => write!(f, "compiled"),
+        }
+    }
+}
+
+/// One instruction, with its operands and the quirks baked in.
+type Chip8Op = Box<dyn Fn(&mut Chip8Core) -> Result<(), SimpleError> + Send>;
+
+/// Straight-line instructions ending at the first one that can change the
+/// PC or store to memory.
+pub(super) struct Chip8Block {
+    ops: Vec<Chip8Op>,
+}
+
+/// Compiled blocks by start address. Every store to memory must go through
+/// `invalidate`; a store into compiled code marks the instructions it hit as
+/// modified, and those are interpreted from then on.
+pub(super) struct Chip8BlockCache {
+    blocks: Vec<Option<Chip8Block>>,
+    /// Bytes that are part of some compiled block.
+    covered: Vec<bool>,
+    /// Instruction addresses that were written over after being compiled.
+    modified: Vec<bool>,
+    /// Goes up whenever blocks are thrown away, so one that was running at
+    /// the time isn't put back.
+    generation: u64,
+}
+
+impl Chip8BlockCache {
+    pub fn new() -> Chip8BlockCache {
+        Chip8BlockCache {
+            blocks: (0..MEM_SIZE).map(|_| None).collect(),
+            covered: vec![false; MEM_SIZE],
+            modified: vec![false; MEM_SIZE],
+            generation: 0,
+        }
+    }
+
+    fn take(&mut self, addr: u16) -> Option<Chip8Block> {
+        self.blocks.get_mut(addr as usize).and_then(Option::take)
+    }
+
+    fn put_back(&mut self, addr: u16, block: Chip8Block, generation: u64) {
+        if generation == self.generation {
+            self.blocks[addr as usize] = Some(block);
+        }
+    }
+
+    #[inline]
+    pub fn invalidate(&mut self, addr: usize) {
+        if !self.covered.get(addr).copied().unwrap_or(false) {
+            return;
+        }
+        self.modified[addr] = true;
+        if let Some(prev) = addr.checked_sub(1) {
+            self.modified[prev] = true;
+        }
+        self.drop_blocks();
+    }
+
+    fn drop_blocks(&mut self) {
+        self.blocks.iter_mut().for_each(|b| *b = None);
+        self.covered.iter_mut().for_each(|c| *c = false);
+        self.generation += 1;
+    }
+
+    /// Forgets everything, including which code was modified, for a new
+    /// memory image or new quirks.
+    pub fn clear(&mut self) {
+        self.drop_blocks();
+        self.modified.iter_mut().for_each(|m| *m = false);
+    }
+
+    pub fn is_modified(&self, addr: usize) -> bool {
+        self.modified[addr]
+    }
+}
+
+fn ends_block(instr: &Chip8Instr) -> bool {
+    matches!(
+        instr,
+        Chip8Instr::Return(_)
+            | Chip8Instr::Jump(_)
+            | Chip8Instr::Call(_)
+            | Chip8Instr::SkipImmEq(_)
+            | Chip8Instr::SkipImmNe(_)
+            | Chip8Instr::SkipRegEq(_)
+            | Chip8Instr::SkipRegNe(_)
+            | Chip8Instr::RelJump(_)
+            | Chip8Instr::Key(_)
+            | Chip8Instr::Extra(Chip8ExtraInstr::WaitForKey(_))
+            | Chip8Instr::Extra(Chip8ExtraInstr::BcdReg(_))
+            | Chip8Instr::Extra(Chip8ExtraInstr::SaveRegRange(_))
+    )
+}
+
+/// The closure for one instruction. The common ones are specialised here;
+/// the rest go through `execute`. Either way the effect must be exactly what
+/// `execute` would do, down to which of VX and VF is written last.
+fn compile(instr: Chip8Instr, quirks: Chip8Quirks) -> Chip8Op {
+    match instr {
+        Chip8Instr::Jump(args) => Box::new(move |core| {
+            core.regs.pc = args.imm;
+            Ok(())
+        }),
+        Chip8Instr::Call(args) => Box::new(move |core| {
+            core.stack.push_back(core.regs.pc + 2);
+            core.regs.pc = args.imm;
+            Ok(())
+        }),
+        Chip8Instr::SkipImmEq(args) => {
+            let x: usize = args.reg as usize;
+            Box::new(move |core| {
+                core.regs.pc += if core.regs.v_regs[x] == args.imm {
+                    4
+                } else {
+                    2
+                };
+                Ok(())
+            })
+        }
+        Chip8Instr::SkipImmNe(args) => {
+            let x: usize = args.reg as usize;
+            Box::new(move |core| {
+                core.regs.pc += if core.regs.v_regs[x] != args.imm {
+                    4
+                } else {
+                    2
+                };
+                Ok(())
+            })
+        }
+        Chip8Instr::SkipRegEq(args) => {
+            let (x, y) = (args.a as usize, args.b as usize);
+            Box::new(move |core| {
+                let v = &core.regs.v_regs;
+                core.regs.pc += if v[x] == v[y] { 4 } else { 2 };
+                Ok(())
+            })
+        }
+        Chip8Instr::SkipRegNe(args) => {
+            let (x, y) = (args.a as usize, args.b as usize);
+            Box::new(move |core| {
+                let v = &core.regs.v_regs;
+                core.regs.pc += if v[x] != v[y] { 4 } else { 2 };
+                Ok(())
+            })
+        }
+        Chip8Instr::RegAssign(args) => {
+            let x: usize = args.reg as usize;
+            Box::new(move |core| {
+                core.regs.pc += 2;
+                core.regs.v_regs[x] = args.imm;
+                Ok(())
+            })
+        }
+        Chip8Instr::RegIncr(args) => {
+            let x: usize = args.reg as usize;
+            Box::new(move |core| {
+                core.regs.pc += 2;
+                core.regs.v_regs[x] = core.regs.v_regs[x].wrapping_add(args.imm);
+                Ok(())
+            })
+        }
+        Chip8Instr::Math(math) => compile_math(math, quirks),
+        Chip8Instr::SetIndex(args) => Box::new(move |core| {
+            core.regs.pc += 2;
+            core.regs.index_reg = args.imm;
+            Ok(())
+        }),
+        Chip8Instr::Extra(Chip8ExtraInstr::CheckDelay(args)) => {
+            let x: usize = args.reg as usize;
+            Box::new(move |core| {
+                core.regs.pc += 2;
+                core.regs.v_regs[x] = core.timers.delay;
+                Ok(())
+            })
+        }
+        Chip8Instr::Extra(Chip8ExtraInstr::SetDelay(args)) => {
+            let x: usize = args.reg as usize;
+            Box::new(move |core| {
+                core.regs.pc += 2;
+                core.timers.delay = core.regs.v_regs[x];
+                Ok(())
+            })
+        }
+        _ => Box::new(move |core| core.execute(instr)),
+    }
+}
+
+fn compile_math(math: Chip8MathInstr, quirks: Chip8Quirks) -> Chip8Op {
+    // Every 8XYN op reads VX and VY, then writes VX and maybe VF, in that
+    // order so the flag wins when X is F.
+    fn op(x: u8, y: u8, f: impl Fn(u8, u8) -> (u8, Option<u8>) + Send + 'static) -> Chip8Op {
+        let (x, y) = (x as usize, y as usize);
+        Box::new(move |core| {
+            core.regs.pc += 2;
+            let (result, flag) = f(core.regs.v_regs[x], core.regs.v_regs[y]);
+            core.regs.v_regs[x] = result;
+            if let Some(flag) = flag {
+                core.regs.v_regs[0xF] = flag;
+            }
+            Ok(())
+        })
+    }
+    let logic_flag: Option<u8> = if quirks.logic_resets_vf {
+        Some(0)
+    } else {
+        None
+    };
+    let uses_vy: bool = quirks.shift_uses_vy;
+    match math {
+        Chip8MathInstr::Assign(r) => op(r.a, r.b, |_, b| (b, None)),
+        Chip8MathInstr::Or(r) => op(r.a, r.b, move |a, b| (a | b, logic_flag)),
+        Chip8MathInstr::And(r) => op(r.a, r.b, move |a, b| (a & b, logic_flag)),
+        Chip8MathInstr::Xor(r) => op(r.a, r.b, move |a, b| (a ^ b, logic_flag)),
+        Chip8MathInstr::IncrBy(r) => op(r.a, r.b, |a, b| {
+            let (sum, carry) = a.overflowing_add(b);
+            (sum, Some(carry as u8))
+        }),
+        Chip8MathInstr::DecrBy(r) => op(r.a, r.b, |a, b| (a.wrapping_sub(b), Some((a >= b) as u8))),
+        Chip8MathInstr::InvDecrBy(r) => {
+            op(r.a, r.b, |a, b| (b.wrapping_sub(a), Some((b >= a) as u8)))
+        }
+        Chip8MathInstr::RightShift(r) => op(r.a, r.b, move |a, b| {
+            let target: u8 = if uses_vy { b } else { a };
+            (target >> 1, Some(target & 0x01))
+        }),
+        Chip8MathInstr::LeftShift(r) => op(r.a, r.b, move |a, b| {
+            let target: u8 = if uses_vy { b } else { a };
+            (target << 1, Some(target >> 7))
+        }),
+    }
+}
+
+impl Chip8Core {
+    /// Compiles the block starting at `start`, or `None` if the instruction
+    /// there can't be compiled and has to be interpreted.
+    fn compile_block(&mut self, start: u16) -> Option<Chip8Block> {
+        let mut ops: Vec<Chip8Op> = Vec::new();
+        let mut addr: usize = start as usize;
+        while ops.len() < MAX_BLOCK_LEN && addr + 1 < MEM_SIZE && !self.blocks.is_modified(addr) {
+            let opcode: u16 =
+                u16::from_be_bytes([self.mem.memspace[addr], self.mem.memspace[addr + 1]]);
+            let instr: Chip8Instr = match Chip8Instr::from_u16(opcode) {
+                Ok(instr) => instr,
+                Err(_) => break,
+            };
+            ops.push(compile(instr, self.quirks));
+            addr += 2;
+            if ends_block(&instr) {
+                break;
+            }
+        }
+        if ops.is_empty() {
+            return None;
+        }
+        self.blocks.covered[start as usize..addr]
+            .iter_mut()
+            .for_each(|c| *c = true);
+        Some(Chip8Block { ops })
+    }
+
+    /// The compiled engine's half of `run_frame`: runs blocks until the
+    /// frame is done, stopping partway through one if need be.
+    pub(super) fn run_blocks(&mut self) {
+        while self.frame_pos < self.instrs_per_frame && !self.cycle_limit_reached() {
+            let pc: u16 = self.regs.pc;
+            let block: Chip8Block = match self.blocks.take(pc).or_else(|| self.compile_block(pc)) {
+                Some(block) => block,
+                None => {
+                    self.tick_in_frame();
+                    continue;
+                }
+            };
+            let generation: u64 = self.blocks.generation;
+            for op in block.ops.iter() {
+                if self.frame_pos >= self.instrs_per_frame || self.cycle_limit_reached() {
+                    break;
+                }
+                let result: Result<(), SimpleError> = op(self);
+                self.frame_pos += 1;
+                if let Err(e) = result {
+                    error!("Failed to tick with err {}", e);
+                    break;
+                }
+                self.cycles += 1;
+            }
+            self.blocks.put_back(pc, block, generation);
+        }
+    }
+}
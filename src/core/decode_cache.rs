@@ -0,0 +1,50 @@
+use super::instrs::Chip8Instr;
+
+const MEM_SIZE: usize = 4096;
+
+/// Opcodes and their decoded instructions by address, filled in the first
+/// time each address is executed. Every store to memory must go through
+/// `invalidate` so self-modifying code runs what it wrote.
+pub(super) struct Chip8DecodeCache {
+    entries: Vec<Option<(u16, Chip8Instr)>>,
+}
+
+impl Chip8DecodeCache {
+    pub fn new() -> Chip8DecodeCache {
+        Chip8DecodeCache {
+            entries: vec![None; MEM_SIZE],
+        }
+    }
+
+    pub fn get(&self, addr: u16) -> Option<(u16, Chip8Instr)> {
+        self.entries.get(addr as usize).copied().flatten()
+    }
+
+    pub fn insert(&mut self, addr: u16, opcode: u16, instr: Chip8Instr) {
+        if let Some(entry) = self.entries.get_mut(addr as usize) {
+            *entry = Some((opcode, instr));
+        }
+    }
+
+    /// Forgets both instructions the byte at `addr` can be part of: the one
+    /// starting there and the one starting the byte before.
+    #[inline]
+    pub fn invalidate(&mut self, addr: usize) {
+        if let Some(entry) = self.entries.get_mut(addr) {
+            *entry = None;
+        }
+        if let Some(entry) = addr.checked_sub(1).and_then(|a| self.entries.get_mut(a)) {
+            *entry = None;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|e| *e = None);
+    }
+
+    /// How many addresses have a decoded instruction.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.entries.iter().filter(|e| e.is_some()).count()
+    }
+}
@@ -1,5 +1,5 @@
-use log::debug;
 use simple_error::{simple_error, SimpleError};
+use std::fmt::Display;
 
 pub const CHIP8_CLEAR_RET_FIRST_NIBBLE: u8 = 0;
 pub const CHIP8_JUMP_FIRST_NIBBLE: u8 = 1;
@@ -197,8 +197,8 @@ impl Chip8Instr {
             CHIP8_KEY_OP_FIRST_NIBBLE => {
                 let secondary_op: u8 = (instr & 0xFF) as u8;
                 let mi: Chip8KeyConditionalInstr = match secondary_op {
-                    0x9E => Chip8KeyConditionalInstr::KeyNotPressed(Chip8SingleRegOp::new(&instr)),
-                    0xA1 => Chip8KeyConditionalInstr::KeyPressed(Chip8SingleRegOp::new(&instr)),
+                    0x9E => Chip8KeyConditionalInstr::KeyPressed(Chip8SingleRegOp::new(&instr)),
+                    0xA1 => Chip8KeyConditionalInstr::KeyNotPressed(Chip8SingleRegOp::new(&instr)),
                     _ => {
                         return Err(simple_error!(
                             "Failed key conditional with secondary op {:?}",
@@ -234,7 +234,135 @@ impl Chip8Instr {
                 return Err(simple_error!("Could not decode instr: {:?}", instr));
             }
         };
-        debug!("Instruction {:X} became {:?}", instr, out_instr);
+        instr_debug!("Instruction {:X} became {:?}", instr, out_instr);
         Ok(out_instr)
     }
 }
+
+impl Chip8Instr {
+    /// True if this instruction may skip over the one that follows it.
+    pub fn is_skip(&self) -> bool {
+        matches!(
+            self,
+            Chip8Instr::SkipImmEq(_)
+                | Chip8Instr::SkipImmNe(_)
+                | Chip8Instr::SkipRegEq(_)
+                | Chip8Instr::SkipRegNe(_)
+                | Chip8Instr::Key(_)
+        )
+    }
+
+    /// The opcode pattern this instruction was decoded from, e.g. `8XY4`.
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            Chip8Instr::Clear(_) => "00E0",
+            Chip8Instr::Return(_) => "00EE",
+            Chip8Instr::Jump(_) => "1NNN",
+            Chip8Instr::Call(_) => "2NNN",
+            Chip8Instr::SkipImmEq(_) => "3XNN",
+            Chip8Instr::SkipImmNe(_) => "4XNN",
+            Chip8Instr::SkipRegEq(_) => "5XY0",
+            Chip8Instr::RegAssign(_) => "6XNN",
+            Chip8Instr::RegIncr(_) => "7XNN",
+            Chip8Instr::Math(inner_instr) => match inner_instr {
+                Chip8MathInstr::Assign(_) => "8XY0",
+                Chip8MathInstr::Or(_) => "8XY1",
+                Chip8MathInstr::And(_) => "8XY2",
+                Chip8MathInstr::Xor(_) => "8XY3",
+                Chip8MathInstr::IncrBy(_) => "8XY4",
+                Chip8MathInstr::DecrBy(_) => "8XY5",
+                Chip8MathInstr::RightShift(_) => "8XY6",
+                Chip8MathInstr::InvDecrBy(_) => "8XY7",
+                Chip8MathInstr::LeftShift(_) => "8XYE",
+            },
+            Chip8Instr::SkipRegNe(_) => "9XY0",
+            Chip8Instr::SetIndex(_) => "ANNN",
+            Chip8Instr::RelJump(_) => "BNNN",
+            Chip8Instr::Random(_) => "CXNN",
+            Chip8Instr::Draw(_) => "DXYN",
+            Chip8Instr::Key(inner_instr) => match inner_instr {
+                Chip8KeyConditionalInstr::KeyPressed(_) => "EX9E",
+                Chip8KeyConditionalInstr::KeyNotPressed(_) => "EXA1",
+            },
+            Chip8Instr::Extra(inner_instr) => match inner_instr {
+                Chip8ExtraInstr::CheckDelay(_) => "FX07",
+                Chip8ExtraInstr::WaitForKey(_) => "FX0A",
+                Chip8ExtraInstr::SetDelay(_) => "FX15",
+                Chip8ExtraInstr::SetBuzzer(_) => "FX18",
+                Chip8ExtraInstr::IncrIndex(_) => "FX1E",
+                Chip8ExtraInstr::SetIndexHex(_) => "FX29",
+                Chip8ExtraInstr::BcdReg(_) => "FX33",
+                Chip8ExtraInstr::SaveRegRange(_) => "FX55",
+                Chip8ExtraInstr::LoadRegRange(_) => "FX65",
+            },
+        }
+    }
+}
+
+impl Display for Chip8MathInstr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (mnemonic, args) = match self {
+            Chip8MathInstr::Assign(args) => ("LD", args),
+            Chip8MathInstr::Or(args) => ("OR", args),
+            Chip8MathInstr::And(args) => ("AND", args),
+            Chip8MathInstr::Xor(args) => ("XOR", args),
+            Chip8MathInstr::IncrBy(args) => ("ADD", args),
+            Chip8MathInstr::DecrBy(args) => ("SUB", args),
+            Chip8MathInstr::RightShift(args) => ("SHR", args),
+            Chip8MathInstr::InvDecrBy(args) => ("SUBN", args),
+            Chip8MathInstr::LeftShift(args) => ("SHL", args),
+        };
+        write!(f, "{} V{:X}, V{:X}", mnemonic, args.a, args.b)
+    }
+}
+
+impl Display for Chip8ExtraInstr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Chip8ExtraInstr::CheckDelay(args) => write!(f, "LD V{:X}, DT", args.reg),
+            Chip8ExtraInstr::WaitForKey(args) => write!(f, "LD V{:X}, K", args.reg),
+            Chip8ExtraInstr::SetDelay(args) => write!(f, "LD DT, V{:X}", args.reg),
+            Chip8ExtraInstr::SetBuzzer(args) => write!(f, "LD ST, V{:X}", args.reg),
+            Chip8ExtraInstr::IncrIndex(args) => write!(f, "ADD I, V{:X}", args.reg),
+            Chip8ExtraInstr::SetIndexHex(args) => write!(f, "LD F, V{:X}", args.reg),
+            Chip8ExtraInstr::BcdReg(args) => write!(f, "LD B, V{:X}", args.reg),
+            Chip8ExtraInstr::SaveRegRange(args) => write!(f, "LD [I], V{:X}", args.reg),
+            Chip8ExtraInstr::LoadRegRange(args) => write!(f, "LD V{:X}, [I]", args.reg),
+        }
+    }
+}
+
+impl Display for Chip8KeyConditionalInstr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Chip8KeyConditionalInstr::KeyPressed(args) => write!(f, "SKP V{:X}", args.reg),
+            Chip8KeyConditionalInstr::KeyNotPressed(args) => write!(f, "SKNP V{:X}", args.reg),
+        }
+    }
+}
+
+impl Display for Chip8Instr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Chip8Instr::Clear(_) => write!(f, "CLS"),
+            Chip8Instr::Return(_) => write!(f, "RET"),
+            Chip8Instr::Jump(args) => write!(f, "JP {:#05X}", args.imm),
+            Chip8Instr::Call(args) => write!(f, "CALL {:#05X}", args.imm),
+            Chip8Instr::SkipImmEq(args) => write!(f, "SE V{:X}, {:#04X}", args.reg, args.imm),
+            Chip8Instr::SkipImmNe(args) => write!(f, "SNE V{:X}, {:#04X}", args.reg, args.imm),
+            Chip8Instr::SkipRegEq(args) => write!(f, "SE V{:X}, V{:X}", args.a, args.b),
+            Chip8Instr::RegAssign(args) => write!(f, "LD V{:X}, {:#04X}", args.reg, args.imm),
+            Chip8Instr::RegIncr(args) => write!(f, "ADD V{:X}, {:#04X}", args.reg, args.imm),
+            Chip8Instr::Math(inner_instr) => write!(f, "{}", inner_instr),
+            Chip8Instr::SkipRegNe(args) => write!(f, "SNE V{:X}, V{:X}", args.a, args.b),
+            Chip8Instr::SetIndex(args) => write!(f, "LD I, {:#05X}", args.imm),
+            Chip8Instr::RelJump(args) => write!(f, "JP V0, {:#05X}", args.imm),
+            Chip8Instr::Random(args) => write!(f, "RND V{:X}, {:#04X}", args.reg, args.imm),
+            Chip8Instr::Draw(args) => {
+                write!(f, "DRW V{:X}, V{:X}, {:#X}", args.a, args.b, args.imm)
+            }
+            Chip8Instr::Key(inner_instr) => write!(f, "{}", inner_instr),
+            Chip8Instr::Extra(inner_instr) => write!(f, "{}", inner_instr),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use log::debug;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// A key going down or up, stamped with when the frontend saw it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chip8KeyEvent {
+    pub key: u8,
+    pub pressed: bool,
+    pub timestamp: Instant,
+}
+
+impl Chip8KeyEvent {
+    pub fn new(key: u8, pressed: bool) -> Chip8KeyEvent {
+        Chip8KeyEvent {
+            key,
+            pressed,
+            timestamp: Instant::now(),
+        }
+    }
+}
+
+/// The 16-key hex keypad. Events are queued as they arrive and only applied
+/// at frame boundaries, so the ROM sees the same input at the same point in
+/// its instruction stream no matter when the frontend got around to sending
+/// it. Each key changes at most once per frame; a tap shorter than a frame
+/// is held over, so the press is always visible for at least one frame.
+#[derive(Debug, Clone, Default)]
+pub struct Chip8Keypad {
+    down: [bool; 16],
+    pressed: [bool; 16],
+    released: [bool; 16],
+    pending: VecDeque<Chip8KeyEvent>,
+}
+
+impl Chip8Keypad {
+    pub fn new() -> Chip8Keypad {
+        Chip8Keypad::default()
+    }
+
+    pub fn queue(&mut self, event: Chip8KeyEvent) {
+        self.pending.push_back(event);
+    }
+
+    /// Clears the last frame's edges and applies queued events.
+    pub fn begin_frame(&mut self) {
+        self.pressed = [false; 16];
+        self.released = [false; 16];
+        let mut changed: [bool; 16] = [false; 16];
+        // Events for a key that already changed this frame go back on the
+        // end of the queue, in order, for the next frame.
+        for _ in 0..self.pending.len() {
+            let event: Chip8KeyEvent = match self.pending.pop_front() {
+                Some(event) => event,
+                None => break,
+            };
+            let k: usize = (event.key & 0xF) as usize;
+            if changed[k] {
+                self.pending.push_back(event);
+                continue;
+            }
+            if self.down[k] == event.pressed {
+                // Key repeat, or a release we never saw the press for.
+                continue;
+            }
+            debug!(
+                "Key {:X} {} after {:?}",
+                k,
+                match event.pressed {
+                    true => "down",
+                    false => "up",
+                },
+                event.timestamp.elapsed()
+            );
+            changed[k] = true;
+            self.down[k] = event.pressed;
+            self.pressed[k] = event.pressed;
+            self.released[k] = !event.pressed;
+        }
+    }
+
+    pub fn is_down(&self, key: u8) -> bool {
+        self.down[(key & 0xF) as usize]
+    }
+
+    /// Whether `key` went down at the start of this frame.
+    pub fn just_pressed(&self, key: u8) -> bool {
+        self.pressed[(key & 0xF) as usize]
+    }
+
+    /// Whether `key` came up at the start of this frame.
+    pub fn just_released(&self, key: u8) -> bool {
+        self.released[(key & 0xF) as usize]
+    }
+
+    /// Takes the lowest key released this frame, for `FX0A`. Each release is
+    /// only handed out once, so back-to-back waits need separate presses.
+    pub fn take_released(&mut self) -> Option<u8> {
+        let k: usize = self.released.iter().position(|r| *r)?;
+        self.released[k] = false;
+        Some(k as u8)
+    }
+
+    pub fn state(&self) -> [u8; 16] {
+        let mut keys: [u8; 16] = [0; 16];
+        for (k, d) in keys.iter_mut().zip(self.down.iter()) {
+            *k = *d as u8;
+        }
+        keys
+    }
+
+    /// Keys that came up at the start of this frame and haven't been taken
+    /// by `FX0A`, bit N for key N.
+    pub(crate) fn released_mask(&self) -> u16 {
+        self.released
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (k, r)| mask | (*r as u16) << k)
+    }
+
+    /// Puts the keypad mid-frame with `down` held and `released` just let
+    /// go, bit N for key N, without going through events. For setting up
+    /// test vectors.
+    pub(crate) fn set_state(&mut self, down: u16, released: u16) {
+        *self = Chip8Keypad::default();
+        for k in 0..16 {
+            self.down[k] = down & (1 << k) != 0;
+            self.released[k] = released & (1 << k) != 0;
+        }
+    }
+
+    /// Releases everything and drops queued events, e.g. on reset.
+    pub fn clear(&mut self) {
+        *self = Chip8Keypad::default();
+    }
+}
@@ -1,18 +1,56 @@
+/// `debug!` for the per-instruction paths, compiled out unless the
+/// `instr-log` feature is on so the inner loop never formats anything.
+macro_rules! instr_debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "instr-log")]
+        log::debug!($($arg)*);
+    };
+}
+
+pub mod analysis;
+pub mod asm;
+pub mod bench;
+pub mod blocks;
+mod decode_cache;
 mod instrs;
+pub mod keypad;
+pub mod profile;
+pub mod quirks;
+pub mod rom_db;
 mod tests;
-use bitvec::prelude::*;
+pub mod trace;
+pub mod vectors;
+use blocks::{Chip8BlockCache, Chip8Engine};
 use byteorder::{BigEndian, ByteOrder};
+use crossbeam::channel::Receiver;
+use decode_cache::Chip8DecodeCache;
 use instrs::*;
-use log::{debug, error, info};
-use rand::random;
+use keypad::{Chip8KeyEvent, Chip8Keypad};
+use log::{debug, error, info, warn};
+use profile::Chip8Profiler;
+use quirks::Chip8Quirks;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use simple_error::{simple_error, SimpleError};
 use std::collections::VecDeque;
 use std::fmt::Display;
+use std::time::{Duration, Instant};
 use std::{fs, io::Read};
+use trace::Chip8Tracer;
 
+use crate::graphics::control_adapter::{
+    Chip8Command, Chip8CommandAck, Chip8ControlMessage, ControlAdapter,
+};
+use crate::graphics::debug_adapter::{Chip8DebugRequest, Chip8DebugResponse, DebugAdapter};
 use crate::graphics::graphics_adapter::GraphicsAdapter;
 
 pub const PROGRAM_OFFSET: u16 = 0x200;
+/// The most ROM that fits between `PROGRAM_OFFSET` and the end of memory.
+pub const MAX_ROM_SIZE: usize = 4096 - PROGRAM_OFFSET as usize;
+/// Roughly the speed of the original COSMAC VIP interpreter, ~660 instructions a second.
+pub const DEFAULT_INSTRS_PER_FRAME: u32 = 11;
+pub const SAVE_SLOTS: usize = 4;
+const FRAME_DURATION: Duration = Duration::from_micros(16_667);
 
 // credit to https://tobiasvl.github.io/blog/write-a-chip-8-emulator/
 const DEFAULT_FONT_MEM: [u8; 80] = [
@@ -33,17 +71,20 @@ const DEFAULT_FONT_MEM: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
+#[derive(Clone)]
 pub struct Chip8Regs {
     index_reg: u16,
     pc: u16,
     v_regs: [u8; 16],
 }
 
+#[derive(Clone)]
 pub struct Chip8Timers {
     delay: u8,
     sound: u8,
 }
 
+#[derive(Clone)]
 pub struct Chip8Mem {
     memspace: [u8; 4096],
 }
@@ -61,6 +102,24 @@ impl Default for Chip8DisplayData {
     }
 }
 
+impl Chip8DisplayData {
+    pub fn width(&self) -> usize {
+        self._display[0].len()
+    }
+
+    pub fn height(&self) -> usize {
+        self._display.len()
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self._display[y][x] != 0
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, lit: bool) {
+        self._display[y][x] = lit as u8;
+    }
+}
+
 impl Display for Chip8DisplayData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for r in self._display.into_iter() {
@@ -77,35 +136,78 @@ impl Display for Chip8DisplayData {
     }
 }
 
+/// A copy of memory plus the addresses a debugger wants to highlight in it.
+#[derive(Clone)]
+pub struct Chip8MemorySnapshot {
+    pub mem: Vec<u8>,
+    pub pc: u16,
+    pub index_reg: u16,
+    pub stack: Vec<u16>,
+}
+
+/// Registers, timers, stack and keypad as of the last instruction boundary.
+#[derive(Clone)]
+pub struct Chip8StateSnapshot {
+    pub v_regs: [u8; 16],
+    pub index_reg: u16,
+    pub pc: u16,
+    pub opcode: u16,
+    /// Disassembly of the instruction at `pc`, which will execute next.
+    pub instr: String,
+    pub stack: Vec<u16>,
+    pub delay: u8,
+    pub sound: u8,
+    pub keys: [u8; 16],
+    pub cycles: u64,
+}
+
+/// Everything needed to pick up execution where it left off. Held by the
+/// core's save state slots.
+#[derive(Clone)]
+pub struct Chip8SaveState {
+    regs: Chip8Regs,
+    timers: Chip8Timers,
+    disp: Chip8DisplayData,
+    mem: Box<Chip8Mem>,
+    stack: VecDeque<u16>,
+    cycles: u64,
+}
+
 pub struct Chip8Core {
     regs: Chip8Regs,
     timers: Chip8Timers,
     _disp: Chip8DisplayData,
+    display_dirty: bool,
     mem: Chip8Mem,
+    decoded: Chip8DecodeCache,
+    engine: Chip8Engine,
+    blocks: Chip8BlockCache,
     stack: VecDeque<u16>,
-    keys: [u8; 16],
-    cosmac: bool,
+    keypad: Chip8Keypad,
+    quirks: Chip8Quirks,
     ga: GraphicsAdapter,
+    debug: Option<DebugAdapter>,
     running: bool,
+    shutdown: bool,
+    cycles: u64,
+    frames: u64,
+    cycle_limit: Option<u64>,
+    tracer: Option<Chip8Tracer>,
+    profiler: Option<Chip8Profiler>,
+    rom: Vec<u8>,
+    control: Option<ControlAdapter>,
+    instrs_per_frame: u32,
+    /// Instructions executed so far in the current frame.
+    frame_pos: u32,
+    fast_forward: bool,
+    save_slots: [Option<Chip8SaveState>; SAVE_SLOTS],
+    rng: StdRng,
+    seed: Option<u64>,
 }
 
 impl Chip8Core {
     pub fn new(prog_path: &str, cosmac_compat: bool, ga: &GraphicsAdapter) -> Chip8Core {
         info!("Generating Chip8 Core from fname {}", prog_path);
-        let mut mem: Chip8Mem = Chip8Mem {
-            memspace: [0; 4096],
-        };
-        mem.memspace[0..80].copy_from_slice(&DEFAULT_FONT_MEM[..]);
-        let timers: Chip8Timers = Chip8Timers { delay: 0, sound: 0 };
-        let disp: Chip8DisplayData = Chip8DisplayData {
-            _display: [[0; 64]; 32],
-        };
-        let regs: Chip8Regs = Chip8Regs {
-            index_reg: 0,
-            pc: 0x200,
-            v_regs: [0; 16],
-        };
-
         let mut infile: fs::File = match fs::File::open(prog_path) {
             Ok(f) => f,
             Err(_) => panic!("ononon"),
@@ -118,88 +220,536 @@ impl Chip8Core {
             Err(_) => error!("Failed to read file in."),
         };
 
-        while prog_vec.len() < (4096 - 0x200) {
-            prog_vec.push(0);
-        }
+        Chip8Core::from_rom(prog_vec, cosmac_compat, ga)
+    }
 
-        mem.memspace[0x200..].copy_from_slice(&prog_vec[..]);
+    /// A core with `rom` loaded, for ROMs that didn't come from a file.
+    pub fn from_rom(prog_vec: Vec<u8>, cosmac_compat: bool, ga: &GraphicsAdapter) -> Chip8Core {
+        let timers: Chip8Timers = Chip8Timers { delay: 0, sound: 0 };
+        let disp: Chip8DisplayData = Chip8DisplayData {
+            _display: [[0; 64]; 32],
+        };
+        let regs: Chip8Regs = Chip8Regs {
+            index_reg: 0,
+            pc: 0x200,
+            v_regs: [0; 16],
+        };
+
+        let mem: Chip8Mem = Chip8Core::initial_memory(&prog_vec);
 
         Chip8Core {
             regs,
             timers,
             _disp: disp,
+            display_dirty: false,
             mem,
+            decoded: Chip8DecodeCache::new(),
+            engine: Chip8Engine::default(),
+            blocks: Chip8BlockCache::new(),
             stack: VecDeque::new(),
-            keys: [0; 16],
-            cosmac: cosmac_compat,
+            keypad: Chip8Keypad::new(),
+            quirks: match cosmac_compat {
+                true => Chip8Quirks::default(),
+                false => Chip8Quirks::modern(),
+            },
             ga: ga.clone(),
+            debug: None,
             running: true,
+            shutdown: false,
+            cycles: 0,
+            frames: 0,
+            cycle_limit: None,
+            tracer: None,
+            profiler: None,
+            rom: prog_vec,
+            control: None,
+            instrs_per_frame: DEFAULT_INSTRS_PER_FRAME,
+            frame_pos: 0,
+            fast_forward: false,
+            save_slots: Default::default(),
+            rng: StdRng::from_entropy(),
+            seed: None,
+        }
+    }
+
+    /// Font at the bottom of memory and the ROM at `PROGRAM_OFFSET`.
+    fn initial_memory(rom: &[u8]) -> Chip8Mem {
+        let mut mem: Chip8Mem = Chip8Mem {
+            memspace: [0; 4096],
+        };
+        mem.memspace[0..80].copy_from_slice(&DEFAULT_FONT_MEM[..]);
+        let len: usize = rom.len().min(MAX_ROM_SIZE);
+        if len < rom.len() {
+            warn!("ROM is {} bytes, only the first {} fit", rom.len(), len);
         }
+        mem.memspace[PROGRAM_OFFSET as usize..PROGRAM_OFFSET as usize + len]
+            .copy_from_slice(&rom[..len]);
+        mem
     }
 
+    fn cycle_limit_reached(&self) -> bool {
+        match self.cycle_limit {
+            Some(limit) => self.cycles >= limit,
+            None => false,
+        }
+    }
+
+    /// Runs the core at 60 frames a second (or flat out when fast-forwarding)
+    /// until the cycle limit, if any, is reached.
     pub fn run_loop(&mut self) {
+        let mut next_frame: Instant = Instant::now();
         loop {
-            std::thread::sleep(std::time::Duration::from_micros(1));
-            while !self.ga.key_state_receiver.is_empty() {
-                if let Ok(k) = self
-                    .ga
-                    .key_state_receiver
-                    .recv_timeout(std::time::Duration::from_micros(1))
-                {
-                    self.keys = k;
-                    debug!("Got new keys {:?}", k);
-                }
+            if self.cycle_limit_reached() {
+                info!("Stopping after {} cycles", self.cycles);
+                return;
+            }
+            while let Ok(event) = self.ga.key_event_receiver.try_recv() {
+                self.keypad.queue(event);
+            }
+            self.handle_debug_requests();
+            self.handle_commands();
+            if self.shutdown {
+                info!("Shutting down after {} cycles", self.cycles);
+                return;
             }
             if self.running {
-                match self.tick() {
-                    Ok(_) => {}
-                    Err(e) => {
-                        error!("Failed to tick with err {}", e);
-                    }
+                self.run_frame();
+            }
+
+            if self.fast_forward && self.running {
+                next_frame = Instant::now();
+            } else {
+                next_frame += FRAME_DURATION;
+                let now: Instant = Instant::now();
+                if next_frame > now {
+                    std::thread::sleep(next_frame - now);
+                } else {
+                    // Fell behind, e.g. after a debugger stall; don't try to catch up.
+                    next_frame = now;
+                }
+            }
+        }
+    }
+
+    /// Applies queued key events, executes one frame's worth of
+    /// instructions, then ticks the timers.
+    pub fn run_frame(&mut self) {
+        if self.frame_pos == 0 {
+            self.keypad.begin_frame();
+        }
+        match self.engine {
+            // Tracing and profiling see every instruction, so interpret
+            Chip8Engine::Compiled if self.tracer.is_none() && self.profiler.is_none() => {
+                self.run_blocks()
+            }
+            _ => {
+                while self.frame_pos < self.instrs_per_frame && !self.cycle_limit_reached() {
+                    self.tick_in_frame();
+                }
+            }
+        }
+        self.end_frame();
+    }
+
+    /// Interprets one instruction as part of the current frame.
+    #[inline]
+    fn tick_in_frame(&mut self) {
+        if let Err(e) = self.tick() {
+            error!("Failed to tick with err {}", e);
+        }
+        self.frame_pos += 1;
+    }
+
+    /// Executes one instruction, finishing the frame (timers, display,
+    /// vblank) if it was the frame's last, so stepping through a ROM an
+    /// instruction at a time runs it exactly as `run_frame` would.
+    pub fn step(&mut self) -> Result<(), SimpleError> {
+        if self.frame_pos == 0 {
+            self.keypad.begin_frame();
+        }
+        let result = self.tick();
+        self.frame_pos += 1;
+        if self.frame_pos >= self.instrs_per_frame {
+            self.end_frame();
+        }
+        result
+    }
+
+    fn end_frame(&mut self) {
+        self.frame_pos = 0;
+        self.timers.delay = self.timers.delay.saturating_sub(1);
+        self.timers.sound = self.timers.sound.saturating_sub(1);
+        if self.display_dirty {
+            self.publish_display();
+        }
+        self.ga.set_sound_active(self.timers.sound > 0);
+        self.frames += 1;
+        self.ga.signal_vblank(self.frames);
+    }
+
+    pub fn set_instrs_per_frame(&mut self, instrs: u32) {
+        self.instrs_per_frame = instrs;
+    }
+
+    /// Queues a key press or release for the start of the next frame, just
+    /// as the frontend's arrive. For driving the core without a frontend.
+    pub fn queue_key(&mut self, event: Chip8KeyEvent) {
+        self.keypad.queue(event);
+    }
+
+    pub fn set_fast_forward(&mut self, fast_forward: bool) {
+        self.fast_forward = fast_forward;
+    }
+
+    /// Makes `CXNN` draw from a generator seeded with `seed`, so runs can be
+    /// repeated exactly. Reset starts the sequence again.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn set_quirks(&mut self, quirks: Chip8Quirks) {
+        self.quirks = quirks;
+        // Compiled blocks have the old quirks baked in
+        self.blocks.clear();
+    }
+
+    /// Picks how `run_frame` runs instructions. `step` and `tick` always
+    /// interpret; both engines give exactly the same results.
+    pub fn set_engine(&mut self, engine: Chip8Engine) {
+        self.engine = engine;
+        self.blocks.clear();
+    }
+
+    pub fn engine(&self) -> Chip8Engine {
+        self.engine
+    }
+
+    pub fn quirks(&self) -> Chip8Quirks {
+        self.quirks
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.regs.pc
+    }
+
+    /// Instructions executed since the last reset.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    pub fn attach_control(&mut self, ca: &ControlAdapter) {
+        self.control = Some(ca.clone());
+    }
+
+    /// Reloads the ROM and clears everything else, as if just powered on.
+    pub fn reset(&mut self) {
+        info!("Resetting");
+        self.mem = Chip8Core::initial_memory(&self.rom);
+        self.decoded.clear();
+        self.blocks.clear();
+        self.regs = Chip8Regs {
+            index_reg: 0,
+            pc: PROGRAM_OFFSET,
+            v_regs: [0; 16],
+        };
+        self.timers = Chip8Timers { delay: 0, sound: 0 };
+        self.stack.clear();
+        self.keypad.clear();
+        self.cycles = 0;
+        self.frame_pos = 0;
+        if let Some(seed) = self.seed {
+            self.rng = StdRng::seed_from_u64(seed);
+        }
+        self._disp = Chip8DisplayData::default();
+        self.publish_display();
+    }
+
+    pub fn save_state(&self) -> Chip8SaveState {
+        Chip8SaveState {
+            regs: self.regs.clone(),
+            timers: self.timers.clone(),
+            disp: self._disp,
+            mem: Box::new(self.mem.clone()),
+            stack: self.stack.clone(),
+            cycles: self.cycles,
+        }
+    }
+
+    pub fn load_state(&mut self, state: &Chip8SaveState) {
+        self.regs = state.regs.clone();
+        self.timers = state.timers.clone();
+        self._disp = state.disp;
+        self.mem = (*state.mem).clone();
+        self.decoded.clear();
+        self.blocks.clear();
+        self.stack = state.stack.clone();
+        self.cycles = state.cycles;
+        self.frame_pos = 0;
+        self.publish_display();
+    }
+
+    fn handle_commands(&mut self) {
+        let commands: Receiver<Chip8ControlMessage> = match &self.control {
+            Some(ca) => ca.command_receiver.clone(),
+            None => return,
+        };
+        while let Ok(message) = commands.try_recv() {
+            debug!("Got command {:?}", message.command);
+            let result: Result<(), String> = self
+                .execute_command(message.command)
+                .map_err(|e| e.to_string());
+            if let Err(e) = &result {
+                warn!("Command failed: {}", e);
+            }
+            self.ga.set_paused(!self.running);
+            if let Some(reply) = message.reply {
+                let ack = Chip8CommandAck {
+                    result,
+                    running: self.running,
+                    pc: self.regs.pc,
+                    cycles: self.cycles,
                 };
+                // Whoever asked may have given up waiting; that's fine.
+                let _ = reply.send(ack);
+            }
+        }
+    }
+
+    fn execute_command(&mut self, command: Chip8Command) -> Result<(), SimpleError> {
+        match command {
+            Chip8Command::Pause => self.running = false,
+            Chip8Command::Resume => self.running = true,
+            Chip8Command::Reset => self.reset(),
+            Chip8Command::FrameAdvance => {
+                if !self.running {
+                    self.run_frame();
+                }
+            }
+            Chip8Command::FastForward(on) => self.fast_forward = on,
+            Chip8Command::SaveState(slot) => {
+                if slot as usize >= SAVE_SLOTS {
+                    return Err(simple_error!("No save state slot {}", slot));
+                }
+                self.save_slots[slot as usize] = Some(self.save_state());
+                info!("Saved state to slot {}", slot);
+            }
+            Chip8Command::LoadState(slot) => {
+                match self.save_slots.get(slot as usize).cloned().flatten() {
+                    Some(state) => {
+                        self.load_state(&state);
+                        info!("Loaded state from slot {}", slot);
+                    }
+                    None => return Err(simple_error!("Save state slot {} is empty", slot)),
+                }
+            }
+            Chip8Command::Step(count) => {
+                self.running = false;
+                for _ in 0..count {
+                    self.step()?;
+                }
+                if self.display_dirty {
+                    self.publish_display();
+                }
             }
+            Chip8Command::LoadRom(rom) => {
+                if rom.len() > MAX_ROM_SIZE {
+                    return Err(simple_error!(
+                        "ROM is {} bytes, the most that fits is {}",
+                        rom.len(),
+                        MAX_ROM_SIZE
+                    ));
+                }
+                self.rom = rom;
+                self.save_slots = Default::default();
+                self.reset();
+            }
+            Chip8Command::SetQuirks(quirks) => self.set_quirks(quirks),
+            Chip8Command::SetInstrsPerFrame(instrs) => self.instrs_per_frame = instrs,
+            Chip8Command::Shutdown => self.shutdown = true,
         }
+        Ok(())
     }
+
+    /// Starts writing a per-instruction trace. Replaces any existing tracer.
+    pub fn set_tracer(&mut self, tracer: Chip8Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    pub fn attach_debugger(&mut self, da: &DebugAdapter) {
+        self.debug = Some(da.clone());
+    }
+
+    pub fn memory_snapshot(&self) -> Chip8MemorySnapshot {
+        Chip8MemorySnapshot {
+            mem: self.mem.memspace.to_vec(),
+            pc: self.regs.pc,
+            index_reg: self.regs.index_reg,
+            stack: self.stack.iter().copied().collect(),
+        }
+    }
+
+    pub fn state_snapshot(&self) -> Chip8StateSnapshot {
+        let opcode: u16 = self.fetch().unwrap_or(0);
+        let instr: String = match self.fetch().and_then(Chip8Instr::from_u16) {
+            Ok(i) => i.to_string(),
+            Err(_) => String::from("???"),
+        };
+        Chip8StateSnapshot {
+            v_regs: self.regs.v_regs,
+            index_reg: self.regs.index_reg,
+            pc: self.regs.pc,
+            opcode,
+            instr,
+            stack: self.stack.iter().copied().collect(),
+            delay: self.timers.delay,
+            sound: self.timers.sound,
+            keys: self.keypad.state(),
+            cycles: self.cycles,
+        }
+    }
+
+    fn handle_debug_requests(&mut self) {
+        // Taken for the loop, since writes go through `store`
+        let da: DebugAdapter = match self.debug.take() {
+            Some(da) => da,
+            None => return,
+        };
+        while let Ok(req) = da.request_receiver.try_recv() {
+            match req {
+                Chip8DebugRequest::ReadMemory => {
+                    let snapshot = Box::new(self.memory_snapshot());
+                    if let Err(e) = da
+                        .response_sender
+                        .send(Chip8DebugResponse::Memory(snapshot))
+                    {
+                        error!("ERR: {} ", e);
+                    }
+                }
+                Chip8DebugRequest::WriteMemory { addr, val } => {
+                    debug!("Debugger wrote {:X} to {:X}", val, addr);
+                    self.store((addr & 0xFFF) as usize, val);
+                }
+                Chip8DebugRequest::ReadState => {
+                    let snapshot = Box::new(self.state_snapshot());
+                    if let Err(e) = da.response_sender.send(Chip8DebugResponse::State(snapshot)) {
+                        error!("ERR: {} ", e);
+                    }
+                }
+            }
+        }
+        self.debug = Some(da);
+    }
+
+    /// Makes `run_loop` return once this many instructions have executed.
+    pub fn set_cycle_limit(&mut self, limit: u64) {
+        self.cycle_limit = Some(limit);
+    }
+
+    pub fn enable_profiler(&mut self) {
+        self.profiler = Some(Chip8Profiler::new());
+    }
+
+    pub fn profiler(&self) -> Option<&Chip8Profiler> {
+        self.profiler.as_ref()
+    }
+
     pub fn tick(&mut self) -> Result<(), SimpleError> {
-        let instr: Chip8Instr = self.fetch_decode()?;
+        let (opcode, instr) = self.fetch_decode()?;
+        if let Some(tracer) = self.tracer.as_mut() {
+            let sp: usize = self.stack.len();
+            if let Err(e) = tracer.record(self.cycles, opcode, &instr, &self.regs, &self.timers, sp)
+            {
+                error!("Failed to write trace, disabling it: {}", e);
+                self.tracer = None;
+            }
+        }
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(self.cycles, self.regs.pc, &instr);
+        }
         self.execute(instr)?;
+        self.cycles += 1;
         Ok(())
     }
 
-    fn fetch_decode(&mut self) -> Result<Chip8Instr, SimpleError> {
+    fn fetch(&self) -> Result<u16, SimpleError> {
         let fetch_addr: usize = self.regs.pc as usize;
-        let instr: u16 = BigEndian::read_u16(&self.mem.memspace[fetch_addr..fetch_addr + 2]);
-        Chip8Instr::from_u16(instr)
+        match self.mem.memspace.get(fetch_addr..fetch_addr + 2) {
+            Some(bytes) => Ok(BigEndian::read_u16(bytes)),
+            None => Err(simple_error!(
+                "PC {:#05X} is past the end of memory",
+                self.regs.pc
+            )),
+        }
+    }
+
+    /// The opcode at `pc` and its decoding, from the cache if it's been run
+    /// before.
+    fn fetch_decode(&mut self) -> Result<(u16, Chip8Instr), SimpleError> {
+        if let Some(decoded) = self.decoded.get(self.regs.pc) {
+            return Ok(decoded);
+        }
+        let opcode: u16 = self.fetch()?;
+        let instr: Chip8Instr = Chip8Instr::from_u16(opcode)?;
+        self.decoded.insert(self.regs.pc, opcode, instr);
+        Ok((opcode, instr))
+    }
+
+    /// Writes a byte of memory. All stores go through here so neither the
+    /// decode cache nor the compiled blocks hold a stale instruction.
+    #[inline]
+    fn store(&mut self, addr: usize, val: u8) {
+        self.mem.memspace[addr] = val;
+        self.decoded.invalidate(addr);
+        self.blocks.invalidate(addr);
+    }
+
+    /// The `len` bytes of memory from I on, or an error if they'd run past
+    /// the end. Checked before touching anything, so a failed instruction
+    /// has no effect besides moving the PC on.
+    fn index_range(&self, len: usize) -> Result<std::ops::Range<usize>, SimpleError> {
+        let start: usize = self.regs.index_reg as usize;
+        if start + len > self.mem.memspace.len() {
+            return Err(simple_error!(
+                "{} bytes from I = {:#05X} run past the end of memory",
+                len,
+                start
+            ));
+        }
+        Ok(start..start + len)
     }
 
     fn clear_display(&mut self) -> Result<(), SimpleError> {
         self._disp._display = [[0; 64]; 32];
+        self.display_dirty = true;
         Ok(())
     }
 
     fn draw(&mut self, x: u8, y: u8, height: u8) -> Result<(), SimpleError> {
         let x = (x % (self._disp._display[0].len() as u8)) as usize;
         let y = (y % (self._disp._display.len() as u8)) as usize;
+        let sprite = self.index_range(height as usize)?;
 
         let mut collision: bool = false;
-        for row in 0..height {
-            let offset: usize = self.regs.index_reg as usize + row as usize;
-            let val: u8 = self.mem.memspace[offset];
-            let hots: BitVec<Msb0, u8> = BitVec::<Msb0, u8>::from_element(val);
-            debug!("BV: {:?} val: {:X} offset: {}", row, val, offset);
-            for (col_offset, b) in hots.into_iter().enumerate() {
+        for row in 0..height as usize {
+            let row_val: usize = y + row;
+            if row_val >= self._disp._display.len() {
+                break;
+            }
+            let val: u8 = self.mem.memspace[sprite.start + row];
+            let active_row = &mut self._disp._display[row_val];
+            for col_offset in 0..8 {
                 let col_val: usize = x + col_offset;
-                let row_val: usize = y + (row as usize);
-                if row_val < self._disp._display.len() {
-                    let active_row = &mut self._disp._display[row_val];
-                    if col_val < active_row.len() {
-                        if active_row[col_val] == 1 {
-                            collision = true;
-                        }
-                        if b {
-                            active_row[col_val] ^= 1;
-                        }
-                    }
+                if col_val >= active_row.len() {
+                    break;
+                }
+                if val & (0x80 >> col_offset) != 0 {
+                    collision |= active_row[col_val] == 1;
+                    active_row[col_val] ^= 1;
                 }
             }
         }
@@ -208,16 +758,20 @@ impl Chip8Core {
         } else {
             self.set_reg(0xF, 0)?;
         }
-        match self.ga.display_state_sender.send(self._disp) {
-            Ok(_) => {}
-            Err(e) => {
-                error!("ERR: {} ", e);
-            }
-        }
+        self.display_dirty = true;
 
         Ok(())
     }
 
+    fn publish_display(&mut self) {
+        self.ga.publish(&self._disp);
+        self.display_dirty = false;
+    }
+
+    pub fn display(&self) -> &Chip8DisplayData {
+        &self._disp
+    }
+
     pub fn dbg_display(&self) {
         print!("{}", self._disp);
     }
@@ -241,10 +795,10 @@ impl Chip8Core {
         }
     }
 
-    fn get_key(&mut self, key: u8) -> Result<u8, SimpleError> {
-        match self.keys.get(key as usize) {
-            Some(v) => Ok(*v),
-            None => Err(simple_error!("Failed to get key: {:?}", key)),
+    fn get_key(&mut self, key: u8) -> Result<bool, SimpleError> {
+        match key {
+            0..=0xF => Ok(self.keypad.is_down(key)),
+            _ => Err(simple_error!("Failed to get key: {:?}", key)),
         }
     }
 
@@ -259,7 +813,7 @@ impl Chip8Core {
 
     fn execute(&mut self, instr: Chip8Instr) -> Result<(), SimpleError> {
         self.regs.pc += 2;
-        debug!("Attempting to execute instruction: {:?}", instr);
+        instr_debug!("Attempting to execute instruction: {:?}", instr);
         match instr {
             Chip8Instr::Clear(_) => self.clear_display(),
             Chip8Instr::Return(_) => {
@@ -279,9 +833,9 @@ impl Chip8Core {
             Chip8Instr::SkipImmEq(args) => {
                 let a: u8 = self.get_reg(args.reg)?;
                 let b: u8 = args.imm;
-                debug!("{} {}", a, b);
+                instr_debug!("{} {}", a, b);
                 if a == b {
-                    debug!("Skipping bc {} == {}", a, b);
+                    instr_debug!("Skipping bc {} == {}", a, b);
                     self.regs.pc += 2;
                 }
                 Ok(())
@@ -290,7 +844,7 @@ impl Chip8Core {
                 let a: u8 = self.get_reg(args.reg)?;
                 let b: u8 = args.imm;
                 if a != b {
-                    debug!("Skipping {} != {}", a, b);
+                    instr_debug!("Skipping {} != {}", a, b);
                     self.regs.pc += 2;
                 }
                 Ok(())
@@ -300,7 +854,7 @@ impl Chip8Core {
                 let b: u8 = self.get_reg(args.b)?;
 
                 if a == b {
-                    debug!("Skipping {} == {}", a, b);
+                    instr_debug!("Skipping {} == {}", a, b);
                     self.regs.pc += 2;
                 }
 
@@ -322,99 +876,75 @@ impl Chip8Core {
                     let b: u8 = self.get_reg(args.b)?;
 
                     a |= b;
-                    self.set_reg(args.a, a)
+                    self.set_reg(args.a, a)?;
+                    if self.quirks.logic_resets_vf {
+                        self.set_reg(0xF, 0)?;
+                    }
+                    Ok(())
                 }
                 Chip8MathInstr::And(args) => {
                     let mut a: u8 = self.get_reg(args.a)?;
                     let b: u8 = self.get_reg(args.b)?;
 
                     a &= b;
-                    self.set_reg(args.a, a)
+                    self.set_reg(args.a, a)?;
+                    if self.quirks.logic_resets_vf {
+                        self.set_reg(0xF, 0)?;
+                    }
+                    Ok(())
                 }
                 Chip8MathInstr::Xor(args) => {
                     let mut a: u8 = self.get_reg(args.a)?;
                     let b: u8 = self.get_reg(args.b)?;
 
                     a ^= b;
-                    self.set_reg(args.a, a)
+                    self.set_reg(args.a, a)?;
+                    if self.quirks.logic_resets_vf {
+                        self.set_reg(0xF, 0)?;
+                    }
+                    Ok(())
                 }
+                // The flag is written after the result, so it wins when X is F
                 Chip8MathInstr::IncrBy(args) => {
-                    let mut a: u8 = self.get_reg(args.a)?;
+                    let a: u8 = self.get_reg(args.a)?;
                     let b: u8 = self.get_reg(args.b)?;
-                    a = a.wrapping_add(b);
-                    if a < b {
-                        self.set_reg(0xF, 1)?;
-                    } else {
-                        self.set_reg(0xF, 0)?;
-                    }
-                    self.set_reg(args.a, a)
+                    let (sum, carry) = a.overflowing_add(b);
+                    self.set_reg(args.a, sum)?;
+                    self.set_reg(0xF, carry as u8)
                 }
                 Chip8MathInstr::DecrBy(args) => {
-                    let mut a: u8 = self.get_reg(args.a)?;
+                    let a: u8 = self.get_reg(args.a)?;
                     let b: u8 = self.get_reg(args.b)?;
-
-                    if a > b {
-                        self.set_reg(0xF, 1)?;
-                    } else {
-                        self.set_reg(0xF, 0)?;
-                    }
-
-                    a = a.wrapping_sub(b);
-                    self.set_reg(args.a, a)
+                    self.set_reg(args.a, a.wrapping_sub(b))?;
+                    // VF is 1 when there's no borrow, equal values included
+                    self.set_reg(0xF, (a >= b) as u8)
                 }
                 Chip8MathInstr::RightShift(args) => {
-                    let mut a: u8 = self.get_reg(args.a)?;
+                    let a: u8 = self.get_reg(args.a)?;
                     let b: u8 = self.get_reg(args.b)?;
-                    let target: u8;
-                    if self.cosmac {
-                        target = b
-                    } else {
-                        target = a
-                    }
-                    if target & 0x01 == 1 {
-                        self.set_reg(0xF, 1)?;
-                    } else {
-                        self.set_reg(0xF, 0)?;
-                    }
-
-                    a = target >> 1;
-                    self.set_reg(args.a, a)
+                    let target: u8 = if self.quirks.shift_uses_vy { b } else { a };
+                    self.set_reg(args.a, target >> 1)?;
+                    self.set_reg(0xF, target & 0x01)
                 }
                 Chip8MathInstr::InvDecrBy(args) => {
-                    let mut a: u8 = self.get_reg(args.a)?;
+                    let a: u8 = self.get_reg(args.a)?;
                     let b: u8 = self.get_reg(args.b)?;
-                    if b > a {
-                        self.set_reg(0xF, 1)?;
-                    } else {
-                        self.set_reg(0xF, 0)?;
-                    }
-
-                    a = b.wrapping_sub(a);
-                    self.set_reg(args.a, a)
+                    self.set_reg(args.a, b.wrapping_sub(a))?;
+                    self.set_reg(0xF, (b >= a) as u8)
                 }
                 Chip8MathInstr::LeftShift(args) => {
-                    let mut a: u8 = self.get_reg(args.a)?;
+                    let a: u8 = self.get_reg(args.a)?;
                     let b: u8 = self.get_reg(args.b)?;
-                    let target: u8;
-                    if self.cosmac {
-                        target = b;
-                    } else {
-                        target = a;
-                    }
-                    if target & 0x80 == 0x80 {
-                        self.set_reg(0xF, 1)?;
-                    } else {
-                        self.set_reg(0xF, 0)?;
-                    }
-                    a = target << 1;
-                    self.set_reg(args.a, a)
+                    let target: u8 = if self.quirks.shift_uses_vy { b } else { a };
+                    self.set_reg(args.a, target << 1)?;
+                    self.set_reg(0xF, target >> 7)
                 }
             },
             Chip8Instr::SkipRegNe(args) => {
                 let a: u8 = self.get_reg(args.a)?;
                 let b: u8 = self.get_reg(args.b)?;
                 if a != b {
-                    debug!("Skipping bc {} != {}", a, b);
+                    instr_debug!("Skipping bc {} != {}", a, b);
                     self.regs.pc += 2;
                 }
                 Ok(())
@@ -424,20 +954,17 @@ impl Chip8Core {
                 Ok(())
             }
             Chip8Instr::RelJump(args) => {
-                let addr: u16;
-                if self.cosmac {
-                    let a: u8 = self.get_reg(0)?;
-                    addr = args.imm + a as u16;
+                let reg: u8 = if self.quirks.jump_uses_v0 {
+                    0
                 } else {
-                    let reg: u8 = ((args.imm & 0xF00) >> 8) as u8;
-                    let a: u8 = self.get_reg(reg)?;
-                    addr = args.imm + a as u16;
-                }
-                self.regs.pc = addr;
+                    ((args.imm & 0xF00) >> 8) as u8
+                };
+                let a: u8 = self.get_reg(reg)?;
+                self.regs.pc = args.imm + a as u16;
                 Ok(())
             }
             Chip8Instr::Random(args) => {
-                let mut base_rand: u8 = random();
+                let mut base_rand: u8 = self.rng.gen();
                 base_rand &= args.imm;
                 self.set_reg(args.reg, base_rand)?;
                 Ok(())
@@ -450,16 +977,14 @@ impl Chip8Core {
             Chip8Instr::Key(inner_instr) => match inner_instr {
                 Chip8KeyConditionalInstr::KeyPressed(args) => {
                     let k: u8 = self.get_reg(args.reg)?;
-                    let key = self.get_key(k)?;
-                    if key == 1 {
+                    if self.get_key(k)? {
                         self.regs.pc += 2;
                     }
                     Ok(())
                 }
                 Chip8KeyConditionalInstr::KeyNotPressed(args) => {
                     let k: u8 = self.get_reg(args.reg)?;
-                    let key = self.get_key(k)?;
-                    if key != 1 {
+                    if !self.get_key(k)? {
                         self.regs.pc += 2;
                     }
                     Ok(())
@@ -467,14 +992,14 @@ impl Chip8Core {
             },
             Chip8Instr::Extra(inner_instr) => match inner_instr {
                 Chip8ExtraInstr::CheckDelay(args) => self.set_reg(args.reg, self.timers.delay),
-                Chip8ExtraInstr::WaitForKey(args) => {
-                    let k: u8 = self.get_reg(args.reg)?;
-                    let key = self.get_key(k)?;
-                    if key != 1 {
+                // The COSMAC VIP waits for a key to be pressed and released
+                Chip8ExtraInstr::WaitForKey(args) => match self.keypad.take_released() {
+                    Some(k) => self.set_reg(args.reg, k),
+                    None => {
                         self.regs.pc -= 2;
+                        Ok(())
                     }
-                    Ok(())
-                }
+                },
                 Chip8ExtraInstr::SetDelay(args) => {
                     let val: u8 = self.get_reg(args.reg)?;
                     self.timers.delay = val;
@@ -485,54 +1010,54 @@ impl Chip8Core {
                     self.timers.sound = val;
                     Ok(())
                 }
+                // VF is left alone, as on the COSMAC VIP
                 Chip8ExtraInstr::IncrIndex(args) => {
                     let a: u8 = self.get_reg(args.reg)?;
-                    let start: u16 = self.regs.index_reg;
                     self.regs.index_reg = self.regs.index_reg.wrapping_add(a as u16);
-                    if self.regs.index_reg < start {
-                        self.set_reg(0xF, 1)?;
-                    } else {
-                        self.set_reg(0xF, 0)?;
-                    }
                     Ok(())
                 }
                 Chip8ExtraInstr::SetIndexHex(args) => {
-                    let val: u8 = self.get_reg(args.reg)?;
-                    self.regs.index_reg = (val * 5) as u16;
+                    // Only the low nibble picks a character
+                    let val: u8 = self.get_reg(args.reg)? & 0xF;
+                    self.regs.index_reg = val as u16 * 5;
                     Ok(())
                 }
                 Chip8ExtraInstr::BcdReg(args) => {
                     let val: u8 = self.get_reg(args.reg)?;
-                    let origin: usize = self.regs.index_reg as usize;
+                    let origin: usize = self.index_range(3)?.start;
                     let hunds = val / 100;
                     let tens: u8 = (val % 100) / 10;
                     let ones: u8 = val % 10;
-                    self.mem.memspace[origin] = hunds;
-                    self.mem.memspace[origin + 1] = tens;
-                    self.mem.memspace[origin + 2] = ones;
+                    self.store(origin, hunds);
+                    self.store(origin + 1, tens);
+                    self.store(origin + 2, ones);
 
-                    debug!("BCD of {} is {} {} {}", val, hunds, tens, ones);
+                    instr_debug!("BCD of {} is {} {} {}", val, hunds, tens, ones);
                     Ok(())
                 }
                 Chip8ExtraInstr::SaveRegRange(args) => {
-                    let mut addr: u16 = self.regs.index_reg;
                     let end: u8 = args.reg;
-                    for i in 0..end + 1 {
+                    self.index_range(end as usize + 1)?;
+                    for (addr, i) in (self.regs.index_reg..).zip(0..end + 1) {
                         let val: u8 = self.get_reg(i)?;
-                        self.mem.memspace[addr as usize] = val;
-                        debug!("Saving {} from reg {} to {}", val, i, addr);
-                        addr += 1
+                        self.store(addr as usize, val);
+                        instr_debug!("Saving {} from reg {} to {}", val, i, addr);
+                    }
+                    if self.quirks.load_store_increments_i {
+                        self.regs.index_reg += end as u16 + 1;
                     }
                     Ok(())
                 }
                 Chip8ExtraInstr::LoadRegRange(args) => {
-                    let mut addr: u16 = self.regs.index_reg;
                     let end: u8 = args.reg;
-                    for i in 0..end + 1 {
+                    self.index_range(end as usize + 1)?;
+                    for (addr, i) in (self.regs.index_reg..).zip(0..end + 1) {
                         let val: u8 = self.mem.memspace[addr as usize];
                         self.set_reg(i, val)?;
-                        debug!("Loading {} to reg {} from {}", val, i, addr);
-                        addr += 1
+                        instr_debug!("Loading {} to reg {} from {}", val, i, addr);
+                    }
+                    if self.quirks.load_store_increments_i {
+                        self.regs.index_reg += end as u16 + 1;
                     }
                     Ok(())
                 }
@@ -558,15 +1083,34 @@ impl Chip8Core {
         };
 
         Chip8Core {
-            regs: regs,
-            timers: timers,
+            regs,
+            timers,
             _disp: disp,
-            mem: mem,
+            display_dirty: false,
+            mem,
+            decoded: Chip8DecodeCache::new(),
+            engine: Chip8Engine::default(),
+            blocks: Chip8BlockCache::new(),
             stack: VecDeque::new(),
-            keys: [0; 16],
-            cosmac: true,
+            keypad: Chip8Keypad::new(),
+            quirks: Chip8Quirks::default(),
             ga: GraphicsAdapter::new(),
+            debug: None,
             running: true,
+            shutdown: false,
+            cycles: 0,
+            frames: 0,
+            cycle_limit: None,
+            tracer: None,
+            profiler: None,
+            rom: Vec::new(),
+            control: None,
+            instrs_per_frame: DEFAULT_INSTRS_PER_FRAME,
+            frame_pos: 0,
+            fast_forward: false,
+            save_slots: Default::default(),
+            rng: StdRng::from_entropy(),
+            seed: None,
         }
     }
 }
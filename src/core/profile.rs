@@ -0,0 +1,217 @@
+use super::instrs::*;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+const MEM_SIZE: usize = 4096;
+const REPORT_TOP_N: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Chip8SubroutineStats {
+    pub calls: u64,
+    /// Instructions executed between each `CALL` and its matching `RET`,
+    /// including everything the subroutine itself called.
+    pub cycles: u64,
+}
+
+/// Counts where a ROM spends its instructions: per address, per opcode
+/// pattern, per subroutine and per loop (any backwards `1NNN`).
+pub struct Chip8Profiler {
+    total: u64,
+    addr_counts: Vec<u64>,
+    addr_instrs: Vec<Option<Chip8Instr>>,
+    pattern_counts: BTreeMap<&'static str, u64>,
+    subroutines: BTreeMap<u16, Chip8SubroutineStats>,
+    call_stack: Vec<u16>,
+    call_entry_cycles: Vec<u64>,
+    loops: BTreeMap<(u16, u16), u64>,
+    folded: HashMap<Vec<u16>, u64>,
+}
+
+impl Default for Chip8Profiler {
+    fn default() -> Self {
+        Chip8Profiler::new()
+    }
+}
+
+impl Chip8Profiler {
+    pub fn new() -> Chip8Profiler {
+        Chip8Profiler {
+            total: 0,
+            addr_counts: vec![0; MEM_SIZE],
+            addr_instrs: vec![None; MEM_SIZE],
+            pattern_counts: BTreeMap::new(),
+            subroutines: BTreeMap::new(),
+            call_stack: Vec::new(),
+            call_entry_cycles: Vec::new(),
+            loops: BTreeMap::new(),
+            folded: HashMap::new(),
+        }
+    }
+
+    /// Records `instr` at `pc` before it executes.
+    pub(super) fn record(&mut self, cycle: u64, pc: u16, instr: &Chip8Instr) {
+        self.total += 1;
+        self.addr_counts[pc as usize] += 1;
+        self.addr_instrs[pc as usize] = Some(*instr);
+        *self.pattern_counts.entry(instr.pattern()).or_insert(0) += 1;
+        match self.folded.get_mut(&self.call_stack[..]) {
+            Some(count) => *count += 1,
+            None => {
+                self.folded.insert(self.call_stack.clone(), 1);
+            }
+        }
+
+        match instr {
+            Chip8Instr::Call(args) => {
+                self.call_stack.push(args.imm);
+                self.call_entry_cycles.push(cycle);
+                self.subroutines.entry(args.imm).or_default().calls += 1;
+            }
+            Chip8Instr::Return(_) => {
+                if let (Some(target), Some(entry)) =
+                    (self.call_stack.pop(), self.call_entry_cycles.pop())
+                {
+                    self.subroutines.entry(target).or_default().cycles += cycle - entry;
+                }
+            }
+            Chip8Instr::Jump(args) if args.imm <= pc => {
+                *self.loops.entry((args.imm, pc)).or_insert(0) += 1;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn count_at(&self, addr: u16) -> u64 {
+        match self.addr_counts.get(addr as usize) {
+            Some(c) => *c,
+            None => 0,
+        }
+    }
+
+    pub fn pattern_count(&self, pattern: &str) -> u64 {
+        match self.pattern_counts.get(pattern) {
+            Some(c) => *c,
+            None => 0,
+        }
+    }
+
+    pub fn subroutine(&self, addr: u16) -> Option<Chip8SubroutineStats> {
+        self.subroutines.get(&addr).copied()
+    }
+
+    fn percent(&self, count: u64) -> f64 {
+        match self.total {
+            0 => 0.0,
+            t => 100.0 * count as f64 / t as f64,
+        }
+    }
+
+    fn instr_str(&self, addr: u16) -> String {
+        match self.addr_instrs[addr as usize] {
+            Some(instr) => instr.to_string(),
+            None => String::from("???"),
+        }
+    }
+
+    /// Instructions executed in `start..=end`, i.e. inside a loop body.
+    fn range_count(&self, start: u16, end: u16) -> u64 {
+        let end: usize = (end as usize + 2).min(MEM_SIZE);
+        self.addr_counts[start as usize..end].iter().sum()
+    }
+
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "Executed {} instructions", self.total);
+
+        let _ = writeln!(out, "\nHot addresses:");
+        let mut addrs: Vec<(u16, u64)> = self
+            .addr_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c > 0)
+            .map(|(a, c)| (a as u16, *c))
+            .collect();
+        addrs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        for (addr, count) in addrs.iter().take(REPORT_TOP_N) {
+            let _ = writeln!(
+                out,
+                "  {:#05X}  {:>12}  {:>6.2}%  {}",
+                addr,
+                count,
+                self.percent(*count),
+                self.instr_str(*addr)
+            );
+        }
+
+        let _ = writeln!(out, "\nInstruction mix:");
+        let mut patterns: Vec<(&&str, &u64)> = self.pattern_counts.iter().collect();
+        patterns.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (pattern, count) in patterns {
+            let _ = writeln!(
+                out,
+                "  {}  {:>12}  {:>6.2}%",
+                pattern,
+                count,
+                self.percent(*count)
+            );
+        }
+
+        let _ = writeln!(out, "\nSubroutines (inclusive):");
+        let mut subs: Vec<(&u16, &Chip8SubroutineStats)> = self.subroutines.iter().collect();
+        subs.sort_by(|a, b| b.1.cycles.cmp(&a.1.cycles).then(a.0.cmp(b.0)));
+        for (addr, stats) in subs.iter().take(REPORT_TOP_N) {
+            let avg: f64 = match stats.calls {
+                0 => 0.0,
+                c => stats.cycles as f64 / c as f64,
+            };
+            let _ = writeln!(
+                out,
+                "  {:#05X}  {:>8} calls  {:>12} instrs  {:>6.2}%  {:>8.1} avg",
+                addr,
+                stats.calls,
+                stats.cycles,
+                self.percent(stats.cycles),
+                avg
+            );
+        }
+
+        let _ = writeln!(out, "\nHot loops:");
+        let mut loops: Vec<(u16, u16, u64, u64)> = self
+            .loops
+            .iter()
+            .map(|((start, end), iters)| (*start, *end, *iters, self.range_count(*start, *end)))
+            .collect();
+        loops.sort_by(|a, b| b.3.cmp(&a.3).then(a.0.cmp(&b.0)));
+        for (start, end, iters, count) in loops.iter().take(REPORT_TOP_N) {
+            let _ = writeln!(
+                out,
+                "  {:#05X}-{:#05X}  {:>8} iterations  {:>12} instrs  {:>6.2}%",
+                start,
+                end,
+                iters,
+                count,
+                self.percent(*count)
+            );
+        }
+        out
+    }
+
+    /// Writes the samples as folded stacks (`main;sub_0x300;sub_0x320 42`),
+    /// one instruction per sample, for `flamegraph.pl` or `inferno`.
+    pub fn write_folded(&self, out: &mut dyn std::io::Write) -> std::io::Result<()> {
+        let mut stacks: Vec<(&Vec<u16>, &u64)> = self.folded.iter().collect();
+        stacks.sort();
+        for (stack, count) in stacks {
+            let mut line = String::from("main");
+            for addr in stack {
+                let _ = write!(line, ";sub_{:#05X}", addr);
+            }
+            writeln!(out, "{} {}", line, count)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Behaviours that differ between CHIP-8 interpreters. ROMs written for one
+/// interpreter can misbehave on another, so these can be changed at runtime.
+///
+/// In config files, either a preset name (`quirks = "cosmac"`) or a table of
+/// flags, with unlisted flags taken from the default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Chip8QuirksSetting", into = "Chip8QuirksSetting")]
+pub struct Chip8Quirks {
+    /// `8XY6`/`8XYE` shift VY into VX, rather than shifting VX in place.
+    pub shift_uses_vy: bool,
+    /// `BNNN` jumps to NNN + V0, rather than XNN + VX.
+    pub jump_uses_v0: bool,
+    /// `FX55`/`FX65` leave I pointing just past the last register.
+    pub load_store_increments_i: bool,
+    /// `8XY1`/`8XY2`/`8XY3` set VF to 0.
+    pub logic_resets_vf: bool,
+}
+
+/// What the core has always done: COSMAC shifts and jumps, but I and VF are
+/// left alone.
+impl Default for Chip8Quirks {
+    fn default() -> Self {
+        Chip8Quirks {
+            shift_uses_vy: true,
+            jump_uses_v0: true,
+            load_store_increments_i: false,
+            logic_resets_vf: false,
+        }
+    }
+}
+
+impl Chip8Quirks {
+    /// The original COSMAC VIP interpreter.
+    pub fn cosmac() -> Chip8Quirks {
+        Chip8Quirks {
+            shift_uses_vy: true,
+            jump_uses_v0: true,
+            load_store_increments_i: true,
+            logic_resets_vf: true,
+        }
+    }
+
+    /// CHIP-48 / SUPER-CHIP, which most "modern" ROMs assume.
+    pub fn modern() -> Chip8Quirks {
+        Chip8Quirks {
+            shift_uses_vy: false,
+            jump_uses_v0: false,
+            load_store_increments_i: false,
+            logic_resets_vf: false,
+        }
+    }
+}
+
+impl FromStr for Chip8Quirks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Chip8Quirks::default()),
+            "cosmac" => Ok(Chip8Quirks::cosmac()),
+            "modern" => Ok(Chip8Quirks::modern()),
+            _ => Err(format!("Expected default, cosmac or modern, got {:?}", s)),
+        }
+    }
+}
+
+impl Chip8Quirks {
+    /// The preset these quirks match, if any.
+    pub fn preset_name(&self) -> Option<&'static str> {
+        ["default", "cosmac", "modern"]
+            .into_iter()
+            .find(|name| name.parse::<Chip8Quirks>().as_ref() == Ok(self))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct Chip8QuirksTable {
+    shift_uses_vy: bool,
+    jump_uses_v0: bool,
+    load_store_increments_i: bool,
+    logic_resets_vf: bool,
+}
+
+impl Default for Chip8QuirksTable {
+    fn default() -> Self {
+        let q = Chip8Quirks::default();
+        Chip8QuirksTable {
+            shift_uses_vy: q.shift_uses_vy,
+            jump_uses_v0: q.jump_uses_v0,
+            load_store_increments_i: q.load_store_increments_i,
+            logic_resets_vf: q.logic_resets_vf,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Chip8QuirksSetting {
+    Preset(String),
+    Table(Chip8QuirksTable),
+}
+
+impl TryFrom<Chip8QuirksSetting> for Chip8Quirks {
+    type Error = String;
+
+    fn try_from(setting: Chip8QuirksSetting) -> Result<Self, Self::Error> {
+        match setting {
+            Chip8QuirksSetting::Preset(name) => name.parse(),
+            Chip8QuirksSetting::Table(t) => Ok(Chip8Quirks {
+                shift_uses_vy: t.shift_uses_vy,
+                jump_uses_v0: t.jump_uses_v0,
+                load_store_increments_i: t.load_store_increments_i,
+                logic_resets_vf: t.logic_resets_vf,
+            }),
+        }
+    }
+}
+
+impl From<Chip8Quirks> for Chip8QuirksSetting {
+    fn from(q: Chip8Quirks) -> Self {
+        match q.preset_name() {
+            Some(name) => Chip8QuirksSetting::Preset(String::from(name)),
+            None => Chip8QuirksSetting::Table(Chip8QuirksTable {
+                shift_uses_vy: q.shift_uses_vy,
+                jump_uses_v0: q.jump_uses_v0,
+                load_store_increments_i: q.load_store_increments_i,
+                logic_resets_vf: q.logic_resets_vf,
+            }),
+        }
+    }
+}
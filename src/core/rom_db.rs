@@ -0,0 +1,201 @@
+use crate::core::quirks::Chip8Quirks;
+use serde::Deserialize;
+use simple_error::{simple_error, SimpleError};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// The ROMs known out of the box, in the community database's format. It only
+/// covers a few so far; drop the community database's `programs.json` into
+/// `Chip8RomDatabase::default_path()` for the rest.
+const BUNDLED_PROGRAMS: &str = include_str!("programs.json");
+
+/// Platforms this core can run, by their community database ids, and the
+/// quirks each one implies.
+fn platform_quirks(platform: &str) -> Option<Chip8Quirks> {
+    match platform {
+        "originalChip8" | "hybridVIP" => Some(Chip8Quirks::cosmac()),
+        "modernChip8" => Some(Chip8Quirks {
+            logic_resets_vf: false,
+            ..Chip8Quirks::cosmac()
+        }),
+        "chip48" | "superchip1" | "superchip" => Some(Chip8Quirks::modern()),
+        _ => None,
+    }
+}
+
+/// Applies a community database quirk override. Returns false for quirks this
+/// core doesn't model.
+fn apply_quirk(quirks: &mut Chip8Quirks, name: &str, on: bool) -> bool {
+    match name {
+        "shift" => quirks.shift_uses_vy = !on,
+        "jump" => quirks.jump_uses_v0 = !on,
+        "memoryLeaveIUnchanged" => quirks.load_store_increments_i = !on,
+        "logic" => quirks.logic_resets_vf = on,
+        _ => return false,
+    }
+    true
+}
+
+#[derive(Deserialize)]
+struct Chip8ProgramEntry {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    roms: BTreeMap<String, Chip8RomEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Chip8RomEntry {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: BTreeMap<String, BTreeMap<String, bool>>,
+    tickrate: Option<u32>,
+    colors: Option<Chip8RomColors>,
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+}
+
+#[derive(Deserialize)]
+struct Chip8RomColors {
+    /// Background first, then foreground.
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+/// Everything the database knows about one ROM image.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Chip8RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    /// The first of the ROM's platforms this core can run.
+    pub platform: Option<String>,
+    pub quirks: Option<Chip8Quirks>,
+    /// Instructions per frame.
+    pub tickrate: Option<u32>,
+    /// Foreground and background, as `#RRGGBB`.
+    pub colors: Option<(String, String)>,
+    /// What each keypad key does in the game, e.g. ("up", 5).
+    pub keys: Vec<(String, u8)>,
+    /// Things the database asks for that this core can't do.
+    pub warnings: Vec<String>,
+}
+
+impl Chip8RomInfo {
+    fn from_entry(program: &Chip8ProgramEntry, rom: &Chip8RomEntry) -> Chip8RomInfo {
+        let mut info = Chip8RomInfo {
+            title: program.title.clone(),
+            authors: program.authors.clone(),
+            tickrate: rom.tickrate,
+            keys: rom
+                .keys
+                .iter()
+                .filter(|(_, key)| **key < 16)
+                .map(|(action, key)| (action.clone(), *key))
+                .collect(),
+            ..Default::default()
+        };
+        info.keys.sort_by_key(|(_, key)| *key);
+
+        match rom
+            .platforms
+            .iter()
+            .find_map(|p| Some((p, platform_quirks(p)?)))
+        {
+            Some((platform, mut quirks)) => {
+                for (name, on) in rom.quirky_platforms.get(platform).into_iter().flatten() {
+                    if !apply_quirk(&mut quirks, name, *on) {
+                        info.warnings
+                            .push(format!("the {} quirk isn't supported", name));
+                    }
+                }
+                info.platform = Some(platform.clone());
+                info.quirks = Some(quirks);
+            }
+            None if !rom.platforms.is_empty() => info.warnings.push(format!(
+                "it's for {}, which isn't supported",
+                rom.platforms.join(" or ")
+            )),
+            None => (),
+        }
+
+        if let Some(colors) = &rom.colors {
+            if let [bg, fg, ..] = colors.pixels.as_slice() {
+                info.colors = Some((fg.clone(), bg.clone()));
+            }
+        }
+        info
+    }
+}
+
+/// Known ROMs keyed by SHA-1, in the format of the community CHIP-8 database's
+/// `programs.json`.
+#[derive(Debug, Default)]
+pub struct Chip8RomDatabase {
+    roms: HashMap<String, Chip8RomInfo>,
+}
+
+impl Chip8RomDatabase {
+    /// `~/.config/chiprust8/programs.json`, or under `$XDG_CONFIG_HOME` if set.
+    pub fn default_path() -> Option<PathBuf> {
+        let base: PathBuf = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("chiprust8").join("programs.json"))
+    }
+
+    pub fn bundled() -> Chip8RomDatabase {
+        Chip8RomDatabase::from_json(BUNDLED_PROGRAMS).expect("Bundled ROM database is invalid")
+    }
+
+    pub fn from_json(json: &str) -> Result<Chip8RomDatabase, SimpleError> {
+        let programs: Vec<Chip8ProgramEntry> =
+            serde_json::from_str(json).map_err(|e| simple_error!("Invalid ROM database: {}", e))?;
+        let mut roms: HashMap<String, Chip8RomInfo> = HashMap::new();
+        for program in programs.iter() {
+            for (hash, rom) in program.roms.iter() {
+                roms.insert(
+                    hash.to_ascii_lowercase(),
+                    Chip8RomInfo::from_entry(program, rom),
+                );
+            }
+        }
+        Ok(Chip8RomDatabase { roms })
+    }
+
+    /// Loads the file at `path`, or an empty database if it doesn't exist.
+    pub fn load(path: &Path) -> Result<Chip8RomDatabase, SimpleError> {
+        match fs::read_to_string(path) {
+            Ok(json) => Chip8RomDatabase::from_json(&json)
+                .map_err(|e| simple_error!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Chip8RomDatabase::default()),
+            Err(e) => Err(simple_error!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Adds `other`'s entries, replacing any for the same ROM.
+    pub fn merge(&mut self, other: Chip8RomDatabase) {
+        self.roms.extend(other.roms);
+    }
+
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&Chip8RomInfo> {
+        self.roms.get(&sha1_hex(rom))
+    }
+}
+
+pub fn sha1_hex(data: &[u8]) -> String {
+    sha1_smol::Sha1::from(data).digest().to_string()
+}
@@ -1,7 +1,38 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
 
     use crate::core::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    /// Counts allocations made by each thread, so a test can check a piece
+    /// of code doesn't allocate while the others run alongside it.
+    struct CountingAlloc;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOC: CountingAlloc = CountingAlloc;
+
+    #[cfg(not(feature = "instr-log"))]
+    fn allocations() -> u64 {
+        ALLOCATIONS.with(|n| n.get())
+    }
+
     fn test_init() -> Chip8Core {
         let _ = env_logger::builder()
             .filter_level(log::LevelFilter::Debug)
@@ -16,7 +47,6 @@ mod tests {
             Ok(_) => {}
             Err(e) => {
                 error!("Failed to execute {:?} with err: {:?}", instr, e);
-                return;
             }
         }
     }
@@ -57,7 +87,7 @@ mod tests {
         assert_eq!(chip8.regs.pc, addr, "Jump to method");
         assert_eq!(chip8.stack.len(), 1, "one item on stack _only_");
         assert_eq!(
-            *chip8.stack.get(0).unwrap(),
+            *chip8.stack.front().unwrap(),
             orig_pc + 2,
             "item on stack is original PC (incremented)"
         );
@@ -405,4 +435,1064 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_analysis_separates_sprites() {
+        let rom: [u8; 15] = [
+            0xA2, 0x0A, // LD I, 0x20A
+            0x22, 0x06, // CALL 0x206
+            0x12, 0x04, // JP 0x204
+            0xD0, 0x15, // DRW V0, V1, 5
+            0x00, 0xEE, // RET
+            0xF0, 0x90, 0x90, 0x90, 0xF0, // sprite
+        ];
+        let analysis = analysis::Chip8Analysis::analyze(&rom);
+
+        assert_eq!(analysis.kind(0x206), analysis::Chip8ByteKind::Code);
+        for addr in 0x20A..0x20F {
+            assert_eq!(analysis.kind(addr), analysis::Chip8ByteKind::Data);
+        }
+        assert_eq!(analysis.blocks().count(), 3);
+
+        let disasm: String = analysis.disassemble();
+        assert!(disasm.contains("0x206: D015  DRW V0, V1, 0x5"));
+        assert!(disasm.contains("0x20A: db 0xF0, 0x90, 0x90, 0x90, 0xF0  ; data"));
+    }
+
+    #[test]
+    fn test_analysis_flags_computed_jump() {
+        let rom: [u8; 6] = [
+            0x30, 0x01, // SE V0, 0x01
+            0xB3, 0x00, // JP V0, 0x300
+            0x00, 0xEE, // RET
+        ];
+        let analysis = analysis::Chip8Analysis::analyze(&rom);
+
+        assert_eq!(analysis.computed_jumps(), &[0x202]);
+        let dot: String = analysis.to_dot();
+        assert!(dot.contains("\"0x200\" -> \"0x202\";"));
+        assert!(dot.contains("\"0x200\" -> \"0x204\";"));
+        assert!(dot.contains("color=red"));
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut chip8 = test_init();
+        let path = std::env::temp_dir().join("chiprust8_test_trace.txt");
+        let path_str: &str = path.to_str().unwrap();
+        let range: trace::Chip8TraceRange = "0x202-0x2FF".parse().unwrap();
+        chip8.set_tracer(
+            trace::Chip8Tracer::to_file(path_str)
+                .unwrap()
+                .with_range(range),
+        );
+
+        chip8.regs.pc = PROGRAM_OFFSET;
+        chip8.mem.memspace[0x200..0x204].copy_from_slice(&[0x63, 0x2A, 0xA1, 0x23]);
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+
+        let trace: String = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+        assert_eq!(lines.len(), 1, "0x200 is outside the traced range");
+        assert!(lines[0].starts_with("000000000001 PC:0202 OP:A123 LD I, 0x123"));
+        assert!(lines[0].contains(" V3:2A "));
+        assert!(lines[0].ends_with("I:0000 SP:0 DT:00 ST:00"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_trace_template() {
+        let mut chip8 = test_init();
+        let path = std::env::temp_dir().join("chiprust8_test_trace_template.txt");
+        let template: trace::Chip8TraceTemplate =
+            "{{{cycle}}} {pc:03x} {opcode} {instr:9}|vf={vf:d} {v:1x} I={i:X}"
+                .parse()
+                .unwrap();
+        chip8.set_tracer(
+            trace::Chip8Tracer::to_file(path.to_str().unwrap())
+                .unwrap()
+                .with_format(trace::Chip8TraceFormat::Template(template)),
+        );
+
+        chip8.regs.pc = PROGRAM_OFFSET;
+        chip8.regs.v_regs[0xF] = 12;
+        chip8.regs.v_regs[0xA] = 0xB;
+        chip8.mem.memspace[0x200..0x202].copy_from_slice(&[0xA1, 0x23]);
+        chip8.tick().unwrap();
+
+        let trace: String = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            trace,
+            "{0} 200 A123 LD I, 0x123|vf=12 0 0 0 0 0 0 0 0 0 0 b 0 0 0 0 c I=0000\n"
+        );
+        let _ = fs::remove_file(&path);
+
+        for bad in ["{pc", "pc}", "{v10}", "{sp:zz}", "{nope}"] {
+            assert!(bad.parse::<trace::Chip8TraceTemplate>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_profiler() {
+        let mut chip8 = test_init();
+        chip8.enable_profiler();
+        chip8.regs.pc = PROGRAM_OFFSET;
+        chip8.mem.memspace[0x200..0x208].copy_from_slice(&[
+            0x22, 0x06, // CALL 0x206
+            0x70, 0x01, // ADD V0, 0x01
+            0x12, 0x00, // JP 0x200
+            0x00, 0xEE, // RET
+        ]);
+        for _ in 0..8 {
+            chip8.tick().unwrap();
+        }
+
+        let profiler = chip8.profiler().unwrap();
+        assert_eq!(profiler.total(), 8);
+        assert_eq!(profiler.count_at(0x200), 2);
+        assert_eq!(profiler.pattern_count("2NNN"), 2);
+        let sub = profiler.subroutine(0x206).unwrap();
+        assert_eq!(sub.calls, 2);
+        assert_eq!(sub.cycles, 2);
+        assert!(profiler
+            .report()
+            .contains("0x200-0x204         2 iterations"));
+
+        let mut folded: Vec<u8> = Vec::new();
+        profiler.write_folded(&mut folded).unwrap();
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "main 6\nmain;sub_0x206 2\n"
+        );
+    }
+
+    #[test]
+    fn test_debug_memory_requests() {
+        use crate::graphics::debug_adapter::*;
+        let mut chip8 = test_init();
+        let da = DebugAdapter::new();
+        chip8.attach_debugger(&da);
+        chip8.stack.push_back(0x234);
+
+        da.request_sender
+            .send(Chip8DebugRequest::WriteMemory {
+                addr: 0x300,
+                val: 0x42,
+            })
+            .unwrap();
+        da.request_sender
+            .send(Chip8DebugRequest::ReadMemory)
+            .unwrap();
+        chip8.handle_debug_requests();
+
+        assert_eq!(chip8.mem.memspace[0x300], 0x42);
+        match da.response_receiver.try_recv() {
+            Ok(Chip8DebugResponse::Memory(snapshot)) => {
+                assert_eq!(snapshot.mem[0x300], 0x42);
+                assert_eq!(snapshot.pc, chip8.regs.pc);
+                assert_eq!(snapshot.stack, vec![0x234]);
+            }
+            _ => panic!("Expected a memory snapshot"),
+        }
+    }
+
+    #[test]
+    fn test_debug_state_request() {
+        use crate::graphics::debug_adapter::*;
+        let mut chip8 = test_init();
+        let da = DebugAdapter::new();
+        chip8.attach_debugger(&da);
+        chip8.regs.pc = PROGRAM_OFFSET;
+        chip8.mem.memspace[0x200..0x202].copy_from_slice(&[0x8A, 0xB4]);
+        chip8.set_reg(0xA, 0x12).unwrap();
+        chip8.timers.delay = 3;
+
+        da.request_sender
+            .send(Chip8DebugRequest::ReadState)
+            .unwrap();
+        chip8.handle_debug_requests();
+
+        match da.response_receiver.try_recv() {
+            Ok(Chip8DebugResponse::State(state)) => {
+                assert_eq!(state.v_regs[0xA], 0x12);
+                assert_eq!(state.pc, PROGRAM_OFFSET);
+                assert_eq!(state.opcode, 0x8AB4);
+                assert_eq!(state.instr, "ADD VA, VB");
+                assert_eq!(state.delay, 3);
+            }
+            _ => panic!("Expected a state snapshot"),
+        }
+    }
+
+    #[test]
+    fn test_run_frame() {
+        let mut chip8 = test_init();
+        chip8.regs.pc = PROGRAM_OFFSET;
+        // 7001: ADD V0, 1, over and over
+        for addr in (0x200..0x300).step_by(2) {
+            chip8.mem.memspace[addr..addr + 2].copy_from_slice(&[0x70, 0x01]);
+        }
+        chip8.timers.delay = 2;
+        chip8.run_frame();
+
+        assert_eq!(chip8.regs.v_regs[0], DEFAULT_INSTRS_PER_FRAME as u8);
+        assert_eq!(chip8.cycles, DEFAULT_INSTRS_PER_FRAME as u64);
+        assert_eq!(chip8.timers.delay, 1);
+    }
+
+    #[test]
+    fn test_save_states() {
+        use crate::graphics::control_adapter::*;
+        let mut chip8 = test_init();
+        let ca = ControlAdapter::new();
+        chip8.attach_control(&ca);
+        chip8.regs.pc = PROGRAM_OFFSET;
+        chip8.mem.memspace[0x200..0x202].copy_from_slice(&[0x70, 0x01]);
+
+        ca.send(Chip8Command::SaveState(1)).unwrap();
+        chip8.handle_commands();
+        chip8.tick().unwrap();
+        chip8.mem.memspace[0x300] = 0x42;
+        assert_eq!(chip8.regs.v_regs[0], 1);
+
+        ca.send(Chip8Command::LoadState(1)).unwrap();
+        chip8.handle_commands();
+        assert_eq!(chip8.regs.v_regs[0], 0);
+        assert_eq!(chip8.regs.pc, PROGRAM_OFFSET);
+        assert_eq!(chip8.mem.memspace[0x300], 0);
+        assert_eq!(chip8.cycles, 0);
+
+        // Empty and out of range slots leave the state alone
+        chip8.tick().unwrap();
+        ca.send(Chip8Command::LoadState(0)).unwrap();
+        ca.send(Chip8Command::SaveState(SAVE_SLOTS as u8)).unwrap();
+        chip8.handle_commands();
+        assert_eq!(chip8.regs.v_regs[0], 1);
+    }
+
+    #[test]
+    fn test_pause_and_reset() {
+        use crate::graphics::control_adapter::*;
+        let mut chip8 = test_init();
+        let ca = ControlAdapter::new();
+        chip8.attach_control(&ca);
+        chip8.rom = vec![0x70, 0x01];
+        chip8.reset();
+        chip8.run_frame();
+        chip8.mem.memspace[0x200] = 0xFF;
+
+        ca.send(Chip8Command::Pause).unwrap();
+        ca.send(Chip8Command::Reset).unwrap();
+        chip8.handle_commands();
+        assert!(!chip8.running);
+        assert_eq!(chip8.regs.pc, PROGRAM_OFFSET);
+        assert_eq!(chip8.regs.v_regs[0], 0);
+        assert_eq!(chip8.mem.memspace[0x200], 0x70);
+
+        ca.send(Chip8Command::FrameAdvance).unwrap();
+        chip8.handle_commands();
+        assert_eq!(chip8.regs.v_regs[0], 1);
+    }
+
+    #[test]
+    fn test_keypad_edges() {
+        use crate::core::keypad::*;
+        let mut keypad = Chip8Keypad::new();
+        keypad.queue(Chip8KeyEvent::new(0x5, true));
+        keypad.queue(Chip8KeyEvent::new(0x5, true));
+        keypad.begin_frame();
+        assert!(keypad.is_down(0x5));
+        assert!(keypad.just_pressed(0x5));
+
+        keypad.begin_frame();
+        assert!(keypad.is_down(0x5));
+        assert!(!keypad.just_pressed(0x5));
+
+        keypad.queue(Chip8KeyEvent::new(0x5, false));
+        keypad.begin_frame();
+        assert!(!keypad.is_down(0x5));
+        assert!(keypad.just_released(0x5));
+        assert_eq!(keypad.state(), [0; 16]);
+    }
+
+    #[test]
+    fn test_keypad_short_tap() {
+        use crate::core::keypad::*;
+        let mut keypad = Chip8Keypad::new();
+        // Pressed and released between two frames
+        keypad.queue(Chip8KeyEvent::new(0xA, true));
+        keypad.queue(Chip8KeyEvent::new(0x1, true));
+        keypad.queue(Chip8KeyEvent::new(0xA, false));
+        keypad.begin_frame();
+        assert!(keypad.is_down(0xA));
+        assert!(keypad.is_down(0x1));
+
+        keypad.begin_frame();
+        assert!(!keypad.is_down(0xA));
+        assert!(keypad.just_released(0xA));
+        assert!(keypad.is_down(0x1));
+    }
+
+    #[test]
+    fn test_key_instrs() {
+        use crate::core::keypad::*;
+        let mut chip8 = test_init();
+        chip8.regs.pc = PROGRAM_OFFSET;
+        chip8.set_reg(0x3, 0xB).unwrap();
+        assert_eq!(Chip8Instr::from_u16(0xE39E).unwrap().pattern(), "EX9E");
+        assert_eq!(Chip8Instr::from_u16(0xE3A1).unwrap().pattern(), "EXA1");
+
+        // SKP V3 / SKNP V3 with key B up
+        chip8
+            .execute(Chip8Instr::from_u16(0xE39E).unwrap())
+            .unwrap();
+        assert_eq!(chip8.regs.pc, PROGRAM_OFFSET + 2);
+        chip8
+            .execute(Chip8Instr::from_u16(0xE3A1).unwrap())
+            .unwrap();
+        assert_eq!(chip8.regs.pc, PROGRAM_OFFSET + 6);
+
+        chip8.keypad.queue(Chip8KeyEvent::new(0xB, true));
+        chip8.keypad.begin_frame();
+        chip8
+            .execute(Chip8Instr::from_u16(0xE39E).unwrap())
+            .unwrap();
+        assert_eq!(chip8.regs.pc, PROGRAM_OFFSET + 10);
+
+        // LD V4, K waits for the key to come back up
+        let wait: Chip8Instr = Chip8Instr::from_u16(0xF40A).unwrap();
+        chip8.execute(wait).unwrap();
+        assert_eq!(chip8.regs.pc, PROGRAM_OFFSET + 10);
+        chip8.keypad.queue(Chip8KeyEvent::new(0xB, false));
+        chip8.keypad.begin_frame();
+        chip8.execute(wait).unwrap();
+        assert_eq!(chip8.regs.pc, PROGRAM_OFFSET + 12);
+        assert_eq!(chip8.regs.v_regs[0x4], 0xB);
+        // The release is only handed out once
+        chip8.execute(wait).unwrap();
+        assert_eq!(chip8.regs.pc, PROGRAM_OFFSET + 12);
+    }
+
+    #[test]
+    fn test_frame_published_once_per_frame() {
+        let mut chip8 = test_init();
+        chip8.regs.pc = PROGRAM_OFFSET;
+        // D001: DRW V0, V0, 1 over and over, toggling the top-left pixel
+        for addr in (0x200..0x300).step_by(2) {
+            chip8.mem.memspace[addr..addr + 2].copy_from_slice(&[0xD0, 0x01]);
+        }
+        chip8.regs.index_reg = 0x0; // font "0", top row 0xF0
+
+        chip8.run_frame();
+        chip8.run_frame();
+        let (data, generation) = chip8.ga.latest_frame();
+        assert_eq!(generation, 2);
+        // Two frames of draws is an even number of toggles
+        assert!(!data.pixel(0, 0));
+        // Only the newest vblank is kept
+        assert_eq!(chip8.ga.vblank_receiver.len(), 1);
+        assert_eq!(chip8.ga.vblank_receiver.try_recv(), Ok(1));
+
+        // Nothing drawn, nothing published. 1300: JP 0x300
+        chip8.mem.memspace[0x300..0x302].copy_from_slice(&[0x13, 0x00]);
+        chip8.regs.pc = 0x300;
+        chip8.run_frame();
+        assert_eq!(chip8.ga.latest_frame().1, 2);
+        // but the frame still counts
+        assert_eq!(chip8.ga.frames(), 3);
+    }
+
+    #[test]
+    fn test_control_acks_and_shutdown() {
+        use crate::graphics::control_adapter::*;
+        use std::time::Duration;
+        let mut chip8 = test_init();
+        let ca = ControlAdapter::new();
+        chip8.attach_control(&ca);
+        let core_thread = std::thread::spawn(move || {
+            chip8.run_loop();
+            chip8
+        });
+        let timeout = Duration::from_secs(5);
+
+        // 7001: ADD V0, 1, then loop forever
+        let rom: Vec<u8> = vec![0x70, 0x01, 0x70, 0x01, 0x70, 0x01, 0x12, 0x06];
+        ca.call(Chip8Command::Pause, timeout).unwrap();
+        let ack = ca.call(Chip8Command::LoadRom(rom), timeout).unwrap();
+        assert_eq!(ack.result, Ok(()));
+        assert_eq!((ack.pc, ack.cycles), (PROGRAM_OFFSET, 0));
+
+        let ack = ca.call(Chip8Command::Step(2), timeout).unwrap();
+        assert!(!ack.running);
+        assert_eq!((ack.pc, ack.cycles), (PROGRAM_OFFSET + 4, 2));
+
+        let ack = ca
+            .call(Chip8Command::LoadRom(vec![0; 4096]), timeout)
+            .unwrap();
+        assert!(ack.result.is_err());
+        let ack = ca.call(Chip8Command::LoadState(0), timeout).unwrap();
+        assert!(ack.result.is_err());
+
+        ca.call(Chip8Command::SetQuirks(Chip8Quirks::modern()), timeout)
+            .unwrap();
+        ca.call(Chip8Command::SetInstrsPerFrame(30), timeout)
+            .unwrap();
+        ca.send(Chip8Command::Shutdown).unwrap();
+        let chip8 = core_thread.join().unwrap();
+        assert_eq!(chip8.regs.v_regs[0], 2);
+        assert_eq!(chip8.quirks(), Chip8Quirks::modern());
+        assert_eq!(chip8.instrs_per_frame, 30);
+    }
+
+    #[test]
+    fn test_control_step_finishes_frames() {
+        use crate::graphics::control_adapter::*;
+        let mut chip8 = test_init();
+        let ca = ControlAdapter::new();
+        chip8.attach_control(&ca);
+        chip8.set_instrs_per_frame(4);
+
+        // 600A: LD V0, 10 / F015: LD DT, V0 / 1204: JP 0x204
+        let rom: Vec<u8> = vec![0x60, 0x0A, 0xF0, 0x15, 0x12, 0x04];
+        ca.send(Chip8Command::LoadRom(rom)).unwrap();
+        ca.send(Chip8Command::Step(4)).unwrap();
+        chip8.handle_commands();
+        assert_eq!(chip8.timers.delay, 9);
+        assert_eq!(chip8.ga.frames(), 1);
+
+        ca.send(Chip8Command::Step(3)).unwrap();
+        chip8.handle_commands();
+        assert_eq!(chip8.timers.delay, 9);
+        ca.send(Chip8Command::Step(1)).unwrap();
+        chip8.handle_commands();
+        assert_eq!(chip8.timers.delay, 8);
+    }
+
+    #[test]
+    fn test_pause_state_published_to_frontends() {
+        use crate::graphics::control_adapter::*;
+        let mut chip8 = test_init();
+        let ca = ControlAdapter::new();
+        chip8.attach_control(&ca);
+        assert!(!chip8.ga.paused());
+
+        // A debugger step pauses the core without any hotkey being pressed
+        ca.send(Chip8Command::Step(1)).unwrap();
+        chip8.handle_commands();
+        assert!(chip8.ga.paused());
+
+        ca.send(Chip8Command::Resume).unwrap();
+        chip8.handle_commands();
+        assert!(!chip8.ga.paused());
+
+        ca.send(Chip8Command::Pause).unwrap();
+        ca.send(Chip8Command::Reset).unwrap();
+        chip8.handle_commands();
+        assert!(chip8.ga.paused());
+    }
+
+    #[test]
+    fn test_quirks() {
+        let mut chip8 = test_init();
+        chip8.set_reg(0x1, 0x0F).unwrap();
+        chip8.set_reg(0xF, 0x1).unwrap();
+        chip8.regs.index_reg = 0x300;
+
+        // 8011: OR V0, V1 / F165: LD V1, [I]
+        chip8
+            .execute(Chip8Instr::from_u16(0x8011).unwrap())
+            .unwrap();
+        chip8
+            .execute(Chip8Instr::from_u16(0xF165).unwrap())
+            .unwrap();
+        assert_eq!(chip8.regs.v_regs[0xF], 1);
+        assert_eq!(chip8.regs.index_reg, 0x300);
+
+        chip8.set_quirks(Chip8Quirks::cosmac());
+        chip8
+            .execute(Chip8Instr::from_u16(0x8011).unwrap())
+            .unwrap();
+        chip8
+            .execute(Chip8Instr::from_u16(0xF165).unwrap())
+            .unwrap();
+        assert_eq!(chip8.regs.v_regs[0xF], 0);
+        assert_eq!(chip8.regs.index_reg, 0x302);
+
+        // 8106: SHR V1 {, V0}
+        chip8.set_reg(0x0, 0x08).unwrap();
+        chip8.set_reg(0x1, 0x40).unwrap();
+        chip8
+            .execute(Chip8Instr::from_u16(0x8106).unwrap())
+            .unwrap();
+        assert_eq!(chip8.regs.v_regs[0x1], 0x04);
+        chip8.set_quirks(Chip8Quirks::modern());
+        chip8
+            .execute(Chip8Instr::from_u16(0x8106).unwrap())
+            .unwrap();
+        assert_eq!(chip8.regs.v_regs[0x1], 0x02);
+        assert_eq!("cosmac".parse::<Chip8Quirks>(), Ok(Chip8Quirks::cosmac()));
+    }
+
+    /// The IBM logo demo, the usual first ROM to get running.
+    const IBM_LOGO: &[u8] = &[
+        0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C, 0x61, 0x08, 0xD0, 0x1F, 0x70, 0x09, 0xA2, 0x39, 0xD0,
+        0x1F, 0xA2, 0x48, 0x70, 0x08, 0xD0, 0x1F, 0x70, 0x04, 0xA2, 0x57, 0xD0, 0x1F, 0x70, 0x08,
+        0xA2, 0x66, 0xD0, 0x1F, 0x70, 0x08, 0xA2, 0x75, 0xD0, 0x1F, 0x12, 0x28, 0xFF, 0x00, 0xFF,
+        0x00, 0x3C, 0x00, 0x3C, 0x00, 0x3C, 0x00, 0x3C, 0x00, 0xFF, 0x00, 0xFF, 0xFF, 0x00, 0xFF,
+        0x00, 0x38, 0x00, 0x3F, 0x00, 0x3F, 0x00, 0x38, 0x00, 0xFF, 0x00, 0xFF, 0x80, 0x00, 0xE0,
+        0x00, 0xE0, 0x00, 0x80, 0x00, 0x80, 0x00, 0xE0, 0x00, 0xE0, 0x00, 0x80, 0xF8, 0x00, 0xFC,
+        0x00, 0x3E, 0x00, 0x3F, 0x00, 0x3B, 0x00, 0x39, 0x00, 0xF8, 0x00, 0xF8, 0x03, 0x00, 0x07,
+        0x00, 0x0F, 0x00, 0xBF, 0x00, 0xFB, 0x00, 0xF3, 0x00, 0xE3, 0x00, 0x43, 0xE0, 0x00, 0xE0,
+        0x00, 0x80, 0x00, 0x80, 0x00, 0x80, 0x00, 0x80, 0x00, 0xE0, 0x00, 0xE0,
+    ];
+
+    #[test]
+    fn test_rom_db() {
+        use crate::core::rom_db::*;
+        assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        let bundled = Chip8RomDatabase::bundled();
+        assert!(bundled.lookup(&[0x12, 0x00]).is_none());
+        let ibm = bundled.lookup(IBM_LOGO).unwrap();
+        assert_eq!(ibm.title, "IBM Logo");
+        assert_eq!(ibm.platform.as_deref(), Some("originalChip8"));
+        assert_eq!(ibm.quirks, Some(Chip8Quirks::cosmac()));
+
+        let rom: [u8; 2] = [0x12, 0x00];
+        let other: [u8; 2] = [0x00, 0xE0];
+        let json = format!(
+            r##"[
+                {{"title": "Spin", "authors": ["Someone"], "roms": {{
+                    "{}": {{
+                        "platforms": ["xochip", "modernChip8"],
+                        "quirkyPlatforms": {{"modernChip8": {{"shift": true, "vblank": true}}}},
+                        "tickrate": 30,
+                        "colors": {{"pixels": ["#000000", "#ff8000"]}},
+                        "keys": {{"right": 6, "left": 4, "bogus": 99}}
+                    }}
+                }}}},
+                {{"title": "Big", "roms": {{"{}": {{"platforms": ["megachip8"]}}}}}}
+            ]"##,
+            sha1_hex(&rom).to_uppercase(),
+            sha1_hex(&other)
+        );
+        let db = Chip8RomDatabase::from_json(&json).unwrap();
+        assert_eq!(db.len(), 2);
+
+        let info = db.lookup(&rom).unwrap();
+        assert_eq!(info.title, "Spin");
+        assert_eq!(info.platform.as_deref(), Some("modernChip8"));
+        let quirks = info.quirks.unwrap();
+        assert!(!quirks.shift_uses_vy);
+        assert!(quirks.jump_uses_v0);
+        assert!(quirks.load_store_increments_i);
+        assert!(!quirks.logic_resets_vf);
+        assert_eq!(info.tickrate, Some(30));
+        assert_eq!(
+            info.colors,
+            Some((String::from("#ff8000"), String::from("#000000")))
+        );
+        assert_eq!(
+            info.keys,
+            vec![(String::from("left"), 4), (String::from("right"), 6)]
+        );
+        assert_eq!(info.warnings.len(), 1);
+
+        let big = db.lookup(&other).unwrap();
+        assert_eq!(big.quirks, None);
+        assert_eq!(big.warnings.len(), 1);
+
+        assert!(Chip8RomDatabase::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn test_assemble() {
+        let source = "\
+start:  CLS
+        LD V0, 0x0A     ; counter
+loop:   ADD V0, 0xFF
+        LD I, sprite
+        DRW V0, V1, 5
+        SE V0, 0
+        JP loop
+        ld [i], vf
+        SHR V3
+        JP V0, start
+sprite: db 0xF0, 0b10010000, #90
+        dw 0x1234
+";
+        let rom: Vec<u8> = asm::assemble(source).unwrap();
+        assert_eq!(
+            rom,
+            vec![
+                0x00, 0xE0, 0x60, 0x0A, 0x70, 0xFF, 0xA2, 0x14, 0xD0, 0x15, 0x30, 0x00, 0x12, 0x04,
+                0xFF, 0x55, 0x83, 0x36, 0xB2, 0x00, 0xF0, 0x90, 0x90, 0x12, 0x34
+            ]
+        );
+
+        // Disassembler output goes back to the same bytes, data and all
+        let listing: String = analysis::Chip8Analysis::analyze(&rom).disassemble();
+        assert_eq!(asm::assemble(&listing).unwrap(), rom);
+        let odd: Vec<u8> = vec![0x12, 0x04, 0x01, 0x23, 0x00, 0xE0, 0x12, 0x04];
+        let listing: String = analysis::Chip8Analysis::analyze(&odd).disassemble();
+        assert_eq!(asm::assemble(&listing).unwrap(), odd);
+
+        let err = asm::assemble("CLS\nJP nowhere").unwrap_err().to_string();
+        assert!(err.starts_with("line 2:"), "{}", err);
+        assert!(asm::assemble("LD V0, 0x100").is_err());
+        assert!(asm::assemble("DRW V0, V1").is_err());
+        assert!(asm::assemble("a: CLS\na: CLS").is_err());
+    }
+
+    #[test]
+    fn test_seed() {
+        let rolls = |chip8: &mut Chip8Core| -> Vec<u8> {
+            (0..8)
+                .map(|_| {
+                    test_exec(
+                        chip8,
+                        Chip8Instr::Random(Chip8SingleRegImmOp { reg: 0, imm: 0xFF }),
+                    );
+                    chip8.regs.v_regs[0]
+                })
+                .collect()
+        };
+        let mut a = test_init();
+        let mut b = test_init();
+        a.set_seed(42);
+        b.set_seed(42);
+        let first: Vec<u8> = rolls(&mut a);
+        assert_eq!(first, rolls(&mut b));
+        b.set_seed(43);
+        assert_ne!(first, rolls(&mut b));
+        a.reset();
+        assert_eq!(rolls(&mut a), first);
+    }
+
+    #[test]
+    fn test_step_matches_run_frame() {
+        // LD V0, 5; LD DT, V0; ADD V1, 1; JP 0x204
+        let program: [u8; 8] = [0x60, 0x05, 0xF0, 0x15, 0x71, 0x01, 0x12, 0x04];
+        let mut a = test_init();
+        let mut b = test_init();
+        for chip8 in [&mut a, &mut b] {
+            chip8.regs.pc = PROGRAM_OFFSET;
+            chip8.mem.memspace[0x200..0x208].copy_from_slice(&program);
+            chip8.set_instrs_per_frame(4);
+        }
+        for _ in 0..3 {
+            a.run_frame();
+        }
+        for _ in 0..10 {
+            b.step().unwrap();
+        }
+        // Two instructions into the third frame, then finish it
+        assert_eq!(b.timers.delay, 3);
+        b.run_frame();
+        assert_eq!(a.cycles(), b.cycles());
+        assert_eq!(a.regs.v_regs, b.regs.v_regs);
+        assert_eq!(a.timers.delay, b.timers.delay);
+        assert_eq!(a.frames, b.frames);
+    }
+
+    #[test]
+    fn test_bench_mixes() {
+        for mix in bench::Chip8BenchMix::all() {
+            assert_eq!(mix.name().parse(), Ok(mix));
+            let mut chip8 = Chip8Core::from_rom(mix.rom(), true, &GraphicsAdapter::new());
+            for _ in 0..10_000 {
+                if let Err(e) = chip8.tick() {
+                    panic!("{} failed at {:#05X}: {}", mix, chip8.pc(), e);
+                }
+            }
+            let report = bench::run_bench(&mut chip8, 1000);
+            assert!(report.instructions >= 1000);
+            assert_eq!(
+                report.instructions,
+                report.frames * DEFAULT_INSTRS_PER_FRAME as u64
+            );
+        }
+        assert!("nope".parse::<bench::Chip8BenchMix>().is_err());
+    }
+
+    #[test]
+    fn test_font_char_uses_low_nibble() {
+        let mut chip8 = test_init();
+        chip8.regs.v_regs[4] = 0xFA;
+        test_exec(
+            &mut chip8,
+            Chip8Instr::Extra(Chip8ExtraInstr::SetIndexHex(Chip8SingleRegOp { reg: 4 })),
+        );
+        assert_eq!(chip8.regs.index_reg, 0xA * 5);
+    }
+
+    #[test]
+    fn test_decode_cache_sees_self_modifying_code() {
+        let source = "
+        CALL target     ; decodes and caches ADD V2, 1
+        LD V0, 0x72
+        LD V1, 0x05
+        LD I, target
+        LD [I], V1      ; target is now ADD V2, 5
+        CALL target
+hang:   JP hang
+target: ADD V2, 1
+        RET
+";
+        let mut chip8 = Chip8Core::from_rom(
+            asm::assemble(source).unwrap(),
+            true,
+            &GraphicsAdapter::new(),
+        );
+        for _ in 0..12 {
+            chip8.tick().unwrap();
+        }
+        assert_eq!(chip8.regs.v_regs[2], 6);
+
+        // A store to the second byte of a cached instruction counts too
+        let cached: usize = chip8.decoded.len();
+        chip8.store(0x20F, 0x10);
+        assert_eq!(chip8.decoded.len(), cached - 1);
+        chip8.regs.pc = 0x20E;
+        chip8.tick().unwrap();
+        assert_eq!(chip8.regs.v_regs[2], 0x16);
+
+        chip8.reset();
+        assert_eq!(chip8.decoded.len(), 0);
+    }
+
+    #[test]
+    fn test_draw_collision_needs_a_set_sprite_pixel() {
+        let mut chip8 = test_init();
+        // 0x300: 0b1010_0000, 0x301: 0b0101_0000
+        chip8.mem.memspace[0x300] = 0xA0;
+        chip8.mem.memspace[0x301] = 0x50;
+        chip8.regs.index_reg = 0x300;
+        let draw_instr: Chip8Instr = Chip8Instr::Draw(Chip8DoubleRegImmOp { a: 0, b: 1, imm: 1 });
+        test_exec(&mut chip8, draw_instr);
+        assert_eq!(chip8.regs.v_regs[0xF], 0);
+
+        // Lit pixels under the sprite's unset bits don't count
+        chip8.regs.index_reg = 0x301;
+        test_exec(&mut chip8, draw_instr);
+        assert_eq!(chip8.regs.v_regs[0xF], 0);
+        assert_eq!(chip8._disp._display[0][0..4], [1, 1, 1, 1]);
+
+        chip8.regs.index_reg = 0x300;
+        test_exec(&mut chip8, draw_instr);
+        assert_eq!(chip8.regs.v_regs[0xF], 1);
+        assert_eq!(chip8._disp._display[0][0..4], [0, 1, 0, 1]);
+
+        // Clipped at the right edge rather than wrapped
+        chip8.set_reg(0, 62).unwrap();
+        chip8.clear_display().unwrap();
+        test_exec(&mut chip8, draw_instr);
+        assert_eq!(chip8._disp._display[0][62..64], [1, 0]);
+        assert_eq!(chip8._disp._display[0][0..4], [0, 0, 0, 0]);
+    }
+
+    // With instr-log every instruction formats a log line, which allocates
+    #[cfg(not(feature = "instr-log"))]
+    #[test]
+    fn test_frames_do_not_allocate() {
+        for engine in [
+            blocks::Chip8Engine::Interpreter,
+            blocks::Chip8Engine::Compiled,
+        ] {
+            for mix in bench::Chip8BenchMix::all() {
+                let mut chip8 = Chip8Core::from_rom(mix.rom(), true, &GraphicsAdapter::new());
+                chip8.set_seed(1);
+                chip8.set_engine(engine);
+                // The first frames fill the decode cache or compile the blocks
+                for _ in 0..200 {
+                    chip8.run_frame();
+                }
+                let before: u64 = allocations();
+                for _ in 0..100 {
+                    chip8.run_frame();
+                }
+                assert_eq!(
+                    allocations() - before,
+                    0,
+                    "{} allocated with {}",
+                    mix,
+                    engine
+                );
+            }
+        }
+    }
+
+    fn assert_same_state(a: &Chip8Core, b: &Chip8Core, what: &str) {
+        assert_eq!(a.regs.pc, b.regs.pc, "PC after {}", what);
+        assert_eq!(a.regs.v_regs, b.regs.v_regs, "V registers after {}", what);
+        assert_eq!(a.regs.index_reg, b.regs.index_reg, "I after {}", what);
+        assert_eq!(a.stack, b.stack, "stack after {}", what);
+        assert_eq!(a.timers.delay, b.timers.delay, "delay timer after {}", what);
+        assert_eq!(a.timers.sound, b.timers.sound, "sound timer after {}", what);
+        assert_eq!(a.cycles, b.cycles, "cycles after {}", what);
+        assert!(a.mem.memspace == b.mem.memspace, "memory after {}", what);
+        assert!(
+            a._disp._display == b._disp._display,
+            "display after {}",
+            what
+        );
+    }
+
+    /// Runs `rom` a frame at a time on both engines and checks they agree
+    /// after every frame.
+    fn assert_engines_agree(rom: &[u8], quirks: Chip8Quirks, ipf: u32, frames: u32, what: &str) {
+        let mut cores: Vec<Chip8Core> = [
+            blocks::Chip8Engine::Interpreter,
+            blocks::Chip8Engine::Compiled,
+        ]
+        .into_iter()
+        .map(|engine| {
+            let mut chip8 = Chip8Core::from_rom(rom.to_vec(), true, &GraphicsAdapter::new());
+            chip8.set_quirks(quirks);
+            chip8.set_instrs_per_frame(ipf);
+            chip8.set_seed(8);
+            chip8.set_engine(engine);
+            chip8
+        })
+        .collect();
+        for frame in 0..frames {
+            for chip8 in cores.iter_mut() {
+                chip8.run_frame();
+            }
+            assert_same_state(&cores[0], &cores[1], &format!("{} frame {}", what, frame));
+        }
+    }
+
+    /// A random program that stays inside itself: jumps and calls land on
+    /// its own instructions, I points past it, and it ends jumping back to
+    /// the start.
+    fn random_program(rng: &mut rand::rngs::StdRng, len: u16) -> Vec<u8> {
+        let mut opcodes: Vec<u16> = Vec::new();
+        for _ in 0..len {
+            let x: u16 = rng.gen_range(0..16) << 8;
+            let y: u16 = rng.gen_range(0..16) << 4;
+            let nn: u16 = rng.gen::<u8>() as u16;
+            let target: u16 = 0x200 + 2 * rng.gen_range(0..len);
+            let pick = |rng: &mut rand::rngs::StdRng, ops: &[u16]| ops[rng.gen_range(0..ops.len())];
+            opcodes.push(match rng.gen_range(0..20) {
+                0 => 0x00E0,
+                1 => 0x00EE,
+                2 => 0x1000 | target,
+                3 => 0x2000 | target,
+                4 => 0x3000 | x | nn,
+                5 => 0x4000 | x | nn,
+                6 => 0x5000 | x | y,
+                7 => 0x6000 | x | nn,
+                8 => 0x7000 | x | nn,
+                9..=11 => 0x8000 | x | y | pick(rng, &[0, 1, 2, 3, 4, 5, 6, 7, 0xE]),
+                12 => 0x9000 | x | y,
+                13 => 0xA000 | rng.gen_range(0x600..0x700),
+                14 => 0xC000 | x | nn,
+                15 => 0xD000 | x | y | rng.gen_range(0..16),
+                16 => 0xE000 | x | pick(rng, &[0x9E, 0xA1]),
+                17 => 0xF000 | x | pick(rng, &[0x07, 0x15, 0x18, 0x29]),
+                18 => 0xF033 | x,
+                _ => 0xF000 | x | pick(rng, &[0x55, 0x65]),
+            });
+        }
+        opcodes.extend([0x1200, 0x1200]);
+        opcodes.iter().flat_map(|op| op.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn test_compiled_engine_matches_interpreter() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(48);
+        for i in 0..200 {
+            let len: u16 = rng.gen_range(1..40);
+            let rom: Vec<u8> = random_program(&mut rng, len);
+            // I only moves where the program puts it, so it stays in bounds
+            let quirks = Chip8Quirks {
+                shift_uses_vy: rng.gen(),
+                jump_uses_v0: rng.gen(),
+                load_store_increments_i: false,
+                logic_resets_vf: rng.gen(),
+            };
+            let ipf: u32 = rng.gen_range(1..20);
+            assert_engines_agree(&rom, quirks, ipf, 30, &format!("random program {}", i));
+        }
+
+        for mix in bench::Chip8BenchMix::all() {
+            for quirks in [Chip8Quirks::cosmac(), Chip8Quirks::modern()] {
+                assert_engines_agree(&mix.rom(), quirks, 7, 300, mix.name());
+            }
+        }
+    }
+
+    #[test]
+    fn test_compiled_engine_sees_quirks_from_control() {
+        use crate::graphics::control_adapter::*;
+        let source = "
+loop:   LD V1, 0x81
+        LD V2, 0x06
+        SHR V1, V2      ; VX or VY shifted
+        ADD V3, V1
+        OR V4, V3       ; VF reset or not
+        ADD V5, VF
+        JP loop
+";
+        let rom: Vec<u8> = asm::assemble(source).unwrap();
+        let mut cores: Vec<(Chip8Core, ControlAdapter)> = [
+            blocks::Chip8Engine::Interpreter,
+            blocks::Chip8Engine::Compiled,
+        ]
+        .into_iter()
+        .map(|engine| {
+            let mut chip8 = Chip8Core::from_rom(rom.clone(), true, &GraphicsAdapter::new());
+            let ca = ControlAdapter::new();
+            chip8.attach_control(&ca);
+            chip8.set_quirks(Chip8Quirks::cosmac());
+            chip8.set_instrs_per_frame(9);
+            chip8.set_engine(engine);
+            (chip8, ca)
+        })
+        .collect();
+        for frame in 0..40 {
+            for (chip8, ca) in cores.iter_mut() {
+                if frame == 20 {
+                    ca.send(Chip8Command::SetQuirks(Chip8Quirks::modern()))
+                        .unwrap();
+                    chip8.handle_commands();
+                }
+                chip8.run_frame();
+            }
+            assert_same_state(&cores[0].0, &cores[1].0, &format!("frame {}", frame));
+        }
+        assert_eq!(cores[1].0.quirks(), Chip8Quirks::modern());
+    }
+
+    #[test]
+    fn test_compiled_engine_interprets_modified_code() {
+        let source = "
+loop:   CALL target
+        ADD V1, 1
+        LD V0, 0x72
+        LD I, target
+        LD [I], V1      ; target becomes ADD V2, V1's value
+        JP loop
+target: ADD V2, 0
+        RET
+";
+        let rom: Vec<u8> = asm::assemble(source).unwrap();
+        assert_engines_agree(&rom, Chip8Quirks::default(), 5, 100, "self-modifying loop");
+
+        let mut chip8 = Chip8Core::from_rom(rom, true, &GraphicsAdapter::new());
+        chip8.set_engine(blocks::Chip8Engine::Compiled);
+        for _ in 0..10 {
+            chip8.run_frame();
+        }
+        assert!(chip8.blocks.is_modified(0x20C));
+        assert!(!chip8.blocks.is_modified(0x200));
+
+        chip8.reset();
+        assert!(!chip8.blocks.is_modified(0x20C));
+    }
+
+    #[test]
+    fn test_math_flags_with_equal_operands_and_vf_destination() {
+        let source = "
+        LD V1, 7
+        LD V2, 7
+        SUB V1, V2      ; no borrow when equal
+        LD V5, VF
+        LD V1, 7
+        SUBN V1, V2
+        LD V6, VF
+        LD VF, 200
+        LD V3, 100
+        ADD VF, V3      ; VF gets the carry, not the sum
+        LD V7, VF
+        LD VF, 4
+        LD V3, 5
+        SUB VF, V3      ; VF gets the borrow flag, not 255
+        LD V8, VF
+        LD VF, 0x81
+        SHR VF, VF      ; VF gets the bit shifted out, not 0x40
+        LD V9, VF
+hang:   JP hang
+";
+        let rom: Vec<u8> = asm::assemble(source).unwrap();
+        for engine in [
+            blocks::Chip8Engine::Interpreter,
+            blocks::Chip8Engine::Compiled,
+        ] {
+            let mut chip8 = Chip8Core::from_rom(rom.clone(), true, &GraphicsAdapter::new());
+            chip8.set_quirks(Chip8Quirks::modern());
+            chip8.set_engine(engine);
+            for _ in 0..5 {
+                chip8.run_frame();
+            }
+            assert_eq!(chip8.regs.v_regs[1], 0, "{}", engine);
+            assert_eq!(chip8.regs.v_regs[5..10], [1, 1, 1, 0, 1], "{}", engine);
+        }
+    }
+
+    #[test]
+    fn test_fetch_past_end_of_memory_fails() {
+        let mut chip8 = test_init();
+        for pc in [0xFFF, 0x1000, 0x10FE] {
+            chip8.regs.pc = pc;
+            assert!(chip8.tick().is_err(), "PC {:#05X}", pc);
+            assert_eq!(chip8.state_snapshot().instr, "???");
+        }
+    }
+
+    #[test]
+    fn test_incr_index_leaves_vf() {
+        let mut chip8 = test_init();
+        chip8.regs.index_reg = 0xFFE;
+        chip8.regs.v_regs[3] = 4;
+        chip8.regs.v_regs[0xF] = 7;
+        test_exec(
+            &mut chip8,
+            Chip8Instr::Extra(Chip8ExtraInstr::IncrIndex(Chip8SingleRegOp { reg: 3 })),
+        );
+        assert_eq!(chip8.regs.index_reg, 0x1002);
+        assert_eq!(chip8.regs.v_regs[0xF], 7);
+    }
+
+    #[test]
+    fn test_generated_vectors_pass_and_cover_every_quirk() {
+        let generated = vectors::generate_vectors(7, 3);
+        assert_eq!(generated.len(), vectors::VECTOR_OPCODES.len());
+        for (name, fixed, mask) in vectors::VECTOR_OPCODES {
+            let cases: &Vec<vectors::Chip8TestVector> = &generated[name];
+            for quirks in vectors::all_quirks() {
+                assert_eq!(
+                    cases.iter().filter(|v| v.quirks == quirks).count(),
+                    3,
+                    "{} under {:?}",
+                    name,
+                    quirks
+                );
+            }
+            for vector in cases.iter() {
+                assert_eq!(
+                    vector.opcode & mask,
+                    fixed,
+                    "{} made {:04X}",
+                    name,
+                    vector.opcode
+                );
+                if let Err(e) = vectors::check_vector(vector) {
+                    panic!("{:04X} under {:?}: {}", vector.opcode, vector.quirks, e);
+                }
+            }
+            let json: String = vectors::vectors_to_json(cases);
+            let parsed: Vec<vectors::Chip8TestVector> = serde_json::from_str(&json).unwrap();
+            assert_eq!(&parsed, cases);
+        }
+        for name in ["DXYN", "FX33", "FX55", "FX65"] {
+            assert!(
+                generated[name].iter().any(|v| v.error),
+                "No {} running off the end of memory",
+                name
+            );
+        }
+        assert!(generated["BNNN"].iter().any(|v| v.expected.pc > 0xFFF));
+    }
 }
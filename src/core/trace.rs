@@ -0,0 +1,322 @@
+use super::instrs::Chip8Instr;
+use super::{Chip8Regs, Chip8Timers};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{LineWriter, Write};
+use std::str::FromStr;
+
+/// Inclusive range of program counter values to trace, e.g. `0x200-0x2FF`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chip8TraceRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl Chip8TraceRange {
+    pub fn contains(&self, pc: u16) -> bool {
+        self.start <= pc && pc <= self.end
+    }
+}
+
+/// Parses a hex address, with or without a `0x` prefix.
+pub fn parse_addr(s: &str) -> Result<u16, String> {
+    let s = s.trim();
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u16::from_str_radix(digits, 16).map_err(|e| format!("Bad address {:?}: {}", s, e))
+}
+
+impl FromStr for Chip8TraceRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = match s.split_once('-') {
+            Some(v) => v,
+            None => return Err(format!("Expected START-END, got {:?}", s)),
+        };
+        Ok(Chip8TraceRange {
+            start: parse_addr(start)?,
+            end: parse_addr(end)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chip8TraceFormat {
+    /// Fixed-width columns, for `diff`.
+    Text,
+    /// One JSON object per line, for scripts.
+    Json,
+    /// A line laid out by a template, to match another emulator's trace.
+    Template(Chip8TraceTemplate),
+}
+
+/// A value a template can print.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Chip8TraceValue {
+    Cycle,
+    Pc,
+    Opcode,
+    Instr,
+    /// All of V0-VF, separated by spaces.
+    AllV,
+    V(usize),
+    I,
+    Sp,
+    Dt,
+    St,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Chip8TracePart {
+    Literal(String),
+    Field {
+        value: Chip8TraceValue,
+        width: usize,
+        radix: char,
+    },
+}
+
+/// A trace line layout, e.g. `{pc:x} {opcode:x} V0={v0} I={i:03X}`.
+///
+/// Fields are `cycle`, `pc`, `opcode`, `instr`, `v0` to `vf`, `v` (all
+/// sixteen, space separated), `i`, `sp`, `dt` and `st`, each optionally
+/// followed by `:`, a zero-padded width and `X`, `x` or `d` for upper- or
+/// lowercase hex or decimal. Numbers default to uppercase hex at their
+/// natural width, except `cycle`, which is unpadded decimal; `instr`'s width
+/// pads it with spaces. `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chip8TraceTemplate {
+    parts: Vec<Chip8TracePart>,
+}
+
+impl FromStr for Chip8TraceTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<Chip8TracePart> = Vec::new();
+        let mut literal: String = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut field: String = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => return Err(format!("Unclosed {{ in {:?}", s)),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Chip8TracePart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(parse_field(&field)?);
+                }
+                '}' => return Err(format!("Unmatched }} in {:?}", s)),
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Chip8TracePart::Literal(literal));
+        }
+        Ok(Chip8TraceTemplate { parts })
+    }
+}
+
+fn parse_field(field: &str) -> Result<Chip8TracePart, String> {
+    let (name, spec) = field.split_once(':').unwrap_or((field, ""));
+    let (value, width) = match name.to_ascii_lowercase().as_str() {
+        "cycle" => (Chip8TraceValue::Cycle, 0),
+        "pc" => (Chip8TraceValue::Pc, 4),
+        "opcode" => (Chip8TraceValue::Opcode, 4),
+        "instr" => (Chip8TraceValue::Instr, 0),
+        "v" => (Chip8TraceValue::AllV, 2),
+        "i" => (Chip8TraceValue::I, 4),
+        "sp" => (Chip8TraceValue::Sp, 1),
+        "dt" => (Chip8TraceValue::Dt, 2),
+        "st" => (Chip8TraceValue::St, 2),
+        reg => match reg.strip_prefix('v').map(|n| usize::from_str_radix(n, 16)) {
+            Some(Ok(n)) if reg.len() == 2 => (Chip8TraceValue::V(n), 2),
+            _ => return Err(format!("Unknown trace field {{{}}}", field)),
+        },
+    };
+    let radix: char = match spec.chars().last() {
+        Some(r @ ('x' | 'X' | 'd')) => r,
+        _ if value == Chip8TraceValue::Cycle => 'd',
+        _ => 'X',
+    };
+    let digits: &str = spec.trim_end_matches(['x', 'X', 'd']);
+    let width: usize = match digits {
+        "" => width,
+        _ => digits
+            .parse()
+            .map_err(|_| format!("Bad width in trace field {{{}}}", field))?,
+    };
+    Ok(Chip8TracePart::Field {
+        value,
+        width,
+        radix,
+    })
+}
+
+fn push_number(line: &mut String, n: u64, width: usize, radix: char) {
+    let _ = match radix {
+        'x' => write!(line, "{:0width$x}", n, width = width),
+        'd' => write!(line, "{:0width$}", n, width = width),
+        _ => write!(line, "{:0width$X}", n, width = width),
+    };
+}
+
+/// Writes one line per executed instruction, with the machine state as it was
+/// just before the instruction ran:
+///
+/// `000000000001 PC:0200 OP:00E0 CLS              V0:00 .. VF:00 I:0000 SP:0 DT:00 ST:00`
+///
+/// Every field is fixed width so traces from two runs can be compared with a
+/// plain `diff`. This layout is this project's own, not any other emulator's;
+/// to diff against another emulator's trace, give a `Chip8TraceTemplate`
+/// that reproduces its columns. With `Chip8TraceFormat::Json` each line is
+/// instead an object with the same fields: `cycle`, `pc`, `opcode`, `instr`,
+/// `v`, `i`, `sp`, `dt` and `st`.
+pub struct Chip8Tracer {
+    out: Box<dyn Write + Send>,
+    range: Option<Chip8TraceRange>,
+    format: Chip8TraceFormat,
+}
+
+impl Chip8Tracer {
+    pub fn new(out: Box<dyn Write + Send>) -> Chip8Tracer {
+        Chip8Tracer {
+            out,
+            range: None,
+            format: Chip8TraceFormat::Text,
+        }
+    }
+
+    pub fn to_file(path: &str) -> std::io::Result<Chip8Tracer> {
+        let f: fs::File = fs::File::create(path)?;
+        Ok(Chip8Tracer::new(Box::new(LineWriter::new(f))))
+    }
+
+    pub fn with_range(mut self, range: Chip8TraceRange) -> Chip8Tracer {
+        self.range = Some(range);
+        self
+    }
+
+    pub fn with_format(mut self, format: Chip8TraceFormat) -> Chip8Tracer {
+        self.format = format;
+        self
+    }
+
+    pub(super) fn record(
+        &mut self,
+        cycle: u64,
+        opcode: u16,
+        instr: &Chip8Instr,
+        regs: &Chip8Regs,
+        timers: &Chip8Timers,
+        sp: usize,
+    ) -> std::io::Result<()> {
+        if let Some(range) = self.range {
+            if !range.contains(regs.pc) {
+                return Ok(());
+            }
+        }
+        if let Chip8TraceFormat::Template(template) = &self.format {
+            let line: String = template.render(cycle, opcode, instr, regs, timers, sp);
+            return writeln!(self.out, "{}", line);
+        }
+        if self.format == Chip8TraceFormat::Json {
+            let line = serde_json::json!({
+                "cycle": cycle,
+                "pc": regs.pc,
+                "opcode": opcode,
+                "instr": instr.to_string(),
+                "v": regs.v_regs,
+                "i": regs.index_reg,
+                "sp": sp,
+                "dt": timers.delay,
+                "st": timers.sound,
+            });
+            return writeln!(self.out, "{}", line);
+        }
+        let mut line: String = format!(
+            "{:012} PC:{:04X} OP:{:04X} {:<16}",
+            cycle,
+            regs.pc,
+            opcode,
+            instr.to_string()
+        );
+        for (i, v) in regs.v_regs.iter().enumerate() {
+            line.push_str(&format!(" V{:X}:{:02X}", i, v));
+        }
+        line.push_str(&format!(
+            " I:{:04X} SP:{:X} DT:{:02X} ST:{:02X}",
+            regs.index_reg, sp, timers.delay, timers.sound
+        ));
+        writeln!(self.out, "{}", line)
+    }
+}
+
+impl Chip8TraceTemplate {
+    fn render(
+        &self,
+        cycle: u64,
+        opcode: u16,
+        instr: &Chip8Instr,
+        regs: &Chip8Regs,
+        timers: &Chip8Timers,
+        sp: usize,
+    ) -> String {
+        let mut line: String = String::new();
+        for part in self.parts.iter() {
+            let (value, width, radix) = match part {
+                Chip8TracePart::Literal(text) => {
+                    line.push_str(text);
+                    continue;
+                }
+                Chip8TracePart::Field {
+                    value,
+                    width,
+                    radix,
+                } => (*value, *width, *radix),
+            };
+            let n: u64 = match value {
+                Chip8TraceValue::Instr => {
+                    let _ = write!(line, "{:<width$}", instr.to_string(), width = width);
+                    continue;
+                }
+                Chip8TraceValue::AllV => {
+                    for (i, v) in regs.v_regs.iter().enumerate() {
+                        if i > 0 {
+                            line.push(' ');
+                        }
+                        push_number(&mut line, *v as u64, width, radix);
+                    }
+                    continue;
+                }
+                Chip8TraceValue::Cycle => cycle,
+                Chip8TraceValue::Pc => regs.pc as u64,
+                Chip8TraceValue::Opcode => opcode as u64,
+                Chip8TraceValue::V(n) => regs.v_regs[n] as u64,
+                Chip8TraceValue::I => regs.index_reg as u64,
+                Chip8TraceValue::Sp => sp as u64,
+                Chip8TraceValue::Dt => timers.delay as u64,
+                Chip8TraceValue::St => timers.sound as u64,
+            };
+            push_number(&mut line, n, width, radix);
+        }
+        line
+    }
+}
@@ -0,0 +1,633 @@
+//! Single-step test vectors: a machine state, one instruction and quirks,
+//! and the state after executing it. `check_vector` runs one against
+//! `Chip8Core::execute`; `generate_vectors` makes them from a reference model
+//! written from the CHIP-8 documentation rather than from the core.
+//!
+//! In JSON, a vector looks like
+//!
+//! ```json
+//! {"opcode": "8AB5", "quirks": "modern",
+//!  "initial": {"pc": 512, "i": 768, "v": [0, ..., 0], "stack": [514],
+//!              "memory": [{"addr": 512, "bytes": "8AB5"}]},
+//!  "expected": {"pc": 514, ...}}
+//! ```
+//!
+//! Memory that isn't listed is zero to start with and must stay untouched.
+//! `stack`, `dt`, `st`, `keys` (held, bit N for key N), `released` (let go
+//! this frame, for `FX0A`), `memory` and `display` (32 rows of 64 bits in
+//! hex, leftmost pixel highest) may be left out; `seed` seeds `CXNN` and
+//! `error` says execution fails.
+
+use super::instrs::Chip8Instr;
+use super::quirks::Chip8Quirks;
+use super::Chip8Core;
+use crate::graphics::graphics_adapter::GraphicsAdapter;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+
+const MEM_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Chip8TestVector {
+    #[serde(serialize_with = "opcode_to_hex", deserialize_with = "opcode_from_hex")]
+    pub opcode: u16,
+    pub quirks: Chip8Quirks,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    pub initial: Chip8VectorState,
+    pub expected: Chip8VectorState,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub error: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Chip8VectorState {
+    pub pc: u16,
+    pub i: u16,
+    pub v: [u8; 16],
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stack: Vec<u16>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub dt: u8,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub st: u8,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub keys: u16,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub released: u16,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub memory: Vec<Chip8VectorMemory>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Chip8VectorMemory {
+    pub addr: u16,
+    /// Hex, two digits a byte.
+    pub bytes: String,
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+fn is_zero<T: Default + PartialEq>(n: &T) -> bool {
+    *n == T::default()
+}
+
+fn opcode_to_hex<S: Serializer>(opcode: &u16, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&format!("{:04X}", opcode))
+}
+
+fn opcode_from_hex<'de, D: Deserializer<'de>>(d: D) -> Result<u16, D::Error> {
+    let text: String = String::deserialize(d)?;
+    u16::from_str_radix(&text, 16).map_err(serde::de::Error::custom)
+}
+
+fn parse_hex_bytes(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(2) {
+        return Err(format!("Odd number of hex digits in {:?}", text));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|n| u8::from_str_radix(&text[n..n + 2], 16).map_err(|e| format!("{:?}: {}", text, e)))
+        .collect()
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+impl Chip8VectorState {
+    /// All of memory: zero, with the listed ranges laid over it.
+    fn memory_image(&self) -> Result<Vec<u8>, String> {
+        let mut mem: Vec<u8> = vec![0; MEM_SIZE];
+        self.overlay_memory(&mut mem)?;
+        Ok(mem)
+    }
+
+    fn overlay_memory(&self, mem: &mut [u8]) -> Result<(), String> {
+        for range in self.memory.iter() {
+            let bytes: Vec<u8> = parse_hex_bytes(&range.bytes)?;
+            let start: usize = range.addr as usize;
+            mem.get_mut(start..start + bytes.len())
+                .ok_or_else(|| format!("Memory at {:#05X} runs off the end", start))?
+                .copy_from_slice(&bytes);
+        }
+        Ok(())
+    }
+
+    fn display_rows(&self) -> Result<Option<[u64; 32]>, String> {
+        let rows: &Vec<String> = match &self.display {
+            Some(rows) => rows,
+            None => return Ok(None),
+        };
+        let mut out: [u64; 32] = [0; 32];
+        if rows.len() != out.len() {
+            return Err(format!("Display has {} rows, not 32", rows.len()));
+        }
+        for (row, text) in out.iter_mut().zip(rows.iter()) {
+            *row = u64::from_str_radix(text, 16).map_err(|e| format!("{:?}: {}", text, e))?;
+        }
+        Ok(Some(out))
+    }
+
+    /// This state with memory, and the display if `like` has one, read back
+    /// from a full machine in the same shape as `like`.
+    fn from_machine(m: &Chip8RefMachine, like: &Chip8VectorState) -> Chip8VectorState {
+        Chip8VectorState {
+            pc: m.pc,
+            i: m.i,
+            v: m.v,
+            stack: m.stack.clone(),
+            dt: m.dt,
+            st: m.st,
+            keys: m.keys,
+            released: m.released,
+            memory: like
+                .memory
+                .iter()
+                .map(|range| {
+                    let start: usize = range.addr as usize;
+                    let len: usize = range.bytes.len() / 2;
+                    Chip8VectorMemory {
+                        addr: range.addr,
+                        bytes: hex_bytes(&m.mem[start..start + len]),
+                    }
+                })
+                .collect(),
+            display: like
+                .display
+                .as_ref()
+                .map(|_| m.display.iter().map(|r| format!("{:016X}", r)).collect()),
+        }
+    }
+}
+
+/// A whole machine, as the reference model and the runner see it.
+#[derive(Clone)]
+struct Chip8RefMachine {
+    pc: u16,
+    i: u16,
+    v: [u8; 16],
+    stack: Vec<u16>,
+    dt: u8,
+    st: u8,
+    keys: u16,
+    released: u16,
+    mem: Vec<u8>,
+    display: [u64; 32],
+}
+
+impl Chip8RefMachine {
+    fn from_state(state: &Chip8VectorState) -> Result<Chip8RefMachine, String> {
+        Ok(Chip8RefMachine {
+            pc: state.pc,
+            i: state.i,
+            v: state.v,
+            stack: state.stack.clone(),
+            dt: state.dt,
+            st: state.st,
+            keys: state.keys,
+            released: state.released,
+            mem: state.memory_image()?,
+            display: state.display_rows()?.unwrap_or([0; 32]),
+        })
+    }
+
+    /// Executes `opcode`, returning false if it fails. Straight from the
+    /// documented behaviour of each instruction, with the quirks applied.
+    fn step(&mut self, opcode: u16, quirks: Chip8Quirks, seed: Option<u64>) -> bool {
+        let x: usize = ((opcode >> 8) & 0xF) as usize;
+        let y: usize = ((opcode >> 4) & 0xF) as usize;
+        let n: u8 = (opcode & 0xF) as u8;
+        let nn: u8 = (opcode & 0xFF) as u8;
+        let nnn: u16 = opcode & 0xFFF;
+        let (vx, vy) = (self.v[x], self.v[y]);
+        let skip = |m: &mut Chip8RefMachine, cond: bool| {
+            if cond {
+                m.pc += 2;
+            }
+        };
+        self.pc += 2;
+        // Running off the end of memory at I fails, with nothing but the PC
+        // changed
+        let at_i: usize = match (opcode >> 12, nn) {
+            (0xD, _) => n as usize,
+            (0xF, 0x33) => 3,
+            (0xF, 0x55 | 0x65) => x + 1,
+            _ => 0,
+        };
+        if at_i > 0 && self.i as usize + at_i > MEM_SIZE {
+            return false;
+        }
+        match opcode >> 12 {
+            0x0 if opcode == 0x00E0 => self.display = [0; 32],
+            0x0 => match self.stack.pop() {
+                Some(addr) => self.pc = addr,
+                None => return false,
+            },
+            0x1 => self.pc = nnn,
+            0x2 => {
+                self.stack.push(self.pc);
+                self.pc = nnn;
+            }
+            0x3 => skip(self, vx == nn),
+            0x4 => skip(self, vx != nn),
+            0x5 => skip(self, vx == vy),
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = vx.wrapping_add(nn),
+            0x8 => {
+                let shifted: u8 = if quirks.shift_uses_vy { vy } else { vx };
+                let logic_flag: Option<u8> = if quirks.logic_resets_vf {
+                    Some(0)
+                } else {
+                    None
+                };
+                let (result, flag): (u8, Option<u8>) = match n {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, logic_flag),
+                    0x2 => (vx & vy, logic_flag),
+                    0x3 => (vx ^ vy, logic_flag),
+                    0x4 => (
+                        vx.wrapping_add(vy),
+                        Some((vx as u16 + vy as u16 > 0xFF) as u8),
+                    ),
+                    0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+                    0x6 => (shifted >> 1, Some(shifted & 1)),
+                    0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+                    _ => (shifted << 1, Some(shifted >> 7)),
+                };
+                self.v[x] = result;
+                if let Some(flag) = flag {
+                    self.v[0xF] = flag;
+                }
+            }
+            0x9 => skip(self, vx != vy),
+            0xA => self.i = nnn,
+            0xB => {
+                let offset: u8 = if quirks.jump_uses_v0 { self.v[0] } else { vx };
+                self.pc = nnn + offset as u16;
+            }
+            0xC => {
+                let mut rng: StdRng = StdRng::seed_from_u64(seed.unwrap_or(0));
+                self.v[x] = rng.gen::<u8>() & nn;
+            }
+            0xD => {
+                let (left, top) = (vx as usize % 64, vy as usize % 32);
+                let mut collision: bool = false;
+                for row in 0..(n as usize).min(32 - top) {
+                    let sprite: u64 = ((self.mem[self.i as usize + row] as u64) << 56) >> left;
+                    collision |= self.display[top + row] & sprite != 0;
+                    self.display[top + row] ^= sprite;
+                }
+                self.v[0xF] = collision as u8;
+            }
+            0xE => {
+                if vx > 0xF {
+                    return false;
+                }
+                let down: bool = self.keys & (1 << vx) != 0;
+                skip(self, if nn == 0x9E { down } else { !down });
+            }
+            _ => match nn {
+                0x07 => self.v[x] = self.dt,
+                0x0A => match (0..16).find(|k| self.released & (1 << k) != 0) {
+                    Some(k) => {
+                        self.released &= !(1 << k);
+                        self.v[x] = k as u8;
+                    }
+                    None => self.pc -= 2,
+                },
+                0x15 => self.dt = vx,
+                0x18 => self.st = vx,
+                0x1E => self.i = self.i.wrapping_add(vx as u16),
+                0x29 => self.i = (vx & 0xF) as u16 * 5,
+                0x33 => {
+                    let at: usize = self.i as usize;
+                    self.mem[at..at + 3].copy_from_slice(&[vx / 100, vx / 10 % 10, vx % 10]);
+                }
+                0x55 | 0x65 => {
+                    let at: usize = self.i as usize;
+                    if nn == 0x55 {
+                        self.mem[at..=at + x].copy_from_slice(&self.v[..=x]);
+                    } else {
+                        self.v[..=x].copy_from_slice(&self.mem[at..=at + x]);
+                    }
+                    if quirks.load_store_increments_i {
+                        self.i += x as u16 + 1;
+                    }
+                }
+                _ => return false,
+            },
+        }
+        true
+    }
+}
+
+impl Chip8Core {
+    /// Puts a fresh core in `state`, with memory outside the listed ranges
+    /// zeroed, font included.
+    fn for_vector(vector: &Chip8TestVector) -> Result<Chip8Core, String> {
+        let state: &Chip8VectorState = &vector.initial;
+        let mut core: Chip8Core = Chip8Core::from_rom(Vec::new(), true, &GraphicsAdapter::new());
+        core.set_quirks(vector.quirks);
+        if let Some(seed) = vector.seed {
+            core.set_seed(seed);
+        }
+        core.regs.pc = state.pc;
+        core.regs.index_reg = state.i;
+        core.regs.v_regs = state.v;
+        core.stack = state.stack.iter().copied().collect();
+        core.timers.delay = state.dt;
+        core.timers.sound = state.st;
+        core.keypad.set_state(state.keys, state.released);
+        core.mem.memspace.copy_from_slice(&state.memory_image()?);
+        if let Some(rows) = state.display_rows()? {
+            for (y, row) in rows.iter().enumerate() {
+                for x in 0..64 {
+                    core._disp.set_pixel(x, y, row & (1 << (63 - x)) != 0);
+                }
+            }
+        }
+        Ok(core)
+    }
+
+    fn vector_machine(&self) -> Chip8RefMachine {
+        let mut display: [u64; 32] = [0; 32];
+        for (y, row) in display.iter_mut().enumerate() {
+            for x in 0..64 {
+                *row |= (self._disp.pixel(x, y) as u64) << (63 - x);
+            }
+        }
+        Chip8RefMachine {
+            pc: self.regs.pc,
+            i: self.regs.index_reg,
+            v: self.regs.v_regs,
+            stack: self.stack.iter().copied().collect(),
+            dt: self.timers.delay,
+            st: self.timers.sound,
+            keys: self
+                .keypad
+                .state()
+                .iter()
+                .enumerate()
+                .fold(0, |mask, (k, d)| mask | (*d as u16) << k),
+            released: self.keypad.released_mask(),
+            mem: self.mem.memspace.to_vec(),
+            display,
+        }
+    }
+}
+
+/// Runs `vector` through `Chip8Core::execute` and describes how the result
+/// differs from what was expected, if it does.
+pub fn check_vector(vector: &Chip8TestVector) -> Result<(), String> {
+    let mut core: Chip8Core = Chip8Core::for_vector(vector)?;
+    let instr: Chip8Instr = Chip8Instr::from_u16(vector.opcode).map_err(|e| e.to_string())?;
+    let failed: bool = core.execute(instr).is_err();
+    let machine: Chip8RefMachine = core.vector_machine();
+    let actual: Chip8VectorState = Chip8VectorState::from_machine(&machine, &vector.expected);
+
+    let mut problems: Vec<String> = Vec::new();
+    if failed != vector.error {
+        problems.push(format!("error: expected {}, got {}", vector.error, failed));
+    }
+    let (e, a) = (&vector.expected, &actual);
+    let mut compare = |name: &str, expected: String, actual: String| {
+        if expected != actual {
+            problems.push(format!("{}: expected {}, got {}", name, expected, actual));
+        }
+    };
+    compare("pc", format!("{:#05X}", e.pc), format!("{:#05X}", a.pc));
+    compare("i", format!("{:#05X}", e.i), format!("{:#05X}", a.i));
+    compare("v", format!("{:02X?}", e.v), format!("{:02X?}", a.v));
+    compare(
+        "stack",
+        format!("{:03X?}", e.stack),
+        format!("{:03X?}", a.stack),
+    );
+    compare("dt", e.dt.to_string(), a.dt.to_string());
+    compare("st", e.st.to_string(), a.st.to_string());
+    compare("keys", format!("{:04X}", e.keys), format!("{:04X}", a.keys));
+    compare(
+        "released",
+        format!("{:04X}", e.released),
+        format!("{:04X}", a.released),
+    );
+    if e.display != a.display {
+        problems.push(String::from("display differs"));
+    }
+
+    // Everything not listed in the expected state must be as it started
+    let mut expected_mem: Vec<u8> = vector.initial.memory_image()?;
+    e.overlay_memory(&mut expected_mem)?;
+    if let Some(addr) = (0..MEM_SIZE).find(|&n| expected_mem[n] != machine.mem[n]) {
+        problems.push(format!(
+            "memory at {:#05X}: expected {:02X}, got {:02X}",
+            addr, expected_mem[addr], machine.mem[addr]
+        ));
+    }
+
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(problems.join("; ")),
+    }
+}
+
+/// Each kind of instruction the generator covers, by name and the bits that
+/// are fixed. The unfixed nibbles are filled in at random.
+pub const VECTOR_OPCODES: [(&str, u16, u16); 34] = [
+    ("00E0", 0x00E0, 0xFFFF),
+    ("00EE", 0x00EE, 0xFFFF),
+    ("1NNN", 0x1000, 0xF000),
+    ("2NNN", 0x2000, 0xF000),
+    ("3XNN", 0x3000, 0xF000),
+    ("4XNN", 0x4000, 0xF000),
+    ("5XY0", 0x5000, 0xF00F),
+    ("6XNN", 0x6000, 0xF000),
+    ("7XNN", 0x7000, 0xF000),
+    ("8XY0", 0x8000, 0xF00F),
+    ("8XY1", 0x8001, 0xF00F),
+    ("8XY2", 0x8002, 0xF00F),
+    ("8XY3", 0x8003, 0xF00F),
+    ("8XY4", 0x8004, 0xF00F),
+    ("8XY5", 0x8005, 0xF00F),
+    ("8XY6", 0x8006, 0xF00F),
+    ("8XY7", 0x8007, 0xF00F),
+    ("8XYE", 0x800E, 0xF00F),
+    ("9XY0", 0x9000, 0xF00F),
+    ("ANNN", 0xA000, 0xF000),
+    ("BNNN", 0xB000, 0xF000),
+    ("CXNN", 0xC000, 0xF000),
+    ("DXYN", 0xD000, 0xF000),
+    ("EX9E", 0xE09E, 0xF0FF),
+    ("EXA1", 0xE0A1, 0xF0FF),
+    ("FX07", 0xF007, 0xF0FF),
+    ("FX0A", 0xF00A, 0xF0FF),
+    ("FX15", 0xF015, 0xF0FF),
+    ("FX18", 0xF018, 0xF0FF),
+    ("FX1E", 0xF01E, 0xF0FF),
+    ("FX29", 0xF029, 0xF0FF),
+    ("FX33", 0xF033, 0xF0FF),
+    ("FX55", 0xF055, 0xF0FF),
+    ("FX65", 0xF065, 0xF0FF),
+];
+
+/// Every combination of the quirk flags.
+pub fn all_quirks() -> Vec<Chip8Quirks> {
+    (0..16)
+        .map(|bits: u8| Chip8Quirks {
+            shift_uses_vy: bits & 1 != 0,
+            jump_uses_v0: bits & 2 != 0,
+            load_store_increments_i: bits & 4 != 0,
+            logic_resets_vf: bits & 8 != 0,
+        })
+        .collect()
+}
+
+/// A byte that's often one of the values flags and comparisons care about.
+fn interesting_byte(rng: &mut StdRng) -> u8 {
+    match rng.gen_range(0..8) {
+        0 => 0,
+        1 => 0xFF,
+        2 => 0x80,
+        3 => 0x01,
+        _ => rng.gen(),
+    }
+}
+
+/// One vector for an opcode matching `fixed` under `mask`. The state leans
+/// towards edge cases: VF and X = Y as operands, 0, 0x80 and 0xFF in
+/// registers, empty stacks, keys past F, and I and the PC at the very end of
+/// memory, where loads, stores and sprites run off it and `BNNN` can jump
+/// past it.
+fn random_vector(rng: &mut StdRng, fixed: u16, mask: u16, quirks: Chip8Quirks) -> Chip8TestVector {
+    let mut opcode: u16 = (rng.gen::<u16>() & !mask) | fixed;
+    let x: u16 = (opcode >> 8) & 0xF;
+    // Registers that are the same, or VF, are where the edge cases are
+    if mask & 0x00F0 == 0 && rng.gen_range(0..4) == 0 {
+        opcode = (opcode & !0x00F0) | (x << 4);
+    }
+    if mask & 0x0F00 == 0 && rng.gen_range(0..4) == 0 {
+        opcode |= 0x0F00;
+    }
+    let x: usize = ((opcode >> 8) & 0xF) as usize;
+    let y: usize = ((opcode >> 4) & 0xF) as usize;
+
+    let mut v: [u8; 16] = [0; 16];
+    v.iter_mut().for_each(|r| *r = interesting_byte(rng));
+    if rng.gen_range(0..4) == 0 {
+        v[y] = v[x];
+    }
+    let nn: u8 = (opcode & 0xFF) as u8;
+    if fixed & 0xF000 == 0x3000 || fixed & 0xF000 == 0x4000 {
+        // Half the time, make the skip happen
+        if rng.gen() {
+            v[x] = nn;
+        }
+    }
+    if fixed & 0xF0FF == 0xE09E || fixed & 0xF0FF == 0xE0A1 {
+        // Mostly real keys, sometimes past F, which is an error
+        v[x] = rng.gen_range(0..0x14);
+    }
+
+    let i: u16 = match fixed {
+        0xF01E if rng.gen() => rng.gen(),
+        _ if rng.gen_range(0..3) == 0 => rng.gen_range(0xFFC..0x1000),
+        _ => rng.gen_range(0x200..0xFFC),
+    };
+    let pc: u16 = match rng.gen_range(0..8) {
+        0 => 0xFFE,
+        _ => rng.gen_range(0x100..0x7FF) * 2,
+    };
+    let stack_len: usize = match fixed {
+        0x00EE => rng.gen_range(0..4),
+        _ => rng.gen_range(0..3),
+    };
+    let stack: Vec<u16> = (0..stack_len)
+        .map(|_| rng.gen_range(0x100..0x800) * 2)
+        .collect();
+
+    let mut memory: Vec<Chip8VectorMemory> = vec![Chip8VectorMemory {
+        addr: pc,
+        bytes: format!("{:04X}", opcode),
+    }];
+    let touches_i: bool = matches!(opcode >> 12, 0xD) || matches!(fixed, 0xF033 | 0xF055 | 0xF065);
+    if touches_i && (i as usize) < MEM_SIZE {
+        let len: usize = (MEM_SIZE - i as usize).min(16);
+        let data: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+        memory.push(Chip8VectorMemory {
+            addr: i,
+            bytes: hex_bytes(&data),
+        });
+    }
+    let display: Option<Vec<String>> = match opcode >> 12 {
+        0x0 | 0xD if fixed != 0x00EE => Some(
+            (0..32)
+                .map(|_| format!("{:016X}", rng.gen::<u64>() & rng.gen::<u64>()))
+                .collect(),
+        ),
+        _ => None,
+    };
+
+    let initial = Chip8VectorState {
+        pc,
+        i,
+        v,
+        stack,
+        dt: rng.gen(),
+        st: rng.gen(),
+        keys: rng.gen(),
+        released: if rng.gen() { rng.gen() } else { 0 },
+        memory,
+        display,
+    };
+    let seed: Option<u64> = (opcode >> 12 == 0xC).then(|| rng.gen());
+    let mut machine: Chip8RefMachine =
+        Chip8RefMachine::from_state(&initial).expect("Generated a bad state");
+    let ok: bool = machine.step(opcode, quirks, seed);
+    Chip8TestVector {
+        opcode,
+        quirks,
+        seed,
+        expected: Chip8VectorState::from_machine(&machine, &initial),
+        initial,
+        error: !ok,
+    }
+}
+
+/// `per_case` random vectors for every instruction under every combination
+/// of quirks, by instruction name, with expectations from the reference
+/// model.
+pub fn generate_vectors(
+    seed: u64,
+    per_case: usize,
+) -> BTreeMap<&'static str, Vec<Chip8TestVector>> {
+    let mut rng: StdRng = StdRng::seed_from_u64(seed);
+    let mut out: BTreeMap<&'static str, Vec<Chip8TestVector>> = BTreeMap::new();
+    for (name, fixed, mask) in VECTOR_OPCODES {
+        let vectors: &mut Vec<Chip8TestVector> = out.entry(name).or_default();
+        for quirks in all_quirks() {
+            for _ in 0..per_case {
+                vectors.push(random_vector(&mut rng, fixed, mask, quirks));
+            }
+        }
+    }
+    out
+}
+
+/// Vectors as a JSON array, one to a line so diffs stay readable.
+pub fn vectors_to_json(vectors: &[Chip8TestVector]) -> String {
+    let lines: Vec<String> = vectors
+        .iter()
+        .map(|v| serde_json::to_string(v).expect("Vectors always serialize"))
+        .collect();
+    format!("[\n{}\n]\n", lines.join(",\n"))
+}
@@ -0,0 +1,101 @@
+use crate::core::quirks::Chip8Quirks;
+use crossbeam::channel::{bounded, unbounded, Receiver, Sender};
+use simple_error::{simple_error, SimpleError};
+use std::time::Duration;
+
+/// Emulator controls, as opposed to keypad input for the ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chip8Command {
+    Pause,
+    Resume,
+    /// Reload the ROM and start over.
+    Reset,
+    /// Run a single frame while paused.
+    FrameAdvance,
+    /// Run as fast as possible instead of at 60 frames a second.
+    FastForward(bool),
+    SaveState(u8),
+    LoadState(u8),
+    /// Pause, then execute exactly this many instructions, finishing a frame
+    /// (timers, keys, vblank) whenever one fills up.
+    Step(u32),
+    /// Replace the ROM and reset.
+    LoadRom(Vec<u8>),
+    SetQuirks(Chip8Quirks),
+    SetInstrsPerFrame(u32),
+    /// Make `run_loop` return, ending the core thread.
+    Shutdown,
+}
+
+/// The core's reply once it has carried out a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chip8CommandAck {
+    pub result: Result<(), String>,
+    pub running: bool,
+    pub pc: u16,
+    pub cycles: u64,
+}
+
+pub struct Chip8ControlMessage {
+    pub command: Chip8Command,
+    /// Where to send the acknowledgement, if anyone is waiting for one.
+    pub reply: Option<Sender<Chip8CommandAck>>,
+}
+
+/// Command path from frontends and tools to the core, which handles commands
+/// between frames.
+#[derive(Clone)]
+pub struct ControlAdapter {
+    pub command_receiver: Receiver<Chip8ControlMessage>,
+    pub command_sender: Sender<Chip8ControlMessage>,
+}
+
+impl Default for ControlAdapter {
+    fn default() -> Self {
+        ControlAdapter::new()
+    }
+}
+
+impl ControlAdapter {
+    pub fn new() -> ControlAdapter {
+        let (cs, cr) = unbounded::<Chip8ControlMessage>();
+        ControlAdapter {
+            command_receiver: cr,
+            command_sender: cs,
+        }
+    }
+
+    /// Sends `command` without waiting for it to be carried out.
+    pub fn send(&self, command: Chip8Command) -> Result<(), SimpleError> {
+        self.command_sender
+            .send(Chip8ControlMessage {
+                command,
+                reply: None,
+            })
+            .map_err(|e| simple_error!("Core is gone: {}", e))
+    }
+
+    /// Sends `command` and returns the channel its acknowledgement will
+    /// arrive on.
+    pub fn request(&self, command: Chip8Command) -> Result<Receiver<Chip8CommandAck>, SimpleError> {
+        let (reply, ack) = bounded::<Chip8CommandAck>(1);
+        self.command_sender
+            .send(Chip8ControlMessage {
+                command,
+                reply: Some(reply),
+            })
+            .map_err(|e| simple_error!("Core is gone: {}", e))?;
+        Ok(ack)
+    }
+
+    /// Sends `command` and waits up to `timeout` for the core to carry it out.
+    pub fn call(
+        &self,
+        command: Chip8Command,
+        timeout: Duration,
+    ) -> Result<Chip8CommandAck, SimpleError> {
+        self.request(command)?
+            .recv_timeout(timeout)
+            .map_err(|e| simple_error!("No acknowledgement from the core: {}", e))
+    }
+}
@@ -0,0 +1,42 @@
+use crate::core::{Chip8MemorySnapshot, Chip8StateSnapshot};
+use crossbeam::channel::{unbounded, Receiver, Sender};
+
+pub enum Chip8DebugRequest {
+    ReadMemory,
+    WriteMemory { addr: u16, val: u8 },
+    ReadState,
+}
+
+pub enum Chip8DebugResponse {
+    Memory(Box<Chip8MemorySnapshot>),
+    State(Box<Chip8StateSnapshot>),
+}
+
+/// Request/response path for debugger views, alongside the `GraphicsAdapter`.
+/// The core thread answers requests between instructions.
+#[derive(Clone)]
+pub struct DebugAdapter {
+    pub request_receiver: Receiver<Chip8DebugRequest>,
+    pub request_sender: Sender<Chip8DebugRequest>,
+    pub response_receiver: Receiver<Chip8DebugResponse>,
+    pub response_sender: Sender<Chip8DebugResponse>,
+}
+
+impl Default for DebugAdapter {
+    fn default() -> Self {
+        DebugAdapter::new()
+    }
+}
+
+impl DebugAdapter {
+    pub fn new() -> DebugAdapter {
+        let (reqs, reqr) = unbounded::<Chip8DebugRequest>();
+        let (resps, respr) = unbounded::<Chip8DebugResponse>();
+        DebugAdapter {
+            request_receiver: reqr,
+            request_sender: reqs,
+            response_receiver: respr,
+            response_sender: resps,
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use crate::core::{analysis::disassemble_opcode, trace::parse_addr, Chip8Core, Chip8StateSnapshot};
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+
+/// How many instructions `continue` runs looking for a breakpoint before
+/// giving up, so a ROM that never reaches one doesn't hang the prompt.
+pub const CONTINUE_LIMIT: u64 = 10_000_000;
+
+const HELP: &str = "\
+step [N]        (s) run N instructions, default 1
+frame [N]       (f) run N whole frames, ignoring breakpoints
+continue [N]    (c) run until a breakpoint, at most N instructions
+break [ADDR]    (b) set a breakpoint, or list them
+delete [ADDR]   (d) remove a breakpoint, or all of them
+regs            (r) show registers, timers and stack
+mem ADDR [LEN]  (m) dump memory, default 64 bytes
+list [ADDR] [N] (l) disassemble N instructions, default 8 from PC
+screen          print the display
+reset           reset the core
+quit            (q) exit
+Addresses are hex. An empty line repeats the last command.";
+
+/// Line-oriented debugger driving a core directly, for stepping through a
+/// ROM without a window.
+#[derive(Default)]
+pub struct Chip8DebugRepl {
+    breakpoints: BTreeSet<u16>,
+    last_command: String,
+}
+
+fn parse_count(arg: Option<&str>, default: u64) -> Result<u64, String> {
+    match arg {
+        Some(n) => n.parse().map_err(|e| format!("Bad count {:?}: {}", n, e)),
+        None => Ok(default),
+    }
+}
+
+fn state_line(state: &Chip8StateSnapshot) -> String {
+    format!(
+        "{:#05X}: {:04X}  {:<16} cycle {}",
+        state.pc, state.opcode, state.instr, state.cycles
+    )
+}
+
+impl Chip8DebugRepl {
+    pub fn breakpoints(&self) -> &BTreeSet<u16> {
+        &self.breakpoints
+    }
+
+    pub fn set_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    /// Runs one command against `core` and returns what to print, or `None`
+    /// to quit.
+    pub fn execute(&mut self, core: &mut Chip8Core, line: &str) -> Result<Option<String>, String> {
+        let line: String = match line.trim() {
+            "" => self.last_command.clone(),
+            line => String::from(line),
+        };
+        self.last_command = line.clone();
+        let mut words = line.split_whitespace();
+        let command: &str = match words.next() {
+            Some(c) => c,
+            None => return Ok(Some(String::new())),
+        };
+        let arg1: Option<&str> = words.next();
+        let arg2: Option<&str> = words.next();
+
+        let out: String = match command {
+            "help" | "h" | "?" => String::from(HELP),
+            "step" | "s" => {
+                for _ in 0..parse_count(arg1, 1)? {
+                    core.step().map_err(|e| e.to_string())?;
+                }
+                state_line(&core.state_snapshot())
+            }
+            "frame" | "f" => {
+                for _ in 0..parse_count(arg1, 1)? {
+                    core.run_frame();
+                }
+                state_line(&core.state_snapshot())
+            }
+            "continue" | "c" => {
+                let limit: u64 = parse_count(arg1, CONTINUE_LIMIT)?;
+                let mut hit: bool = false;
+                for _ in 0..limit {
+                    core.step().map_err(|e| e.to_string())?;
+                    if self.breakpoints.contains(&core.pc()) {
+                        hit = true;
+                        break;
+                    }
+                }
+                let state: String = state_line(&core.state_snapshot());
+                match hit {
+                    true => format!("Breakpoint\n{}", state),
+                    false => format!("No breakpoint after {} instructions\n{}", limit, state),
+                }
+            }
+            "break" | "b" => match arg1 {
+                Some(addr) => {
+                    let addr: u16 = parse_addr(addr)?;
+                    self.breakpoints.insert(addr);
+                    format!("Breakpoint at {:#05X}", addr)
+                }
+                None if self.breakpoints.is_empty() => String::from("No breakpoints"),
+                None => self
+                    .breakpoints
+                    .iter()
+                    .map(|a| format!("{:#05X}", a))
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+            },
+            "delete" | "d" => match arg1 {
+                Some(addr) => {
+                    let addr: u16 = parse_addr(addr)?;
+                    match self.breakpoints.remove(&addr) {
+                        true => format!("Deleted breakpoint at {:#05X}", addr),
+                        false => format!("No breakpoint at {:#05X}", addr),
+                    }
+                }
+                None => {
+                    self.breakpoints.clear();
+                    String::from("Deleted all breakpoints")
+                }
+            },
+            "regs" | "r" => {
+                let state: Chip8StateSnapshot = core.state_snapshot();
+                let mut out: String = String::new();
+                for (i, v) in state.v_regs.iter().enumerate() {
+                    let sep: char = if i % 8 == 7 { '\n' } else { ' ' };
+                    let _ = write!(out, "V{:X}:{:02X}{}", i, v, sep);
+                }
+                let _ = writeln!(
+                    out,
+                    "I:{:04X} DT:{:02X} ST:{:02X}",
+                    state.index_reg, state.delay, state.sound
+                );
+                let stack: Vec<String> = state.stack.iter().map(|a| format!("{:03X}", a)).collect();
+                let _ = writeln!(out, "Stack: [{}]", stack.join(" "));
+                out.push_str(&state_line(&state));
+                out
+            }
+            "mem" | "m" => {
+                let start: usize = parse_addr(arg1.ok_or("mem needs an address")?)? as usize;
+                let len: usize = parse_count(arg2, 64)? as usize;
+                let mem: Vec<u8> = core.memory_snapshot().mem;
+                let end: usize = (start + len).min(mem.len());
+                let mut out: String = String::new();
+                for (i, chunk) in mem[start.min(end)..end].chunks(16).enumerate() {
+                    let bytes: Vec<String> = chunk.iter().map(|b| format!("{:02X}", b)).collect();
+                    let _ = writeln!(out, "{:#05X}: {}", start + i * 16, bytes.join(" "));
+                }
+                out.trim_end().to_string()
+            }
+            "list" | "l" => {
+                let mem: Vec<u8> = core.memory_snapshot().mem;
+                let start: usize = match arg1 {
+                    Some(addr) => parse_addr(addr)? as usize,
+                    None => core.pc() as usize,
+                };
+                let count: usize = parse_count(arg2, 8)? as usize;
+                let mut out: String = String::new();
+                for addr in (start..mem.len() - 1).step_by(2).take(count) {
+                    let opcode: u16 = u16::from_be_bytes([mem[addr], mem[addr + 1]]);
+                    let mark: &str = match self.breakpoints.contains(&(addr as u16)) {
+                        true => "*",
+                        false => " ",
+                    };
+                    let _ = writeln!(
+                        out,
+                        "{}{:#05X}: {:04X}  {}",
+                        mark,
+                        addr,
+                        opcode,
+                        disassemble_opcode(opcode)
+                    );
+                }
+                out.trim_end().to_string()
+            }
+            "screen" => core.display().to_string().trim_end().to_string(),
+            "reset" => {
+                core.reset();
+                state_line(&core.state_snapshot())
+            }
+            "quit" | "q" | "exit" => return Ok(None),
+            other => return Err(format!("Unknown command {:?}, try help", other)),
+        };
+        Ok(Some(out))
+    }
+
+    /// Reads commands from `input` until `quit` or end of input.
+    pub fn run(
+        &mut self,
+        core: &mut Chip8Core,
+        input: &mut dyn BufRead,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(out, "{}", state_line(&core.state_snapshot()))?;
+        loop {
+            write!(out, "(chip8) ")?;
+            out.flush()?;
+            let mut line: String = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            match self.execute(core, &line) {
+                Ok(Some(text)) if text.is_empty() => (),
+                Ok(Some(text)) => writeln!(out, "{}", text)?,
+                Ok(None) => return Ok(()),
+                Err(e) => writeln!(out, "Error: {}", e)?,
+            }
+        }
+    }
+}
@@ -1,18 +1,74 @@
 use crate::{
-    core::Chip8DisplayData, graphics::graphics_adapter::GraphicsAdapter, graphics::key_mapping::*,
+    config::{Chip8Config, Chip8Overrides, Chip8RomConfig, Chip8RomSettings},
+    core::{
+        keypad::Chip8KeyEvent,
+        quirks::Chip8Quirks,
+        rom_db::{sha1_hex, Chip8RomDatabase, Chip8RomInfo},
+        Chip8DisplayData, MAX_ROM_SIZE,
+    },
+    graphics::control_adapter::{Chip8Command, ControlAdapter},
+    graphics::debug_adapter::{Chip8DebugRequest, Chip8DebugResponse, DebugAdapter},
+    graphics::frontend::{Chip8Frontend, Chip8FrontendDriver},
+    graphics::graphics_adapter::GraphicsAdapter,
+    graphics::hotkeys::{Chip8HotkeyAction, Chip8Hotkeys},
+    graphics::inspector::Chip8Inspector,
+    graphics::key_mapping::{key_name, KeyMapping},
+    graphics::keymap_dialog::Chip8KeymapDialog,
+    graphics::memory_view::Chip8MemoryView,
+    graphics::phosphor::{Chip8PersistenceMode, Chip8Phosphor, DEFAULT_DECAY},
+    graphics::renderer::{self, Chip8DisplayRenderer},
+    graphics::rom_browser::Chip8RomBrowser,
 };
+use crossbeam::channel::Sender;
 use eframe::{
     egui::{self},
     epi,
 };
-use log::info;
+use log::{error, info};
+use std::fs;
+use std::path::{Path, PathBuf};
 
 pub struct Chip8EframeApp {
     fname: String,
     pub display_data: Chip8DisplayData,
+    new_frame: bool,
+    /// The core's frame count as of the last repaint.
+    last_vblank: u64,
     frame: Option<epi::Frame>,
     adapter: GraphicsAdapter,
-    last_key_state: [u8; 16],
+    driver: Option<Chip8FrontendDriver>,
+    pending_keys: Vec<Chip8KeyEvent>,
+    pending_commands: Vec<Chip8Command>,
+    tone: bool,
+    debug: Option<DebugAdapter>,
+    memory_view: Chip8MemoryView,
+    memory_request_pending: bool,
+    inspector: Chip8Inspector,
+    state_request_pending: bool,
+    renderer: Chip8DisplayRenderer,
+    phosphor: Chip8Phosphor,
+    key_mapping: KeyMapping,
+    keymap_dialog: Chip8KeymapDialog,
+    hotkeys: Chip8Hotkeys,
+    key_hints: Vec<(String, u8)>,
+    rom_browser: Chip8RomBrowser,
+    rom_db: Chip8RomDatabase,
+    rom_watcher: Option<Sender<PathBuf>>,
+    config: Chip8Config,
+    config_path: Option<PathBuf>,
+    overrides: Chip8Overrides,
+    /// The current ROM's hash and file name.
+    rom_hash: Option<(String, String)>,
+    rom_settings: Chip8RomSettings,
+    audio_enabled: bool,
+    show_settings: bool,
+    settings_status: String,
+    /// The core's pause state, as last reported by the frontend driver.
+    paused: bool,
+    show_help: bool,
+    screenshot_pending: bool,
+    status: String,
+    on_exit: Option<Box<dyn FnOnce()>>,
 }
 
 // struct Chip8EframeDisplayData {
@@ -37,70 +93,578 @@ impl Chip8EframeApp {
         Chip8EframeApp {
             fname: String::from(""),
             display_data: Chip8DisplayData::default(),
+            new_frame: false,
+            last_vblank: 0,
             frame: None,
             adapter: adapter.clone(),
-            last_key_state: [0; 16],
+            driver: Some(Chip8FrontendDriver::new(adapter)),
+            pending_keys: Vec::new(),
+            pending_commands: Vec::new(),
+            tone: false,
+            debug: None,
+            memory_view: Chip8MemoryView::new(),
+            memory_request_pending: false,
+            inspector: Chip8Inspector::new(),
+            state_request_pending: false,
+            renderer: Chip8DisplayRenderer::default(),
+            phosphor: Chip8Phosphor::new(Chip8PersistenceMode::Off, DEFAULT_DECAY),
+            key_mapping: KeyMapping::default(),
+            keymap_dialog: Chip8KeymapDialog::default(),
+            hotkeys: Chip8Hotkeys::default(),
+            key_hints: Vec::new(),
+            rom_browser: Chip8RomBrowser::new(Vec::new(), None),
+            rom_db: Chip8RomDatabase::default(),
+            rom_watcher: None,
+            config: Chip8Config::default(),
+            config_path: None,
+            overrides: Chip8Overrides::default(),
+            rom_hash: None,
+            rom_settings: Chip8Config::default().settings_for(None, None),
+            audio_enabled: true,
+            show_settings: false,
+            settings_status: String::new(),
+            paused: false,
+            show_help: false,
+            screenshot_pending: false,
+            status: String::new(),
+            on_exit: None,
         }
     }
 
-    fn check_for_updates(&mut self) -> bool {
-        match self
-            .adapter
-            .display_state_receiver
-            .recv_timeout(std::time::Duration::from_micros(1))
-        {
-            Ok(v) => {
-                self.display_data = v;
-                true
+    pub fn set_colors(&mut self, fg: egui::Color32, bg: egui::Color32) {
+        self.renderer.fg = fg;
+        self.renderer.bg = bg;
+    }
+
+    pub fn set_persistence(&mut self, mode: Chip8PersistenceMode, decay: f32) {
+        self.phosphor.mode = mode;
+        self.phosphor.decay = decay;
+    }
+
+    /// Settings to start from, and where the settings and keypad windows
+    /// save changes.
+    pub fn set_config(&mut self, config: Chip8Config, path: Option<PathBuf>) {
+        self.renderer.scale = config.scale.unwrap_or(renderer::DEFAULT_SCALE);
+        self.set_persistence(
+            config.persistence.unwrap_or(Chip8PersistenceMode::Off),
+            config.decay.unwrap_or(DEFAULT_DECAY),
+        );
+        self.set_audio_enabled(config.audio.enabled);
+        self.config = config;
+        self.config_path = path;
+    }
+
+    /// Settings from the command line, which beat the config file and the
+    /// ROM database.
+    pub fn set_overrides(&mut self, overrides: Chip8Overrides) {
+        self.overrides = overrides;
+    }
+
+    pub fn set_audio_enabled(&mut self, enabled: bool) {
+        self.audio_enabled = enabled;
+        if let Some(driver) = self.driver.as_mut() {
+            driver.set_muted(!enabled);
+        }
+    }
+
+    /// Lets hotkeys pause, reset and save or load the core through `ca`.
+    pub fn attach_control(&mut self, ca: &ControlAdapter) {
+        if let Some(driver) = self.driver.as_mut() {
+            driver.attach_control(ca);
+        }
+    }
+
+    /// Runs `f` when the window closes, e.g. to stop and join the core
+    /// thread. `run_native` never returns, so this is the last chance.
+    pub fn set_on_exit(&mut self, f: Box<dyn FnOnce()>) {
+        self.on_exit = Some(f);
+    }
+
+    pub fn set_hotkeys(&mut self, hotkeys: Chip8Hotkeys) {
+        self.hotkeys = hotkeys;
+    }
+
+    /// Shown in the menu bar, e.g. the ROM's title from the ROM database.
+    pub fn set_title(&mut self, title: &str) {
+        self.fname = String::from(title);
+    }
+
+    /// What the ROM's keypad keys do, listed alongside the hotkeys.
+    pub fn set_key_hints(&mut self, hints: Vec<(String, u8)>) {
+        self.key_hints = hints;
+    }
+
+    /// Lists `dirs` in the ROM browser, and keeps recently opened ROMs in
+    /// `recent_path`, starting with `current`.
+    pub fn set_rom_browser(
+        &mut self,
+        dirs: Vec<PathBuf>,
+        recent_path: Option<PathBuf>,
+        current: &Path,
+    ) {
+        self.rom_browser = Chip8RomBrowser::new(dirs, recent_path);
+        self.rom_browser.opened(current);
+    }
+
+    /// Titles ROMs in the browser, and configures the core for each ROM
+    /// opened from it.
+    pub fn set_rom_database(&mut self, db: Chip8RomDatabase) {
+        self.rom_db = db;
+    }
+
+    /// Where to point `--watch` when switching ROMs.
+    pub fn set_rom_watcher(&mut self, watcher: Sender<PathBuf>) {
+        self.rom_watcher = Some(watcher);
+    }
+
+    /// Title, colours, key mapping and hints for the ROM at `path`, layered
+    /// from the config, ROM database and command line. Returns the quirks and
+    /// speed the core should run it with.
+    pub fn set_rom(&mut self, path: &Path, rom: &[u8]) -> Chip8RomSettings {
+        let hash: String = sha1_hex(rom);
+        let fname: String = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let info: Option<Chip8RomInfo> = self.rom_db.lookup(rom).cloned();
+        let settings: Chip8RomSettings = self
+            .overrides
+            .apply(self.config.settings_for(Some(&hash), info.as_ref()));
+
+        self.set_colors(settings.fg, settings.bg);
+        match &info {
+            Some(info) => {
+                self.set_title(&info.title);
+                self.set_key_hints(info.keys.clone());
+            }
+            None => {
+                self.set_title(&fname);
+                self.set_key_hints(Vec::new());
+            }
+        }
+        match self.config.mapping_for(Some(&hash)) {
+            Ok(mapping) => self.key_mapping = mapping,
+            Err(e) => self.status = e.to_string(),
+        }
+        self.keymap_dialog.set_rom(&self.config, &hash, &fname);
+        self.rom_hash = Some((hash, fname));
+        self.rom_settings = settings;
+        settings
+    }
+
+    /// Switches the core to the ROM at `path` through the control channel,
+    /// configured like one given with `-f`.
+    fn open_rom(&mut self, path: &Path) {
+        let rom: Vec<u8> = match fs::read(path) {
+            Ok(rom) => rom,
+            Err(e) => {
+                self.status = format!("Failed to read {}: {}", path.display(), e);
+                return;
+            }
+        };
+        if rom.len() > MAX_ROM_SIZE {
+            self.status = format!("{} is too big to be a ROM", path.display());
+            return;
+        }
+        let settings: Chip8RomSettings = self.set_rom(path, &rom);
+        self.send_command(Chip8Command::SetQuirks(settings.quirks));
+        self.send_command(Chip8Command::SetInstrsPerFrame(settings.speed));
+        self.send_command(Chip8Command::LoadRom(rom));
+
+        if let Some(watcher) = &self.rom_watcher {
+            let _ = watcher.send(path.to_path_buf());
+        }
+        self.rom_browser.opened(path);
+        info!("Switched to {}", path.display());
+    }
+
+    /// Window for changing quirks, speed, colours and sound while running,
+    /// and saving them as the defaults or for the current ROM.
+    fn show_settings(&mut self, ctx: &egui::CtxRef) {
+        let mut open: bool = self.show_settings;
+        let mut commands: Vec<Chip8Command> = Vec::new();
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let settings: &mut Chip8RomSettings = &mut self.rom_settings;
+                let old_quirks: Chip8Quirks = settings.quirks;
+                ui.horizontal(|ui| {
+                    ui.label("Quirks");
+                    for name in ["default", "cosmac", "modern"] {
+                        if ui.button(name).clicked() {
+                            settings.quirks = name.parse().unwrap_or_default();
+                        }
+                    }
+                });
+                ui.checkbox(&mut settings.quirks.shift_uses_vy, "Shifts read VY");
+                ui.checkbox(&mut settings.quirks.jump_uses_v0, "BNNN adds V0");
+                ui.checkbox(
+                    &mut settings.quirks.load_store_increments_i,
+                    "FX55/FX65 advance I",
+                );
+                ui.checkbox(&mut settings.quirks.logic_resets_vf, "Logic ops reset VF");
+                if settings.quirks != old_quirks {
+                    commands.push(Chip8Command::SetQuirks(settings.quirks));
+                }
+
+                egui::Grid::new("settings_grid").show(ui, |ui| {
+                    ui.label("Instructions per frame");
+                    if ui
+                        .add(egui::DragValue::new(&mut settings.speed).clamp_range(1..=1000))
+                        .changed()
+                    {
+                        commands.push(Chip8Command::SetInstrsPerFrame(settings.speed));
+                    }
+                    ui.end_row();
+                    ui.label("Colours");
+                    ui.horizontal(|ui| {
+                        ui.color_edit_button_srgba(&mut self.renderer.fg);
+                        ui.color_edit_button_srgba(&mut self.renderer.bg);
+                    });
+                    ui.end_row();
+                    ui.label("Screenshot scale");
+                    ui.add(egui::DragValue::new(&mut self.renderer.scale).clamp_range(1..=32));
+                    ui.end_row();
+                });
+                if ui.checkbox(&mut self.audio_enabled, "Sound").changed() {
+                    if let Some(driver) = self.driver.as_mut() {
+                        driver.set_muted(!self.audio_enabled);
+                    }
+                }
+                settings.fg = self.renderer.fg;
+                settings.bg = self.renderer.bg;
+
+                ui.separator();
+                let path: PathBuf = match &self.config_path {
+                    Some(p) => p.clone(),
+                    None => {
+                        ui.label("No settings file to save to");
+                        return;
+                    }
+                };
+                ui.horizontal(|ui| {
+                    let mut save: bool = false;
+                    if ui.button("Save as defaults").clicked() {
+                        let config: &mut Chip8Config = &mut self.config;
+                        config.quirks = Some(settings.quirks);
+                        config.speed = Some(settings.speed);
+                        config.fg = Some(renderer::format_color(settings.fg));
+                        config.bg = Some(renderer::format_color(settings.bg));
+                        config.scale = Some(self.renderer.scale);
+                        config.persistence = Some(self.phosphor.mode);
+                        config.decay = Some(self.phosphor.decay);
+                        config.audio.enabled = self.audio_enabled;
+                        save = true;
+                    }
+                    if let Some((hash, name)) = &self.rom_hash {
+                        if ui.button(format!("Save for {}", name)).clicked() {
+                            let rom: &mut Chip8RomConfig =
+                                self.config.roms.entry(hash.clone()).or_default();
+                            rom.quirks = Some(settings.quirks);
+                            rom.speed = Some(settings.speed);
+                            rom.fg = Some(renderer::format_color(settings.fg));
+                            rom.bg = Some(renderer::format_color(settings.bg));
+                            save = true;
+                        }
+                    }
+                    if save {
+                        self.settings_status = match self.config.save(&path) {
+                            Ok(_) => format!("Saved to {}", path.display()),
+                            Err(e) => e.to_string(),
+                        };
+                    }
+                });
+                ui.label(self.settings_status.as_str());
+            });
+        self.show_settings = open;
+        for command in commands {
+            self.send_command(command);
+        }
+    }
+
+    fn send_command(&mut self, command: Chip8Command) {
+        self.pending_commands.push(command);
+    }
+
+    fn handle_hotkeys(&mut self, ctx: &egui::CtxRef, frame: &epi::Frame) {
+        let events: Vec<egui::Event> = ctx.input().events.clone();
+        for event in events {
+            if let egui::Event::Key {
+                key,
+                pressed,
+                modifiers,
+            } = event
+            {
+                if !pressed {
+                    let ff = self.hotkeys.binding(Chip8HotkeyAction::FastForward);
+                    if ff.map(|h| h.key) == Some(key) {
+                        self.send_command(Chip8Command::FastForward(false));
+                    }
+                    continue;
+                }
+                if let Some(action) = self.hotkeys.action_for(key, &modifiers) {
+                    self.run_hotkey(action, frame);
+                }
+            }
+        }
+    }
+
+    fn run_hotkey(&mut self, action: Chip8HotkeyAction, frame: &epi::Frame) {
+        info!("Hotkey {:?}", action);
+        match action {
+            Chip8HotkeyAction::Pause => {
+                self.send_command(match self.paused {
+                    true => Chip8Command::Resume,
+                    false => Chip8Command::Pause,
+                });
+            }
+            Chip8HotkeyAction::Reset => self.send_command(Chip8Command::Reset),
+            Chip8HotkeyAction::FrameAdvance => {
+                if !self.paused {
+                    self.send_command(Chip8Command::Pause);
+                }
+                self.send_command(Chip8Command::FrameAdvance);
+            }
+            Chip8HotkeyAction::FastForward => self.send_command(Chip8Command::FastForward(true)),
+            Chip8HotkeyAction::SaveState(slot) => {
+                self.send_command(Chip8Command::SaveState(slot));
+                self.status = format!("Saved state {}", slot + 1);
+            }
+            Chip8HotkeyAction::LoadState(slot) => {
+                self.send_command(Chip8Command::LoadState(slot));
+                self.status = format!("Loaded state {}", slot + 1);
+            }
+            Chip8HotkeyAction::Screenshot => self.screenshot_pending = true,
+            Chip8HotkeyAction::Quit => frame.quit(),
+            Chip8HotkeyAction::Help => self.show_help = !self.show_help,
+        }
+    }
+
+    fn show_help(&mut self, ctx: &egui::CtxRef) {
+        let hotkeys: &Chip8Hotkeys = &self.hotkeys;
+        let key_hints: &[(String, u8)] = &self.key_hints;
+        let key_mapping: &KeyMapping = &self.key_mapping;
+        egui::Window::new("Hotkeys")
+            .open(&mut self.show_help)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("hotkey_help").show(ui, |ui| {
+                    for (action, hotkey) in hotkeys.bindings() {
+                        ui.label(action.description());
+                        match hotkey {
+                            Some(h) => ui.monospace(h.to_string()),
+                            None => ui.label("unbound"),
+                        };
+                        ui.end_row();
+                    }
+                });
+                if !key_hints.is_empty() {
+                    ui.separator();
+                    egui::Grid::new("key_hints").show(ui, |ui| {
+                        for (action, chip8_key) in key_hints {
+                            ui.label(action);
+                            ui.monospace(format!(
+                                "{:X} ({})",
+                                chip8_key,
+                                key_name(key_mapping.key(*chip8_key))
+                            ));
+                            ui.end_row();
+                        }
+                    });
+                }
+            });
+    }
+
+    /// Queues presses and releases of mapped keys for the core as they
+    /// happen, rather than sampling which keys are down once per repaint.
+    fn send_key_events(&mut self, ctx: &egui::CtxRef) {
+        for event in ctx.input().events.iter() {
+            if let egui::Event::Key {
+                key,
+                pressed,
+                modifiers,
+            } = event
+            {
+                let chip8_key: u8 = match self.key_mapping.keys().iter().position(|k| k == key) {
+                    Some(i) => i as u8,
+                    None => continue,
+                };
+                // Keep Ctrl/Alt hotkeys from also pressing keypad keys.
+                if *pressed && (modifiers.ctrl || modifiers.alt) {
+                    continue;
+                }
+                info!("Key {:?} ({:X}) pressed: {}", key, chip8_key, pressed);
+                self.pending_keys
+                    .push(Chip8KeyEvent::new(chip8_key, *pressed));
+            }
+        }
+    }
+
+    /// Enables the debugger views, which talk to the core through `da`.
+    pub fn attach_debugger(&mut self, da: &DebugAdapter) {
+        self.debug = Some(da.clone());
+    }
+
+    fn poll_debugger(&mut self) {
+        let da: &DebugAdapter = match &self.debug {
+            Some(da) => da,
+            None => return,
+        };
+        while let Ok(resp) = da.response_receiver.try_recv() {
+            match resp {
+                Chip8DebugResponse::Memory(snapshot) => {
+                    self.memory_view.set_snapshot(*snapshot);
+                    self.memory_request_pending = false;
+                }
+                Chip8DebugResponse::State(snapshot) => {
+                    self.inspector.set_snapshot(*snapshot);
+                    self.state_request_pending = false;
+                }
+            }
+        }
+        if self.memory_view.open && !self.memory_request_pending {
+            match da.request_sender.send(Chip8DebugRequest::ReadMemory) {
+                Ok(_) => self.memory_request_pending = true,
+                Err(e) => error!("ERR: {} ", e),
+            }
+        }
+        if self.inspector.open && !self.state_request_pending {
+            match da.request_sender.send(Chip8DebugRequest::ReadState) {
+                Ok(_) => self.state_request_pending = true,
+                Err(e) => error!("ERR: {} ", e),
             }
-            Err(_) => false,
+        }
+    }
+
+    fn pump(&mut self) {
+        if let Some(mut driver) = self.driver.take() {
+            driver.pump(self);
+            self.driver = Some(driver);
         }
     }
 }
 
+impl Chip8Frontend for Chip8EframeApp {
+    fn present_frame(&mut self, frame: &Chip8DisplayData) {
+        self.display_data = *frame;
+        self.new_frame = true;
+    }
+
+    fn poll_input(&mut self) -> Vec<Chip8KeyEvent> {
+        std::mem::take(&mut self.pending_keys)
+    }
+
+    // No audio backend yet, so the buzzer is shown in the menu bar instead.
+    fn play_tone(&mut self) {
+        self.tone = true;
+    }
+
+    fn stop_tone(&mut self) {
+        self.tone = false;
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    fn poll_control(&mut self) -> Vec<Chip8Command> {
+        std::mem::take(&mut self.pending_commands)
+    }
+}
+
 impl epi::App for Chip8EframeApp {
     fn update(&mut self, ctx: &egui::CtxRef, _frame: &epi::Frame) {
         // println!("update!");
-        let _ = self.check_for_updates();
-        egui::CentralPanel::default().show(ctx, |ui| {
+        // Pick up the newest frame before painting; input gathered below is
+        // sent by the second pump at the end.
+        self.pump();
+        let new_frame: bool = std::mem::take(&mut self.new_frame);
+        self.poll_debugger();
+        egui::TopBottomPanel::top("menu").show(ctx, |ui| {
             ui.horizontal(|ui| {
-                ui.label("Hello world!");
                 ui.label(self.fname.as_str());
-                ui.label(egui::RichText::new(format!("{}", self.display_data)).monospace());
-                for k in &ctx.input().keys_down {
-                    ui.label(format!("Key {:?} down", k));
+                ui.checkbox(&mut self.rom_browser.open, "ROMs");
+                ui.checkbox(&mut self.keymap_dialog.open, "Keys");
+                ui.checkbox(&mut self.show_help, "Hotkeys");
+                ui.checkbox(&mut self.show_settings, "Settings");
+                egui::ComboBox::from_label("Persistence")
+                    .selected_text(format!("{:?}", self.phosphor.mode))
+                    .show_ui(ui, |ui| {
+                        for mode in [
+                            Chip8PersistenceMode::Off,
+                            Chip8PersistenceMode::Decay,
+                            Chip8PersistenceMode::OrLastTwo,
+                        ] {
+                            ui.selectable_value(
+                                &mut self.phosphor.mode,
+                                mode,
+                                format!("{:?}", mode),
+                            );
+                        }
+                    });
+                if self.phosphor.mode == Chip8PersistenceMode::Decay {
+                    ui.add(egui::Slider::new(&mut self.phosphor.decay, 0.0..=0.95).text("Decay"));
+                }
+                if self.debug.is_some() {
+                    ui.checkbox(&mut self.memory_view.open, "Memory");
+                    ui.checkbox(&mut self.inspector.open, "Inspector");
+                }
+                if self.paused {
+                    ui.label("Paused");
                 }
-            })
+                if self.tone {
+                    ui.label("♪");
+                }
+                ui.label(self.status.as_str());
+            });
+        });
+        if let Some(da) = &self.debug {
+            self.inspector.show(ctx);
+            self.memory_view.show(ctx, da);
+        }
+        // Checked before the dialog sees this frame's input, so the key that
+        // completes a rebind doesn't also reach the core.
+        let capturing: bool = self.keymap_dialog.is_capturing();
+        self.keymap_dialog.show(
+            ctx,
+            &mut self.key_mapping,
+            &mut self.config,
+            self.config_path.as_deref(),
+        );
+        self.show_help(ctx);
+        self.show_settings(ctx);
+        if let Some(path) = self.rom_browser.show(ctx, &self.rom_db) {
+            self.open_rom(&path);
+        }
+        if !capturing {
+            self.handle_hotkeys(ctx, _frame);
+        }
+        // Fade once per emulated frame, not per repaint
+        let vblank: u64 = self.adapter.frames();
+        let frames: u64 = vblank.saturating_sub(self.last_vblank);
+        self.last_vblank = vblank;
+        let frame = self.phosphor.process(&self.display_data, new_frame, frames);
+        if self.screenshot_pending {
+            self.screenshot_pending = false;
+            self.status = match self.renderer.save_screenshot(frame) {
+                Ok(path) => format!("Wrote {}", path),
+                Err(e) => format!("Failed to write screenshot: {}", e),
+            };
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.renderer.paint(ui, frame);
         });
 
-        if ctx.input().key_pressed(egui::Key::Space) {
-            _frame.quit();
-        }
-
-        let mut new_keys: [u8; 16] = [0; 16];
-        let mut new_state: bool = false;
-
-        for (i, k) in KEY_MAP.iter().enumerate() {
-            if ctx.input().key_pressed(*k) {
-                new_keys[i] = 1;
-                new_state = true;
-                info!("Key {:?} pressed", k);
-            } else if ctx.input().key_down(*k) {
-                new_keys[i] = 1;
-                info!("Key {:?} held", k);
-            } else if ctx.input().key_released(*k) {
-                new_keys[i] = 0;
-                info!("Key {:?} released", k);
-                new_state = true;
-            }
+        if !capturing {
+            self.send_key_events(ctx);
         }
+        self.pump();
+    }
 
-        if new_state || self.last_key_state != new_keys {
-            self.last_key_state = new_keys;
-            self.adapter
-                .key_state_sender
-                .send_timeout(new_keys, std::time::Duration::from_micros(1))
-                .unwrap();
+    fn on_exit(&mut self) {
+        if let Some(f) = self.on_exit.take() {
+            f();
         }
     }
 
@@ -117,10 +681,12 @@ impl epi::App for Chip8EframeApp {
         println!("Setting up!");
         self.frame = Some(_frame.clone());
         let bf: Box<epi::Frame> = Box::new(_frame.clone());
+        let vblank = self.adapter.vblank_receiver.clone();
+        // Repaint on every vblank, and regularly anyway so the UI stays live
+        // while the core is paused.
         std::thread::spawn(move || loop {
+            let _ = vblank.recv_timeout(std::time::Duration::from_millis(20));
             bf.request_repaint();
-            // println!("From that thread");
-            std::thread::sleep(std::time::Duration::from_millis(10));
         });
     }
 }
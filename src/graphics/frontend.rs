@@ -0,0 +1,115 @@
+use crate::core::{keypad::Chip8KeyEvent, Chip8DisplayData};
+use crate::graphics::control_adapter::{Chip8Command, ControlAdapter};
+use crate::graphics::graphics_adapter::GraphicsAdapter;
+use log::error;
+use std::time::Duration;
+
+/// What a backend (window, terminal, test harness...) has to provide to run
+/// the core. A `Chip8FrontendDriver` calls these from the backend's own
+/// thread, so implementations don't need to be `Send` or touch channels.
+pub trait Chip8Frontend {
+    /// Shows a newly published frame. Only called when the frame changed.
+    fn present_frame(&mut self, frame: &Chip8DisplayData);
+
+    /// Keypad presses and releases since the last call, oldest first.
+    fn poll_input(&mut self) -> Vec<Chip8KeyEvent>;
+
+    /// Starts the buzzer. Called once when the sound timer becomes non-zero.
+    fn play_tone(&mut self);
+
+    /// Stops the buzzer.
+    fn stop_tone(&mut self);
+
+    /// Shows whether the core is paused. Called once each time that changes,
+    /// whoever paused or resumed it, so frontends shouldn't keep their own
+    /// idea of it.
+    fn set_paused(&mut self, paused: bool);
+
+    /// Emulator commands the user asked for since the last call.
+    fn poll_control(&mut self) -> Vec<Chip8Command>;
+}
+
+/// Moves frames, input, sound and commands between a `Chip8Frontend` and the
+/// core's adapters. Backends call `pump` from their main loop.
+pub struct Chip8FrontendDriver {
+    ga: GraphicsAdapter,
+    control: Option<ControlAdapter>,
+    generation: u64,
+    tone: bool,
+    paused: bool,
+    muted: bool,
+}
+
+impl Chip8FrontendDriver {
+    pub fn new(ga: &GraphicsAdapter) -> Chip8FrontendDriver {
+        Chip8FrontendDriver {
+            ga: ga.clone(),
+            control: None,
+            generation: 0,
+            tone: false,
+            paused: false,
+            muted: false,
+        }
+    }
+
+    /// While muted, the frontend is never asked to play the tone.
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    /// Without a control adapter, commands from the frontend are dropped.
+    pub fn attach_control(&mut self, ca: &ControlAdapter) {
+        self.control = Some(ca.clone());
+    }
+
+    /// Blocks until the core signals the next frame, or `timeout` passes.
+    /// Returns whether a vblank arrived.
+    pub fn wait_for_vblank(&self, timeout: Duration) -> bool {
+        self.ga.vblank_receiver.recv_timeout(timeout).is_ok()
+    }
+
+    /// Does one round of exchange with `frontend`: presents the newest frame
+    /// if there is one, starts or stops the tone, passes on pause changes,
+    /// and forwards input and
+    /// commands to the core. Returns whether a new frame was presented.
+    pub fn pump(&mut self, frontend: &mut dyn Chip8Frontend) -> bool {
+        let (data, generation) = self.ga.latest_frame();
+        let new_frame: bool = generation != self.generation;
+        if new_frame {
+            self.generation = generation;
+            frontend.present_frame(&data);
+        }
+
+        let tone: bool = self.ga.sound_active() && !self.muted;
+        if tone != self.tone {
+            self.tone = tone;
+            match tone {
+                true => frontend.play_tone(),
+                false => frontend.stop_tone(),
+            }
+        }
+
+        let paused: bool = self.ga.paused();
+        if paused != self.paused {
+            self.paused = paused;
+            frontend.set_paused(paused);
+        }
+
+        for event in frontend.poll_input() {
+            if let Err(e) = self.ga.key_event_sender.send(event) {
+                error!("ERR: {} ", e);
+            }
+        }
+        for command in frontend.poll_control() {
+            match &self.control {
+                Some(ca) => {
+                    if let Err(e) = ca.send(command) {
+                        error!("ERR: {} ", e);
+                    }
+                }
+                None => error!("No core control attached, dropping {:?}", command),
+            }
+        }
+        new_frame
+    }
+}
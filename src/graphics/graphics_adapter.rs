@@ -1,12 +1,29 @@
-use crate::core::Chip8DisplayData;
-use crossbeam::channel::{unbounded, Receiver, Sender};
+use crate::core::{keypad::Chip8KeyEvent, Chip8DisplayData};
+use crossbeam::channel::{bounded, unbounded, Receiver, Sender};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
+#[derive(Default)]
+struct Chip8FrontBuffer {
+    data: Chip8DisplayData,
+    generation: u64,
+}
+
+/// Connects the core to a frontend. The core draws into its own display
+/// (the back buffer) and copies it into the shared front buffer at most once
+/// per 60 Hz frame, so frontends never see a half-drawn frame and nothing
+/// queues up however often the ROM draws. Every frame the core also sends a
+/// vblank notification, which is dropped if the last one hasn't been read.
 #[derive(Clone)]
 pub struct GraphicsAdapter {
-    pub display_state_receiver: Receiver<Chip8DisplayData>,
-    pub display_state_sender: Sender<Chip8DisplayData>,
-    pub key_state_receiver: Receiver<[u8; 16]>,
-    pub key_state_sender: Sender<[u8; 16]>,
+    front_buffer: Arc<Mutex<Chip8FrontBuffer>>,
+    sound: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    frames: Arc<AtomicU64>,
+    pub vblank_receiver: Receiver<u64>,
+    pub vblank_sender: Sender<u64>,
+    pub key_event_receiver: Receiver<Chip8KeyEvent>,
+    pub key_event_sender: Sender<Chip8KeyEvent>,
 }
 
 impl Default for GraphicsAdapter {
@@ -17,13 +34,71 @@ impl Default for GraphicsAdapter {
 
 impl GraphicsAdapter {
     pub fn new() -> GraphicsAdapter {
-        let (dss, dsr) = unbounded::<Chip8DisplayData>();
-        let (kss, ksr) = unbounded::<[u8; 16]>();
+        let (vbs, vbr) = bounded::<u64>(1);
+        let (kes, ker) = unbounded::<Chip8KeyEvent>();
         GraphicsAdapter {
-            display_state_receiver: dsr,
-            display_state_sender: dss,
-            key_state_receiver: ksr,
-            key_state_sender: kss,
+            front_buffer: Arc::new(Mutex::new(Chip8FrontBuffer::default())),
+            sound: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
+            frames: Arc::new(AtomicU64::new(0)),
+            vblank_receiver: vbr,
+            vblank_sender: vbs,
+            key_event_receiver: ker,
+            key_event_sender: kes,
         }
     }
+
+    /// Makes `data` the frame frontends see.
+    pub fn publish(&self, data: &Chip8DisplayData) {
+        let mut front = self
+            .front_buffer
+            .lock()
+            .expect("Front buffer lock poisoned");
+        front.data = *data;
+        front.generation += 1;
+    }
+
+    /// The last published frame, and a generation number that goes up by one
+    /// each time a new frame is published.
+    pub fn latest_frame(&self) -> (Chip8DisplayData, u64) {
+        let front = self
+            .front_buffer
+            .lock()
+            .expect("Front buffer lock poisoned");
+        (front.data, front.generation)
+    }
+
+    /// Whether the buzzer should be sounding, i.e. the sound timer is running.
+    pub fn sound_active(&self) -> bool {
+        self.sound.load(Ordering::Relaxed)
+    }
+
+    pub fn set_sound_active(&self, active: bool) {
+        self.sound.store(active, Ordering::Relaxed);
+    }
+
+    /// Whether the core is paused, however that happened: a hotkey, the
+    /// control channel or a debugger step.
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// The number of the last frame signalled, counting from when the core
+    /// started. Unlike the vblank channel this never drops one, so frontends
+    /// can tell how many frames went by between two repaints.
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// Tells the frontend another frame has gone by. `frame` counts frames
+    /// since the core started.
+    pub fn signal_vblank(&self, frame: u64) {
+        self.frames.store(frame, Ordering::Relaxed);
+        // Full means the frontend hasn't seen the last one yet; one is enough.
+        let _ = self.vblank_sender.try_send(frame);
+    }
 }
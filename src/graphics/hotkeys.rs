@@ -0,0 +1,271 @@
+use crate::core::SAVE_SLOTS;
+use crate::graphics::key_mapping::{key_from_name, key_name};
+use eframe::egui::{Key, Modifiers};
+use simple_error::{simple_error, SimpleError};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8HotkeyAction {
+    Pause,
+    Reset,
+    FrameAdvance,
+    /// Held rather than toggled.
+    FastForward,
+    SaveState(u8),
+    LoadState(u8),
+    Screenshot,
+    Quit,
+    Help,
+}
+
+impl Chip8HotkeyAction {
+    pub fn all() -> Vec<Chip8HotkeyAction> {
+        let mut actions: Vec<Chip8HotkeyAction> = vec![
+            Chip8HotkeyAction::Pause,
+            Chip8HotkeyAction::Reset,
+            Chip8HotkeyAction::FrameAdvance,
+            Chip8HotkeyAction::FastForward,
+        ];
+        for slot in 0..SAVE_SLOTS as u8 {
+            actions.push(Chip8HotkeyAction::SaveState(slot));
+            actions.push(Chip8HotkeyAction::LoadState(slot));
+        }
+        actions.extend([
+            Chip8HotkeyAction::Screenshot,
+            Chip8HotkeyAction::Quit,
+            Chip8HotkeyAction::Help,
+        ]);
+        actions
+    }
+
+    /// Name used in the settings file, e.g. `save_state_1`. Slots are
+    /// numbered from 1 there.
+    pub fn name(&self) -> String {
+        match self {
+            Chip8HotkeyAction::Pause => String::from("pause"),
+            Chip8HotkeyAction::Reset => String::from("reset"),
+            Chip8HotkeyAction::FrameAdvance => String::from("frame_advance"),
+            Chip8HotkeyAction::FastForward => String::from("fast_forward"),
+            Chip8HotkeyAction::SaveState(slot) => format!("save_state_{}", slot + 1),
+            Chip8HotkeyAction::LoadState(slot) => format!("load_state_{}", slot + 1),
+            Chip8HotkeyAction::Screenshot => String::from("screenshot"),
+            Chip8HotkeyAction::Quit => String::from("quit"),
+            Chip8HotkeyAction::Help => String::from("help"),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Chip8HotkeyAction::Pause => String::from("Pause / resume"),
+            Chip8HotkeyAction::Reset => String::from("Reset"),
+            Chip8HotkeyAction::FrameAdvance => String::from("Advance one frame"),
+            Chip8HotkeyAction::FastForward => String::from("Fast-forward (hold)"),
+            Chip8HotkeyAction::SaveState(slot) => format!("Save state {}", slot + 1),
+            Chip8HotkeyAction::LoadState(slot) => format!("Load state {}", slot + 1),
+            Chip8HotkeyAction::Screenshot => String::from("Screenshot"),
+            Chip8HotkeyAction::Quit => String::from("Quit"),
+            Chip8HotkeyAction::Help => String::from("Show hotkeys"),
+        }
+    }
+}
+
+impl FromStr for Chip8HotkeyAction {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Chip8HotkeyAction::all()
+            .into_iter()
+            .find(|a| a.name() == s)
+            .ok_or_else(|| simple_error!("Unknown hotkey action {:?}", s))
+    }
+}
+
+/// A key plus the modifiers that must be held with it, e.g. `Ctrl+Shift+S`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hotkey {
+    pub key: Key,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Hotkey {
+    pub fn new(key: Key) -> Hotkey {
+        Hotkey {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        }
+    }
+
+    pub fn ctrl(key: Key) -> Hotkey {
+        Hotkey {
+            ctrl: true,
+            ..Hotkey::new(key)
+        }
+    }
+
+    pub fn alt(key: Key) -> Hotkey {
+        Hotkey {
+            alt: true,
+            ..Hotkey::new(key)
+        }
+    }
+
+    pub fn ctrl_shift(key: Key) -> Hotkey {
+        Hotkey {
+            ctrl: true,
+            shift: true,
+            ..Hotkey::new(key)
+        }
+    }
+
+    pub fn matches(&self, key: Key, modifiers: &Modifiers) -> bool {
+        self.key == key
+            && self.ctrl == modifiers.ctrl
+            && self.shift == modifiers.shift
+            && self.alt == modifiers.alt
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = s.split('+').map(|p| p.trim()).collect();
+        let key: Key = match parts.pop().and_then(key_from_name) {
+            Some(k) => k,
+            None => return Err(simple_error!("No key in hotkey {:?}", s)),
+        };
+        let mut hotkey: Hotkey = Hotkey::new(key);
+        for modifier in parts {
+            match modifier {
+                "Ctrl" => hotkey.ctrl = true,
+                "Shift" => hotkey.shift = true,
+                "Alt" => hotkey.alt = true,
+                _ => return Err(simple_error!("Unknown modifier {:?} in {:?}", modifier, s)),
+            }
+        }
+        Ok(hotkey)
+    }
+}
+
+impl Display for Hotkey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        write!(f, "{}", key_name(self.key))
+    }
+}
+
+const SLOT_KEYS: [Key; 4] = [Key::Num1, Key::Num2, Key::Num3, Key::Num4];
+/// The keys under `SLOT_KEYS`, for saving in the terminal.
+const TERMINAL_SAVE_KEYS: [Key; 4] = [Key::Q, Key::W, Key::E, Key::R];
+
+/// Emulator controls, kept apart from the keypad mapping. Every default
+/// uses Ctrl or Alt, or a key no keypad preset binds, so ROMs keep all of
+/// theirs.
+pub struct Chip8Hotkeys {
+    bindings: Vec<(Chip8HotkeyAction, Option<Hotkey>)>,
+}
+
+impl Default for Chip8Hotkeys {
+    fn default() -> Self {
+        let bindings = Chip8HotkeyAction::all()
+            .into_iter()
+            .map(|action| {
+                let hotkey: Option<Hotkey> = match action {
+                    Chip8HotkeyAction::Pause => Some(Hotkey::ctrl(Key::P)),
+                    Chip8HotkeyAction::Reset => Some(Hotkey::ctrl(Key::R)),
+                    Chip8HotkeyAction::FrameAdvance => Some(Hotkey::ctrl(Key::N)),
+                    Chip8HotkeyAction::FastForward => Some(Hotkey::new(Key::Tab)),
+                    Chip8HotkeyAction::SaveState(slot) => {
+                        SLOT_KEYS.get(slot as usize).map(|k| Hotkey::ctrl_shift(*k))
+                    }
+                    Chip8HotkeyAction::LoadState(slot) => {
+                        SLOT_KEYS.get(slot as usize).map(|k| Hotkey::ctrl(*k))
+                    }
+                    Chip8HotkeyAction::Screenshot => Some(Hotkey::ctrl(Key::G)),
+                    Chip8HotkeyAction::Quit => Some(Hotkey::ctrl(Key::Q)),
+                    Chip8HotkeyAction::Help => Some(Hotkey::ctrl(Key::H)),
+                };
+                (action, hotkey)
+            })
+            .collect();
+        Chip8Hotkeys { bindings }
+    }
+}
+
+impl Chip8Hotkeys {
+    /// The defaults for the terminal frontend. Terminals can't send Ctrl or
+    /// Shift with a digit, and some send Ctrl+H for Backspace, so save states
+    /// and help move to Alt, which terminals send as an Esc prefix: Alt+1-4
+    /// loads a slot and the key below it (Alt+Q-R) saves it.
+    pub fn terminal() -> Chip8Hotkeys {
+        let mut hotkeys = Chip8Hotkeys::default();
+        for (action, hotkey) in hotkeys.bindings.iter_mut() {
+            match action {
+                Chip8HotkeyAction::SaveState(slot) => {
+                    *hotkey = TERMINAL_SAVE_KEYS
+                        .get(*slot as usize)
+                        .map(|k| Hotkey::alt(*k))
+                }
+                Chip8HotkeyAction::LoadState(slot) => {
+                    *hotkey = SLOT_KEYS.get(*slot as usize).map(|k| Hotkey::alt(*k))
+                }
+                Chip8HotkeyAction::Help => *hotkey = Some(Hotkey::alt(Key::H)),
+                _ => (),
+            }
+        }
+        hotkeys
+    }
+
+    /// Applies `[hotkeys]` overrides from the settings file, e.g.
+    /// `pause = "Ctrl+Space"`. An empty string unbinds the action.
+    pub fn apply(&mut self, overrides: &BTreeMap<String, String>) -> Result<(), SimpleError> {
+        for (name, binding) in overrides.iter() {
+            let action: Chip8HotkeyAction = name.parse()?;
+            let hotkey: Option<Hotkey> = match binding.trim() {
+                "" => None,
+                b => Some(b.parse()?),
+            };
+            for (a, h) in self.bindings.iter_mut() {
+                if *a == action {
+                    *h = hotkey;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn binding(&self, action: Chip8HotkeyAction) -> Option<Hotkey> {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .and_then(|(_, h)| *h)
+    }
+
+    pub fn bindings(&self) -> &[(Chip8HotkeyAction, Option<Hotkey>)] {
+        &self.bindings
+    }
+
+    pub fn action_for(&self, key: Key, modifiers: &Modifiers) -> Option<Chip8HotkeyAction> {
+        self.bindings
+            .iter()
+            .find(|(_, h)| match h {
+                Some(h) => h.matches(key, modifiers),
+                None => false,
+            })
+            .map(|(a, _)| *a)
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{core::Chip8StateSnapshot, graphics::key_mapping::KEYPAD_LAYOUT};
+use eframe::egui::{self, Color32, RichText};
+
+const CHANGED_COLOR: Color32 = Color32::YELLOW;
+const PRESSED_COLOR: Color32 = Color32::DARK_GREEN;
+
+/// Side panel showing registers, the next instruction, the call stack, timers
+/// and keypad. Registers that changed since the previous snapshot are
+/// highlighted.
+pub struct Chip8Inspector {
+    pub open: bool,
+    current: Option<Chip8StateSnapshot>,
+    previous_regs: Option<[u8; 16]>,
+}
+
+impl Chip8Inspector {
+    pub fn new() -> Chip8Inspector {
+        Chip8Inspector {
+            open: false,
+            current: None,
+            previous_regs: None,
+        }
+    }
+
+    pub fn set_snapshot(&mut self, snapshot: Chip8StateSnapshot) {
+        self.previous_regs = self.current.as_ref().map(|c| c.v_regs);
+        self.current = Some(snapshot);
+    }
+
+    pub fn show(&mut self, ctx: &egui::CtxRef) {
+        if !self.open {
+            return;
+        }
+        egui::SidePanel::left("inspector")
+            .resizable(false)
+            .show(ctx, |ui| self.contents(ui));
+    }
+
+    fn contents(&self, ui: &mut egui::Ui) {
+        let state: &Chip8StateSnapshot = match &self.current {
+            Some(s) => s,
+            None => {
+                ui.label("Waiting for the core...");
+                return;
+            }
+        };
+
+        ui.heading("Registers");
+        egui::Grid::new("inspector_v_regs").show(ui, |ui| {
+            for (i, v) in state.v_regs.iter().enumerate() {
+                let mut text = RichText::new(format!("V{:X} {:02X}", i, v)).monospace();
+                let changed = match self.previous_regs {
+                    Some(prev) => prev[i] != *v,
+                    None => false,
+                };
+                if changed {
+                    text = text.color(CHANGED_COLOR);
+                }
+                ui.label(text);
+                if i % 4 == 3 {
+                    ui.end_row();
+                }
+            }
+        });
+        ui.monospace(format!("I  {:04X}", state.index_reg));
+        ui.monospace(format!("PC {:04X}", state.pc));
+        ui.monospace(format!("-> {:04X} {}", state.opcode, state.instr));
+        ui.monospace(format!("Cycles {}", state.cycles));
+
+        ui.separator();
+        ui.heading("Stack");
+        if state.stack.is_empty() {
+            ui.monospace("(empty)");
+        }
+        for (depth, addr) in state.stack.iter().enumerate().rev() {
+            ui.monospace(format!("{:X}: {:#05X}", depth, addr));
+        }
+
+        ui.separator();
+        ui.heading("Timers");
+        ui.monospace(format!("DT {:02X}", state.delay));
+        ui.monospace(format!("ST {:02X}", state.sound));
+
+        ui.separator();
+        ui.heading("Keypad");
+        egui::Grid::new("inspector_keypad").show(ui, |ui| {
+            for row in KEYPAD_LAYOUT.iter() {
+                for key in row.iter() {
+                    let mut text = RichText::new(format!(" {:X} ", key)).monospace();
+                    if state.keys[*key as usize] != 0 {
+                        text = text.background_color(PRESSED_COLOR);
+                    }
+                    ui.label(text);
+                }
+                ui.end_row();
+            }
+        });
+    }
+}
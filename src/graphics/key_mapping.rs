@@ -1,6 +1,66 @@
 use eframe::egui::*;
+use serde::{Deserialize, Serialize};
+use simple_error::{simple_error, SimpleError};
+use std::collections::BTreeMap;
 
-pub const KEY_MAP: [eframe::egui::Key; 16] = [
+/// Every key egui can report, so bindings can be named in config files.
+const ALL_KEYS: [Key; 51] = [
+    Key::ArrowDown,
+    Key::ArrowLeft,
+    Key::ArrowRight,
+    Key::ArrowUp,
+    Key::Escape,
+    Key::Tab,
+    Key::Backspace,
+    Key::Enter,
+    Key::Space,
+    Key::Insert,
+    Key::Delete,
+    Key::Home,
+    Key::End,
+    Key::PageUp,
+    Key::PageDown,
+    Key::Num0,
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+];
+
+/// The left four columns of a QWERTY keyboard, bound to CHIP-8 keys 0-F in
+/// order. Easy to remember, but nothing like the real keypad.
+pub const LINEAR_KEY_MAP: [Key; 16] = [
     Key::Num1,
     Key::Num2,
     Key::Num3,
@@ -18,3 +78,138 @@ pub const KEY_MAP: [eframe::egui::Key; 16] = [
     Key::C,
     Key::V,
 ];
+
+/// The same keyboard block laid out like the COSMAC VIP hex keypad:
+///
+/// ```text
+/// 1 2 3 C      1 2 3 4
+/// 4 5 6 D  ->  Q W E R
+/// 7 8 9 E      A S D F
+/// A 0 B F      Z X C V
+/// ```
+pub const COSMAC_KEY_MAP: [Key; 16] = [
+    Key::X,
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Q,
+    Key::W,
+    Key::E,
+    Key::A,
+    Key::S,
+    Key::D,
+    Key::Z,
+    Key::C,
+    Key::Num4,
+    Key::R,
+    Key::F,
+    Key::V,
+];
+
+/// The COSMAC VIP hex keypad, as it's laid out physically.
+pub const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+pub fn key_name(key: Key) -> String {
+    format!("{:?}", key)
+}
+
+pub fn key_from_name(name: &str) -> Option<Key> {
+    ALL_KEYS.iter().find(|k| key_name(**k) == name).copied()
+}
+
+/// Which keyboard key is bound to each of the 16 CHIP-8 keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyMapping {
+    keys: [Key; 16],
+}
+
+impl Default for KeyMapping {
+    fn default() -> Self {
+        KeyMapping::cosmac()
+    }
+}
+
+impl KeyMapping {
+    pub fn cosmac() -> KeyMapping {
+        KeyMapping {
+            keys: COSMAC_KEY_MAP,
+        }
+    }
+
+    pub fn linear() -> KeyMapping {
+        KeyMapping {
+            keys: LINEAR_KEY_MAP,
+        }
+    }
+
+    pub fn from_preset(name: &str) -> Result<KeyMapping, SimpleError> {
+        match name {
+            "cosmac" => Ok(KeyMapping::cosmac()),
+            "linear" => Ok(KeyMapping::linear()),
+            _ => Err(simple_error!("Unknown key mapping preset {:?}", name)),
+        }
+    }
+
+    pub fn key(&self, chip8_key: u8) -> Key {
+        self.keys[chip8_key as usize]
+    }
+
+    pub fn keys(&self) -> &[Key; 16] {
+        &self.keys
+    }
+
+    /// Binds `key` to `chip8_key`. Whatever CHIP-8 key `key` was bound to
+    /// before gets `chip8_key`'s old binding, so no two share a key.
+    pub fn rebind(&mut self, chip8_key: u8, key: Key) {
+        let old: Key = self.keys[chip8_key as usize];
+        for k in self.keys.iter_mut() {
+            if *k == key {
+                *k = old;
+            }
+        }
+        self.keys[chip8_key as usize] = key;
+    }
+
+    pub(crate) fn apply(&mut self, section: &KeyMappingSection) -> Result<(), SimpleError> {
+        if let Some(preset) = &section.preset {
+            *self = KeyMapping::from_preset(preset)?;
+        }
+        for (chip8_key, name) in section.keys.iter() {
+            let idx: u8 = match u8::from_str_radix(chip8_key, 16) {
+                Ok(i) if i < 16 => i,
+                _ => return Err(simple_error!("Bad CHIP-8 key {:?}", chip8_key)),
+            };
+            match key_from_name(name) {
+                Some(k) => self.keys[idx as usize] = k,
+                None => return Err(simple_error!("Unknown key {:?}", name)),
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn to_section(self) -> KeyMappingSection {
+        KeyMappingSection {
+            preset: None,
+            keys: self
+                .keys
+                .iter()
+                .enumerate()
+                .map(|(i, k)| (format!("{:X}", i), key_name(*k)))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeyMappingSection {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// CHIP-8 key in hex to keyboard key name, e.g. `A = "Z"`.
+    #[serde(default)]
+    pub keys: BTreeMap<String, String>,
+}
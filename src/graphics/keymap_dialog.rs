@@ -0,0 +1,126 @@
+use crate::config::Chip8Config;
+use crate::graphics::key_mapping::*;
+use eframe::egui;
+use std::path::Path;
+
+/// Window for rebinding the keypad: click a CHIP-8 key, then press the
+/// keyboard key it should be bound to (Escape cancels).
+#[derive(Default)]
+pub struct Chip8KeymapDialog {
+    pub open: bool,
+    /// The current ROM's hash, which per-ROM settings are keyed by, and the
+    /// name to show for it.
+    rom: Option<(String, String)>,
+    per_rom: bool,
+    waiting_for: Option<u8>,
+    status: String,
+}
+
+impl Chip8KeymapDialog {
+    /// Switches to the ROM with hash `rom`, called `name`.
+    pub fn set_rom(&mut self, config: &Chip8Config, rom: &str, name: &str) {
+        self.per_rom = config.has_rom_mapping(rom);
+        self.rom = Some((String::from(rom), String::from(name)));
+        self.waiting_for = None;
+        self.status.clear();
+    }
+
+    /// True while waiting for the user to press the key to bind, during
+    /// which key presses shouldn't reach the core.
+    pub fn is_capturing(&self) -> bool {
+        self.waiting_for.is_some()
+    }
+
+    /// Shows the window. Saving stores the mapping in `config` and writes it
+    /// to `path`.
+    pub fn show(
+        &mut self,
+        ctx: &egui::CtxRef,
+        mapping: &mut KeyMapping,
+        config: &mut Chip8Config,
+        path: Option<&Path>,
+    ) {
+        if !self.open {
+            self.waiting_for = None;
+            return;
+        }
+        if let Some(chip8_key) = self.waiting_for {
+            for event in ctx.input().events.iter() {
+                if let egui::Event::Key {
+                    key, pressed: true, ..
+                } = event
+                {
+                    if *key != egui::Key::Escape {
+                        mapping.rebind(chip8_key, *key);
+                    }
+                    self.waiting_for = None;
+                    break;
+                }
+            }
+        }
+
+        let mut open: bool = self.open;
+        egui::Window::new("Keypad")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| self.contents(ui, mapping, config, path));
+        self.open = open;
+    }
+
+    fn contents(
+        &mut self,
+        ui: &mut egui::Ui,
+        mapping: &mut KeyMapping,
+        config: &mut Chip8Config,
+        path: Option<&Path>,
+    ) {
+        egui::Grid::new("keymap_grid").show(ui, |ui| {
+            for row in KEYPAD_LAYOUT.iter() {
+                for chip8_key in row.iter() {
+                    let label: String = match self.waiting_for {
+                        Some(k) if k == *chip8_key => format!("{:X}: ...", chip8_key),
+                        _ => format!("{:X}: {}", chip8_key, key_name(mapping.key(*chip8_key))),
+                    };
+                    if ui.button(label).clicked() {
+                        self.waiting_for = Some(*chip8_key);
+                    }
+                }
+                ui.end_row();
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("COSMAC layout").clicked() {
+                *mapping = KeyMapping::cosmac();
+            }
+            if ui.button("Linear layout").clicked() {
+                *mapping = KeyMapping::linear();
+            }
+        });
+
+        if let Some((_, name)) = &self.rom {
+            ui.checkbox(&mut self.per_rom, format!("Only for {}", name));
+        }
+        ui.horizontal(|ui| {
+            let path: &Path = match path {
+                Some(p) => p,
+                None => {
+                    ui.label("No settings file to save to");
+                    return;
+                }
+            };
+            if ui.button("Save").clicked() {
+                let rom: Option<&str> = match (self.per_rom, &self.rom) {
+                    (true, Some((hash, _))) => Some(hash),
+                    _ => None,
+                };
+                config.set_mapping(rom, mapping);
+                self.status = match config.save(path) {
+                    Ok(_) => format!("Saved to {}", path.display()),
+                    Err(e) => e.to_string(),
+                };
+            }
+            ui.label(self.status.as_str());
+        });
+    }
+}
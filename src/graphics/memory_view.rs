@@ -0,0 +1,158 @@
+use crate::{
+    core::Chip8MemorySnapshot,
+    graphics::debug_adapter::{Chip8DebugRequest, DebugAdapter},
+};
+use eframe::egui::{self, Color32, RichText};
+use log::error;
+
+const BYTES_PER_ROW: usize = 16;
+const PC_COLOR: Color32 = Color32::DARK_GREEN;
+const INDEX_COLOR: Color32 = Color32::DARK_BLUE;
+const STACK_COLOR: Color32 = Color32::BROWN;
+const SELECTED_COLOR: Color32 = Color32::DARK_GRAY;
+
+/// Hex view of the core's memory, with the PC, I and every return address on
+/// the stack highlighted. Clicking a byte selects it for editing.
+pub struct Chip8MemoryView {
+    pub open: bool,
+    pub docked: bool,
+    snapshot: Option<Chip8MemorySnapshot>,
+    selected: Option<u16>,
+    edit_text: String,
+    edit_error: bool,
+}
+
+impl Chip8MemoryView {
+    pub fn new() -> Chip8MemoryView {
+        Chip8MemoryView {
+            open: false,
+            docked: true,
+            snapshot: None,
+            selected: None,
+            edit_text: String::new(),
+            edit_error: false,
+        }
+    }
+
+    pub fn set_snapshot(&mut self, snapshot: Chip8MemorySnapshot) {
+        self.snapshot = Some(snapshot);
+    }
+
+    pub fn show(&mut self, ctx: &egui::CtxRef, da: &DebugAdapter) {
+        if !self.open {
+            return;
+        }
+        if self.docked {
+            egui::SidePanel::right("memory_view")
+                .resizable(true)
+                .show(ctx, |ui| self.contents(ui, da));
+        } else {
+            let mut open: bool = self.open;
+            egui::Window::new("Memory")
+                .open(&mut open)
+                .default_size([520.0, 400.0])
+                .show(ctx, |ui| self.contents(ui, da));
+            self.open = open;
+        }
+    }
+
+    fn highlight(
+        snapshot: &Chip8MemorySnapshot,
+        addr: u16,
+        selected: Option<u16>,
+    ) -> Option<Color32> {
+        let covers = |start: u16| addr == start || addr == start.wrapping_add(1);
+        if selected == Some(addr) {
+            Some(SELECTED_COLOR)
+        } else if covers(snapshot.pc) {
+            Some(PC_COLOR)
+        } else if addr == snapshot.index_reg {
+            Some(INDEX_COLOR)
+        } else if snapshot.stack.iter().any(|s| covers(*s)) {
+            Some(STACK_COLOR)
+        } else {
+            None
+        }
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui, da: &DebugAdapter) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.docked, "Dock");
+            ui.label(RichText::new("PC").background_color(PC_COLOR));
+            ui.label(RichText::new("I").background_color(INDEX_COLOR));
+            ui.label(RichText::new("Stack").background_color(STACK_COLOR));
+        });
+
+        let snapshot: &Chip8MemorySnapshot = match &self.snapshot {
+            Some(s) => s,
+            None => {
+                ui.label("Waiting for the core...");
+                return;
+            }
+        };
+
+        let selected: Option<u16> = self.selected;
+        let mut clicked: Option<u16> = None;
+        let row_height: f32 = ui.fonts().row_height(egui::TextStyle::Monospace);
+        let rows: usize = snapshot.mem.len() / BYTES_PER_ROW;
+        egui::ScrollArea::vertical()
+            .max_height(ui.available_height() - 2.0 * row_height)
+            .show_rows(ui, row_height, rows, |ui, row_range| {
+                for row in row_range {
+                    ui.horizontal(|ui| {
+                        ui.monospace(format!("{:03X}:", row * BYTES_PER_ROW));
+                        for col in 0..BYTES_PER_ROW {
+                            let addr: u16 = (row * BYTES_PER_ROW + col) as u16;
+                            let mut text =
+                                RichText::new(format!("{:02X}", snapshot.mem[addr as usize]))
+                                    .monospace();
+                            if let Some(color) =
+                                Chip8MemoryView::highlight(snapshot, addr, selected)
+                            {
+                                text = text.background_color(color);
+                            }
+                            let label = egui::Label::new(text).sense(egui::Sense::click());
+                            if ui.add(label).clicked() {
+                                clicked = Some(addr);
+                            }
+                        }
+                    });
+                }
+            });
+
+        if let Some(addr) = clicked {
+            self.selected = Some(addr);
+            self.edit_text = format!("{:02X}", snapshot.mem[addr as usize]);
+            self.edit_error = false;
+        }
+
+        if let Some(addr) = self.selected {
+            ui.horizontal(|ui| {
+                ui.monospace(format!("{:#05X} =", addr));
+                let resp = ui.add(
+                    egui::TextEdit::singleline(&mut self.edit_text)
+                        .desired_width(24.0)
+                        .text_style(egui::TextStyle::Monospace),
+                );
+                let submitted: bool = resp.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+                if submitted || ui.button("Write").clicked() {
+                    match u8::from_str_radix(self.edit_text.trim(), 16) {
+                        Ok(val) => {
+                            self.edit_error = false;
+                            if let Err(e) = da
+                                .request_sender
+                                .send(Chip8DebugRequest::WriteMemory { addr, val })
+                            {
+                                error!("ERR: {} ", e);
+                            }
+                        }
+                        Err(_) => self.edit_error = true,
+                    }
+                }
+                if self.edit_error {
+                    ui.colored_label(Color32::RED, "Not a hex byte");
+                }
+            });
+        }
+    }
+}
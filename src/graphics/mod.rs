@@ -1,3 +1,17 @@
+pub mod control_adapter;
+pub mod debug_adapter;
+pub mod debug_repl;
 pub mod eframe_runner;
+pub mod frontend;
 pub mod graphics_adapter;
-mod key_mapping;
+pub mod hotkeys;
+mod inspector;
+pub mod key_mapping;
+mod keymap_dialog;
+mod memory_view;
+pub mod phosphor;
+pub mod renderer;
+pub mod rom_browser;
+pub mod rom_watcher;
+mod tests;
+pub mod tui_runner;
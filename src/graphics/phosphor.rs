@@ -0,0 +1,143 @@
+use crate::core::Chip8DisplayData;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+pub const DEFAULT_DECAY: f32 = 0.6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Chip8PersistenceMode {
+    /// Show exactly what's in the framebuffer.
+    Off,
+    /// Lit pixels fade out over a few frames instead of vanishing, like a CRT.
+    Decay,
+    /// A pixel is lit if it was lit in either of the last two frames.
+    #[serde(rename = "or")]
+    OrLastTwo,
+}
+
+impl FromStr for Chip8PersistenceMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Chip8PersistenceMode::Off),
+            "decay" => Ok(Chip8PersistenceMode::Decay),
+            "or" => Ok(Chip8PersistenceMode::OrLastTwo),
+            _ => Err(format!("Expected off, decay or or, got {:?}", s)),
+        }
+    }
+}
+
+/// A post-processed frame, with each pixel's brightness between 0 (unlit)
+/// and 1 (fully lit).
+#[derive(Clone)]
+pub struct Chip8IntensityFrame {
+    width: usize,
+    height: usize,
+    intensity: Vec<f32>,
+}
+
+impl Chip8IntensityFrame {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn intensity(&self, x: usize, y: usize) -> f32 {
+        self.intensity[y * self.width + x]
+    }
+}
+
+/// Anti-flicker stage between the framebuffer and whatever presents it.
+/// CHIP-8 games erase sprites by XORing them again, so moving objects are
+/// only lit on alternate frames; blending recent frames hides that.
+pub struct Chip8Phosphor {
+    pub mode: Chip8PersistenceMode,
+    /// Fraction of its brightness a pixel keeps each frame in `Decay` mode.
+    pub decay: f32,
+    frame: Chip8IntensityFrame,
+    current: Option<Chip8DisplayData>,
+    previous: Option<Chip8DisplayData>,
+}
+
+impl Chip8Phosphor {
+    pub fn new(mode: Chip8PersistenceMode, decay: f32) -> Chip8Phosphor {
+        Chip8Phosphor {
+            mode,
+            decay,
+            frame: Chip8IntensityFrame {
+                width: 0,
+                height: 0,
+                intensity: Vec::new(),
+            },
+            current: None,
+            previous: None,
+        }
+    }
+
+    /// Feeds in the newest framebuffer and returns the blended result.
+    /// `is_new` says whether the core has published a new one since the last
+    /// call, and `frames` how many frames it has run, so decaying pixels fade
+    /// at the emulated frame rate however often this is called.
+    pub fn process(
+        &mut self,
+        data: &Chip8DisplayData,
+        is_new: bool,
+        frames: u64,
+    ) -> &Chip8IntensityFrame {
+        let (w, h) = (data.width(), data.height());
+        if self.frame.width != w || self.frame.height != h {
+            self.frame = Chip8IntensityFrame {
+                width: w,
+                height: h,
+                intensity: vec![0.0; w * h],
+            };
+            self.current = None;
+            self.previous = None;
+        }
+        if is_new || self.current.is_none() {
+            self.previous = self.current.take();
+            self.current = Some(*data);
+        } else if frames > 0 {
+            // The core ran a frame without changing the display, so the
+            // frame before this one looks the same as this one.
+            self.previous = self.current;
+        }
+
+        let fade: f32 = self
+            .decay
+            .clamp(0.0, 1.0)
+            .powi(frames.min(u8::MAX as u64) as i32);
+        for y in 0..h {
+            for x in 0..w {
+                let lit: bool = data.pixel(x, y);
+                let cell: &mut f32 = &mut self.frame.intensity[y * w + x];
+                *cell = match self.mode {
+                    Chip8PersistenceMode::Off => lit as u8 as f32,
+                    Chip8PersistenceMode::Decay => {
+                        let faded: f32 = *cell * fade;
+                        if lit {
+                            1.0
+                        } else if faded < 1.0 / 255.0 {
+                            0.0
+                        } else {
+                            faded
+                        }
+                    }
+                    Chip8PersistenceMode::OrLastTwo => {
+                        let was_lit: bool = match &self.previous {
+                            Some(prev) => prev.pixel(x, y),
+                            None => false,
+                        };
+                        (lit || was_lit) as u8 as f32
+                    }
+                };
+            }
+        }
+        &self.frame
+    }
+}
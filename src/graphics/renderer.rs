@@ -0,0 +1,125 @@
+use crate::graphics::phosphor::Chip8IntensityFrame;
+use eframe::egui::{self, Color32, Pos2, Rect, Vec2};
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Image pixels per CHIP-8 pixel in screenshots, and the initial window size.
+pub const DEFAULT_SCALE: usize = 8;
+
+pub const DEFAULT_FG_COLOR: Color32 = Color32::WHITE;
+pub const DEFAULT_BG_COLOR: Color32 = Color32::BLACK;
+
+/// Parses an `RRGGBB` or `#RRGGBB` hex colour.
+pub fn parse_color(s: &str) -> Result<Color32, String> {
+    let hex: &str = s.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("Expected a colour like #RRGGBB, got {:?}", s));
+    }
+    let rgb = u32::from_str_radix(hex, 16).map_err(|e| format!("Bad colour {:?}: {}", s, e))?;
+    Ok(Color32::from_rgb(
+        (rgb >> 16) as u8,
+        (rgb >> 8) as u8,
+        rgb as u8,
+    ))
+}
+
+/// Formats `color` as `#RRGGBB`, the inverse of `parse_color`.
+pub fn format_color(color: Color32) -> String {
+    format!("#{:02X}{:02X}{:02X}", color.r(), color.g(), color.b())
+}
+
+fn blend(bg: Color32, fg: Color32, t: f32) -> Color32 {
+    let mix = |b: u8, f: u8| (b as f32 + (f as f32 - b as f32) * t).round() as u8;
+    Color32::from_rgb(
+        mix(bg.r(), fg.r()),
+        mix(bg.g(), fg.g()),
+        mix(bg.b(), fg.b()),
+    )
+}
+
+/// Paints the framebuffer as square pixels, scaled by the largest whole
+/// number of screen pixels that fits the available space, and centred.
+pub struct Chip8DisplayRenderer {
+    pub fg: Color32,
+    pub bg: Color32,
+    /// Screenshot pixels per CHIP-8 pixel.
+    pub scale: usize,
+}
+
+impl Default for Chip8DisplayRenderer {
+    fn default() -> Self {
+        Chip8DisplayRenderer {
+            fg: DEFAULT_FG_COLOR,
+            bg: DEFAULT_BG_COLOR,
+            scale: DEFAULT_SCALE,
+        }
+    }
+}
+
+impl Chip8DisplayRenderer {
+    pub fn paint(&self, ui: &mut egui::Ui, data: &Chip8IntensityFrame) {
+        let (w, h) = (data.width() as f32, data.height() as f32);
+        let avail: Vec2 = ui.available_size();
+        let (response, painter) = ui.allocate_painter(avail, egui::Sense::hover());
+
+        // Scale in physical pixels so every emulated pixel is the same size.
+        let ppp: f32 = ui.ctx().pixels_per_point();
+        let scale_px: f32 = ((avail.x * ppp / w).min(avail.y * ppp / h))
+            .floor()
+            .max(1.0);
+        let scale: f32 = scale_px / ppp;
+        let size: Vec2 = Vec2::new(w * scale, h * scale);
+        let origin: Pos2 = painter.round_pos_to_pixels(response.rect.center() - size / 2.0);
+
+        painter.rect_filled(Rect::from_min_size(origin, size), 0.0, self.bg);
+        for y in 0..data.height() {
+            for x in 0..data.width() {
+                let intensity: f32 = data.intensity(x, y);
+                if intensity > 0.0 {
+                    let min: Pos2 = origin + Vec2::new(x as f32 * scale, y as f32 * scale);
+                    let color: Color32 = blend(self.bg, self.fg, intensity);
+                    painter.rect_filled(Rect::from_min_size(min, Vec2::splat(scale)), 0.0, color);
+                }
+            }
+        }
+    }
+
+    /// Writes `data` as a binary PPM image, `scale` image pixels to each
+    /// CHIP-8 pixel, in the same colours it's painted with.
+    pub fn write_ppm(
+        &self,
+        data: &Chip8IntensityFrame,
+        scale: usize,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let (w, h) = (data.width() * scale, data.height() * scale);
+        write!(out, "P6\n{} {}\n255\n", w, h)?;
+        let mut row: Vec<u8> = Vec::with_capacity(w * 3);
+        for y in 0..data.height() {
+            row.clear();
+            for x in 0..data.width() {
+                let color: Color32 = blend(self.bg, self.fg, data.intensity(x, y));
+                for _ in 0..scale {
+                    row.extend([color.r(), color.g(), color.b()]);
+                }
+            }
+            for _ in 0..scale {
+                out.write_all(&row)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves `data` as `chiprust8-<unix time>.ppm` in the working directory
+    /// and returns the file name.
+    pub fn save_screenshot(&self, data: &Chip8IntensityFrame) -> io::Result<String> {
+        let secs: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let path: String = format!("chiprust8-{}.ppm", secs);
+        let mut f = std::fs::File::create(&path)?;
+        self.write_ppm(data, self.scale, &mut f)?;
+        Ok(path)
+    }
+}
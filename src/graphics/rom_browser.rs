@@ -0,0 +1,224 @@
+use crate::core::{rom_db::Chip8RomDatabase, MAX_ROM_SIZE};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use simple_error::{simple_error, SimpleError};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+pub const MAX_RECENT_ROMS: usize = 10;
+
+/// Recently opened ROMs, newest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chip8RecentRoms {
+    #[serde(default)]
+    pub roms: Vec<PathBuf>,
+}
+
+impl Chip8RecentRoms {
+    /// `~/.config/chiprust8/recent.toml`, or under `$XDG_CONFIG_HOME` if set.
+    pub fn default_path() -> Option<PathBuf> {
+        let base: PathBuf = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("chiprust8").join("recent.toml"))
+    }
+
+    /// Loads the file at `path`, or an empty list if it doesn't exist.
+    pub fn load(path: &Path) -> Result<Chip8RecentRoms, SimpleError> {
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                toml::from_str(&text).map_err(|e| simple_error!("{}: {}", path.display(), e))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Chip8RecentRoms::default()),
+            Err(e) => Err(simple_error!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SimpleError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| simple_error!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let text: String = toml::to_string(self).map_err(|e| simple_error!("{}", e))?;
+        fs::write(path, text)
+            .map_err(|e| simple_error!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Moves `rom` to the front, dropping the oldest entry once there are
+    /// more than `MAX_RECENT_ROMS`.
+    pub fn push(&mut self, rom: &Path) {
+        self.roms.retain(|r| r != rom);
+        self.roms.insert(0, rom.to_path_buf());
+        self.roms.truncate(MAX_RECENT_ROMS);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chip8RomListing {
+    pub path: PathBuf,
+    /// From the ROM database, or the file name if the ROM isn't in it.
+    pub title: String,
+}
+
+/// The files in `dir` small enough to be ROMs, sorted by title.
+pub fn list_roms(dir: &Path, db: &Chip8RomDatabase) -> Result<Vec<Chip8RomListing>, SimpleError> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| simple_error!("Failed to list {}: {}", dir.display(), e))?;
+    let mut listings: Vec<Chip8RomListing> = Vec::new();
+    for entry in entries.flatten() {
+        let path: PathBuf = entry.path();
+        match entry.metadata() {
+            Ok(meta) if meta.is_file() && meta.len() > 0 && meta.len() <= MAX_ROM_SIZE as u64 => (),
+            _ => continue,
+        }
+        let rom: Vec<u8> = match fs::read(&path) {
+            Ok(rom) => rom,
+            Err(_) => continue,
+        };
+        let title: String = match db.lookup(&rom) {
+            Some(info) => info.title.clone(),
+            None => entry.file_name().to_string_lossy().into_owned(),
+        };
+        listings.push(Chip8RomListing { path, title });
+    }
+    listings.sort_by_key(|l| l.title.to_lowercase());
+    Ok(listings)
+}
+
+/// Window listing the ROM directories and recently opened ROMs. Picking one
+/// returns its path from `show`; the caller loads it.
+pub struct Chip8RomBrowser {
+    pub open: bool,
+    dirs: Vec<PathBuf>,
+    listings: Vec<Chip8RomListing>,
+    scanned: bool,
+    filter: String,
+    recent: Chip8RecentRoms,
+    recent_path: Option<PathBuf>,
+    status: String,
+}
+
+impl Chip8RomBrowser {
+    pub fn new(dirs: Vec<PathBuf>, recent_path: Option<PathBuf>) -> Chip8RomBrowser {
+        let recent: Chip8RecentRoms = match &recent_path {
+            Some(path) => Chip8RecentRoms::load(path).unwrap_or_default(),
+            None => Chip8RecentRoms::default(),
+        };
+        Chip8RomBrowser {
+            open: false,
+            dirs,
+            listings: Vec::new(),
+            scanned: false,
+            filter: String::new(),
+            recent,
+            recent_path,
+            status: String::new(),
+        }
+    }
+
+    /// Records that `rom` was opened, saving the recent list.
+    pub fn opened(&mut self, rom: &Path) {
+        let rom: PathBuf = fs::canonicalize(rom).unwrap_or_else(|_| rom.to_path_buf());
+        self.recent.push(&rom);
+        if let Some(path) = &self.recent_path {
+            if let Err(e) = self.recent.save(path) {
+                self.status = e.to_string();
+            }
+        }
+    }
+
+    fn rescan(&mut self, db: &Chip8RomDatabase) {
+        self.scanned = true;
+        self.listings.clear();
+        self.status.clear();
+        for dir in self.dirs.iter() {
+            match list_roms(dir, db) {
+                Ok(listings) => self.listings.extend(listings),
+                Err(e) => self.status = e.to_string(),
+            }
+        }
+        self.listings.sort_by_key(|l| l.title.to_lowercase());
+    }
+
+    pub fn show(&mut self, ctx: &egui::CtxRef, db: &Chip8RomDatabase) -> Option<PathBuf> {
+        if !self.open {
+            return None;
+        }
+        if !self.scanned {
+            self.rescan(db);
+        }
+        let mut picked: Option<PathBuf> = None;
+        let mut open: bool = self.open;
+        egui::Window::new("ROMs")
+            .open(&mut open)
+            .default_height(400.0)
+            .show(ctx, |ui| picked = self.contents(ui, db));
+        self.open = open && picked.is_none();
+        picked
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui, db: &Chip8RomDatabase) -> Option<PathBuf> {
+        let mut picked: Option<PathBuf> = None;
+        if !self.recent.roms.is_empty() {
+            ui.label("Recent");
+            for rom in self.recent.roms.iter() {
+                let name: String = rom
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                if ui
+                    .button(name)
+                    .on_hover_text(rom.display().to_string())
+                    .clicked()
+                {
+                    picked = Some(rom.clone());
+                }
+            }
+            ui.separator();
+        }
+
+        ui.horizontal(|ui| {
+            match self.dirs.as_slice() {
+                [] => ui.label("No ROM directories"),
+                [dir] => ui.label(dir.display().to_string()),
+                dirs => ui
+                    .label(format!("{} directories", dirs.len()))
+                    .on_hover_text(
+                        dirs.iter()
+                            .map(|d| d.display().to_string())
+                            .collect::<Vec<String>>()
+                            .join("\n"),
+                    ),
+            };
+            if ui.button("Rescan").clicked() {
+                self.rescan(db);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Filter");
+            ui.text_edit_singleline(&mut self.filter);
+        });
+        let filter: String = self.filter.to_lowercase();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for listing in self
+                .listings
+                .iter()
+                .filter(|l| l.title.to_lowercase().contains(&filter))
+            {
+                if ui
+                    .selectable_label(false, listing.title.as_str())
+                    .on_hover_text(listing.path.display().to_string())
+                    .clicked()
+                {
+                    picked = Some(listing.path.clone());
+                }
+            }
+        });
+        if !self.status.is_empty() {
+            ui.label(self.status.as_str());
+        }
+        picked
+    }
+}
@@ -0,0 +1,106 @@
+use crate::graphics::control_adapter::{Chip8Command, ControlAdapter};
+use crossbeam::channel::{unbounded, Sender};
+use log::{info, warn};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const RELOAD_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What the file looked like when last polled. A change is only acted on once
+/// two polls in a row agree, so a ROM caught half-written by an assembler isn't
+/// loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Chip8RomStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+/// Polls a ROM file for changes.
+pub struct Chip8RomWatcher {
+    path: PathBuf,
+    loaded: Option<Chip8RomStamp>,
+    pending: Option<Chip8RomStamp>,
+}
+
+impl Chip8RomWatcher {
+    /// Starts from the file as it is now, so the ROM the core was started with
+    /// isn't reloaded straight away.
+    pub fn new(path: &Path) -> Chip8RomWatcher {
+        let mut watcher = Chip8RomWatcher {
+            path: path.to_path_buf(),
+            loaded: None,
+            pending: None,
+        };
+        watcher.loaded = watcher.stamp().ok();
+        watcher
+    }
+
+    fn stamp(&self) -> io::Result<Chip8RomStamp> {
+        let meta: fs::Metadata = fs::metadata(&self.path)?;
+        Ok(Chip8RomStamp {
+            modified: meta.modified()?,
+            len: meta.len(),
+        })
+    }
+
+    /// The new ROM image, if the file has changed and settled since the last
+    /// call. A missing or empty file is treated as mid-write and skipped.
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        let stamp: Chip8RomStamp = match self.stamp() {
+            Ok(s) if s.len > 0 => s,
+            _ => {
+                self.pending = None;
+                return None;
+            }
+        };
+        if Some(stamp) == self.loaded {
+            self.pending = None;
+            return None;
+        }
+        if Some(stamp) != self.pending {
+            self.pending = Some(stamp);
+            return None;
+        }
+        self.pending = None;
+        match fs::read(&self.path) {
+            Ok(rom) => {
+                self.loaded = Some(stamp);
+                Some(rom)
+            }
+            Err(e) => {
+                warn!("Failed to read {}: {}", self.path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Polls on a background thread for the rest of the process' life, sending
+    /// each new image to the core. The core keeps its quirks, speed and
+    /// paused state across the reload. Send a path on the returned channel to
+    /// watch a different ROM, e.g. after switching ROMs.
+    pub fn spawn(mut self, control: &ControlAdapter, interval: Duration) -> Sender<PathBuf> {
+        let control: ControlAdapter = control.clone();
+        let (retarget, retargets) = unbounded::<PathBuf>();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            if let Some(path) = retargets.try_iter().last() {
+                self = Chip8RomWatcher::new(&path);
+            }
+            if let Some(rom) = self.poll() {
+                info!("{} changed, reloading", self.path.display());
+                match control.call(Chip8Command::LoadRom(rom), RELOAD_TIMEOUT) {
+                    Ok(ack) => {
+                        if let Err(e) = ack.result {
+                            warn!("Not reloading: {}", e);
+                        }
+                    }
+                    Err(e) => warn!("{}", e),
+                }
+            }
+        });
+        retarget
+    }
+}
@@ -0,0 +1,484 @@
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests {
+    use crate::config::*;
+    use crate::core::{keypad::Chip8KeyEvent, Chip8DisplayData};
+    use crate::graphics::control_adapter::*;
+    use crate::graphics::frontend::*;
+    use crate::graphics::graphics_adapter::GraphicsAdapter;
+    use crate::graphics::hotkeys::*;
+    use crate::graphics::key_mapping::*;
+    use crate::graphics::phosphor::*;
+    use eframe::egui::Key;
+    use std::collections::BTreeMap;
+
+    fn display_with(pixels: &[(usize, usize)]) -> Chip8DisplayData {
+        let mut data = Chip8DisplayData::default();
+        for (x, y) in pixels {
+            data.set_pixel(*x, *y, true);
+        }
+        data
+    }
+
+    #[test]
+    fn test_phosphor_off() {
+        let mut phosphor = Chip8Phosphor::new(Chip8PersistenceMode::Off, DEFAULT_DECAY);
+        phosphor.process(&display_with(&[(1, 1)]), true, 1);
+        let frame = phosphor.process(&display_with(&[]), true, 1);
+        assert_eq!(frame.intensity(1, 1), 0.0);
+    }
+
+    #[test]
+    fn test_phosphor_decay() {
+        let mut phosphor = Chip8Phosphor::new(Chip8PersistenceMode::Decay, 0.5);
+        let frame = phosphor.process(&display_with(&[(1, 1)]), true, 1);
+        assert_eq!(frame.intensity(1, 1), 1.0);
+
+        let blank = display_with(&[]);
+        assert_eq!(phosphor.process(&blank, true, 1).intensity(1, 1), 0.5);
+        assert_eq!(phosphor.process(&blank, false, 1).intensity(1, 1), 0.25);
+        // Extra repaints between the core's frames don't fade anything
+        for _ in 0..8 {
+            assert_eq!(phosphor.process(&blank, false, 0).intensity(1, 1), 0.25);
+        }
+        assert_eq!(phosphor.process(&blank, false, 2).intensity(1, 1), 0.0625);
+        phosphor.process(&blank, false, 8);
+        assert_eq!(phosphor.process(&blank, false, 1).intensity(1, 1), 0.0);
+    }
+
+    #[test]
+    fn test_phosphor_or_last_two() {
+        let mut phosphor = Chip8Phosphor::new(Chip8PersistenceMode::OrLastTwo, DEFAULT_DECAY);
+        phosphor.process(&display_with(&[(1, 1)]), true, 1);
+        let frame = phosphor.process(&display_with(&[(2, 1)]), true, 1);
+        assert_eq!(frame.intensity(1, 1), 1.0);
+        assert_eq!(frame.intensity(2, 1), 1.0);
+
+        // Repainting without a new frame from the core keeps the pair.
+        let frame = phosphor.process(&display_with(&[(2, 1)]), false, 0);
+        assert_eq!(frame.intensity(1, 1), 1.0);
+
+        let frame = phosphor.process(&display_with(&[(3, 1)]), true, 1);
+        assert_eq!(frame.intensity(1, 1), 0.0);
+
+        // An erased sprite goes once a frame passes with nothing new drawn.
+        let frame = phosphor.process(&display_with(&[]), true, 1);
+        assert_eq!(frame.intensity(3, 1), 1.0);
+        let frame = phosphor.process(&display_with(&[]), false, 1);
+        assert_eq!(frame.intensity(3, 1), 0.0);
+    }
+
+    #[test]
+    fn test_keymap_presets() {
+        let cosmac = KeyMapping::default();
+        assert_eq!(cosmac.key(0x1), Key::Num1);
+        assert_eq!(cosmac.key(0xC), Key::Num4);
+        assert_eq!(cosmac.key(0x0), Key::X);
+        assert_eq!(cosmac.key(0xF), Key::V);
+        assert_eq!(
+            KeyMapping::from_preset("linear").unwrap().key(0x0),
+            Key::Num1
+        );
+        assert!(KeyMapping::from_preset("dvorak").is_err());
+    }
+
+    #[test]
+    fn test_keymap_rebind_swaps() {
+        let mut mapping = KeyMapping::cosmac();
+        mapping.rebind(0x5, Key::X);
+        assert_eq!(mapping.key(0x5), Key::X);
+        assert_eq!(mapping.key(0x0), Key::W);
+    }
+
+    #[test]
+    fn test_keymap_config_per_rom() {
+        let mut config: Chip8Config = toml::from_str(
+            r#"
+            preset = "linear"
+
+            [roms."pong.ch8"]
+            keys = { 1 = "W", 4 = "S" }
+            "#,
+        )
+        .unwrap();
+        let default = config.mapping_for(Some("other.ch8")).unwrap();
+        assert_eq!(default, KeyMapping::linear());
+        let pong = config.mapping_for(Some("pong.ch8")).unwrap();
+        assert_eq!(pong.key(0x1), Key::W);
+        assert_eq!(pong.key(0x4), Key::S);
+        assert_eq!(pong.key(0x0), Key::Num1);
+
+        let mut custom = KeyMapping::cosmac();
+        custom.rebind(0xA, Key::Space);
+        config.set_mapping(Some("tetris.ch8"), &custom);
+        let text: String = toml::to_string(&config).unwrap();
+        let reloaded: Chip8Config = toml::from_str(&text).unwrap();
+        assert_eq!(reloaded.mapping_for(Some("tetris.ch8")).unwrap(), custom);
+        assert_eq!(reloaded.mapping_for(None).unwrap(), KeyMapping::linear());
+    }
+
+    #[test]
+    fn test_hotkey_parse() {
+        let hotkey: Hotkey = "Ctrl+Shift+S".parse().unwrap();
+        assert_eq!(hotkey, Hotkey::ctrl_shift(Key::S));
+        assert_eq!(hotkey.to_string(), "Ctrl+Shift+S");
+        assert_eq!("Tab".parse::<Hotkey>().unwrap(), Hotkey::new(Key::Tab));
+        assert!("Ctrl+".parse::<Hotkey>().is_err());
+        assert!("Meta+S".parse::<Hotkey>().is_err());
+    }
+
+    #[test]
+    fn test_hotkey_overrides() {
+        let mut hotkeys = Chip8Hotkeys::default();
+        let ctrl = eframe::egui::Modifiers {
+            ctrl: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            hotkeys.action_for(Key::P, &ctrl),
+            Some(Chip8HotkeyAction::Pause)
+        );
+        assert_eq!(hotkeys.action_for(Key::P, &Default::default()), None);
+
+        let config: Chip8Config = toml::from_str(
+            r#"
+            [hotkeys]
+            pause = "Space"
+            load_state_1 = ""
+            "#,
+        )
+        .unwrap();
+        hotkeys.apply(&config.hotkeys).unwrap();
+        assert_eq!(
+            hotkeys.action_for(Key::Space, &Default::default()),
+            Some(Chip8HotkeyAction::Pause)
+        );
+        assert_eq!(hotkeys.action_for(Key::P, &ctrl), None);
+        assert_eq!(hotkeys.binding(Chip8HotkeyAction::LoadState(0)), None);
+
+        let mut bad = BTreeMap::new();
+        bad.insert(String::from("explode"), String::from("Ctrl+E"));
+        assert!(hotkeys.apply(&bad).is_err());
+    }
+
+    #[derive(Default)]
+    struct MockFrontend {
+        frames: usize,
+        tone: bool,
+        paused: bool,
+        keys: Vec<Chip8KeyEvent>,
+        commands: Vec<Chip8Command>,
+    }
+
+    impl Chip8Frontend for MockFrontend {
+        fn present_frame(&mut self, _frame: &Chip8DisplayData) {
+            self.frames += 1;
+        }
+
+        fn poll_input(&mut self) -> Vec<Chip8KeyEvent> {
+            std::mem::take(&mut self.keys)
+        }
+
+        fn play_tone(&mut self) {
+            self.tone = true;
+        }
+
+        fn stop_tone(&mut self) {
+            self.tone = false;
+        }
+
+        fn set_paused(&mut self, paused: bool) {
+            self.paused = paused;
+        }
+
+        fn poll_control(&mut self) -> Vec<Chip8Command> {
+            std::mem::take(&mut self.commands)
+        }
+    }
+
+    #[test]
+    fn test_frontend_driver() {
+        let ga = GraphicsAdapter::new();
+        let ca = ControlAdapter::new();
+        let mut driver = Chip8FrontendDriver::new(&ga);
+        driver.attach_control(&ca);
+        let mut frontend = MockFrontend::default();
+
+        assert!(!driver.pump(&mut frontend));
+        ga.publish(&display_with(&[(0, 0)]));
+        ga.set_sound_active(true);
+        frontend.keys.push(Chip8KeyEvent::new(0x3, true));
+        frontend.commands.push(Chip8Command::Pause);
+        assert!(driver.pump(&mut frontend));
+        assert!(!driver.pump(&mut frontend));
+        assert_eq!(frontend.frames, 1);
+        assert!(frontend.tone);
+        assert_eq!(ga.key_event_receiver.try_recv().unwrap().key, 0x3);
+        assert_eq!(
+            ca.command_receiver.try_recv().unwrap().command,
+            Chip8Command::Pause
+        );
+
+        ga.set_sound_active(false);
+        driver.pump(&mut frontend);
+        assert!(!frontend.tone);
+
+        ga.set_paused(true);
+        driver.pump(&mut frontend);
+        assert!(frontend.paused);
+        ga.set_paused(false);
+        driver.pump(&mut frontend);
+        assert!(!frontend.paused);
+    }
+
+    #[test]
+    fn test_half_block_rows() {
+        use crate::graphics::tui_runner::half_block_rows;
+        let rows = half_block_rows(&display_with(&[(0, 0), (1, 1), (2, 0), (2, 1), (63, 31)]));
+        assert_eq!(rows.len(), 16);
+        assert!(rows[0].starts_with("▀▄█ "));
+        assert_eq!(rows[0].chars().count(), 64);
+        assert!(rows[15].ends_with('▄'));
+    }
+
+    #[test]
+    fn test_terminal_hotkeys() {
+        use crate::graphics::tui_runner::terminal_key;
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+        let hotkeys = Chip8Hotkeys::terminal();
+        let press = |code: KeyCode, modifiers: KeyModifiers| {
+            let (key, modifiers) = terminal_key(&KeyEvent::new(code, modifiers))?;
+            hotkeys.action_for(key, &modifiers)
+        };
+
+        // What a terminal sends for Alt+key: Esc, then the key
+        assert_eq!(
+            press(KeyCode::Char('1'), KeyModifiers::ALT),
+            Some(Chip8HotkeyAction::LoadState(0))
+        );
+        assert_eq!(
+            press(KeyCode::Char('r'), KeyModifiers::ALT),
+            Some(Chip8HotkeyAction::SaveState(3))
+        );
+        assert_eq!(
+            press(KeyCode::Char('h'), KeyModifiers::ALT),
+            Some(Chip8HotkeyAction::Help)
+        );
+        assert_eq!(
+            press(KeyCode::Char('p'), KeyModifiers::CONTROL),
+            Some(Chip8HotkeyAction::Pause)
+        );
+        // Ctrl+1 arrives as a plain 1, and Ctrl+H as Backspace in some terminals
+        assert_eq!(press(KeyCode::Char('1'), KeyModifiers::NONE), None);
+        assert_eq!(press(KeyCode::Backspace, KeyModifiers::NONE), None);
+
+        // No default needs a combination terminals can't send
+        for (action, hotkey) in hotkeys.bindings() {
+            let hotkey = hotkey.unwrap();
+            let digit: bool = key_name(hotkey.key).starts_with("Num");
+            assert!(!(hotkey.shift || (hotkey.ctrl && digit)), "{:?}", action);
+            assert_ne!((hotkey.ctrl, hotkey.key), (true, Key::H), "{:?}", action);
+        }
+        assert_eq!(
+            Chip8Hotkeys::default().binding(Chip8HotkeyAction::Help),
+            Some(Hotkey::ctrl(Key::H))
+        );
+    }
+
+    #[test]
+    fn test_rom_watcher() {
+        use crate::graphics::rom_watcher::Chip8RomWatcher;
+        let path = std::env::temp_dir().join("chiprust8_test_watch.ch8");
+        std::fs::write(&path, [0x13, 0x00]).unwrap();
+        let mut watcher = Chip8RomWatcher::new(&path);
+        assert_eq!(watcher.poll(), None);
+
+        // Emptied mid-write, then written out in full
+        std::fs::write(&path, []).unwrap();
+        assert_eq!(watcher.poll(), None);
+        std::fs::write(&path, [0x00, 0xE0, 0x12, 0x02]).unwrap();
+        assert_eq!(watcher.poll(), None);
+        assert_eq!(watcher.poll(), Some(vec![0x00, 0xE0, 0x12, 0x02]));
+        assert_eq!(watcher.poll(), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_recent_roms() {
+        use crate::graphics::rom_browser::*;
+        use std::path::{Path, PathBuf};
+        let mut recent = Chip8RecentRoms::default();
+        for i in 0..MAX_RECENT_ROMS + 2 {
+            recent.push(Path::new(&format!("/roms/{}.ch8", i)));
+        }
+        recent.push(Path::new("/roms/5.ch8"));
+        assert_eq!(recent.roms.len(), MAX_RECENT_ROMS);
+        assert_eq!(recent.roms[0], PathBuf::from("/roms/5.ch8"));
+        assert_eq!(recent.roms[1], PathBuf::from("/roms/11.ch8"));
+        assert_eq!(
+            recent.roms.iter().filter(|r| r.ends_with("5.ch8")).count(),
+            1
+        );
+
+        let path = std::env::temp_dir().join("chiprust8_test_recent.toml");
+        recent.save(&path).unwrap();
+        assert_eq!(Chip8RecentRoms::load(&path).unwrap(), recent);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_list_roms() {
+        use crate::core::rom_db::{sha1_hex, Chip8RomDatabase};
+        use crate::graphics::rom_browser::list_roms;
+        let dir = std::env::temp_dir().join("chiprust8_test_roms");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("subdir")).unwrap();
+        std::fs::write(dir.join("b.ch8"), [0x12, 0x00]).unwrap();
+        std::fs::write(dir.join("known.ch8"), [0x00, 0xE0]).unwrap();
+        std::fs::write(dir.join("empty.ch8"), []).unwrap();
+        std::fs::write(dir.join("huge.bin"), vec![0; 8192]).unwrap();
+
+        let db = Chip8RomDatabase::from_json(&format!(
+            r#"[{{"title": "A Game", "roms": {{"{}": {{}}}}}}]"#,
+            sha1_hex(&[0x00, 0xE0])
+        ))
+        .unwrap();
+        let titles: Vec<String> = list_roms(&dir, &db)
+            .unwrap()
+            .into_iter()
+            .map(|l| l.title)
+            .collect();
+        assert_eq!(titles, vec!["A Game", "b.ch8"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_layering() {
+        use crate::core::{quirks::Chip8Quirks, rom_db::Chip8RomInfo, DEFAULT_INSTRS_PER_FRAME};
+        use crate::graphics::renderer::{parse_color, DEFAULT_BG_COLOR};
+        let config: Chip8Config = toml::from_str(
+            r##"
+            speed = 15
+            fg = "#33FF66"
+            quirks = "modern"
+
+            [audio]
+            enabled = false
+
+            [roms.abc]
+            speed = 30
+            quirks = { logic_resets_vf = true }
+            "##,
+        )
+        .unwrap();
+        assert!(!config.audio.enabled);
+        let green = parse_color("#33FF66").unwrap();
+
+        let defaults = config.settings_for(None, None);
+        assert_eq!(defaults.speed, 15);
+        assert_eq!(defaults.quirks, Chip8Quirks::modern());
+        assert_eq!((defaults.fg, defaults.bg), (green, DEFAULT_BG_COLOR));
+
+        // The ROM database beats the global defaults...
+        let info = Chip8RomInfo {
+            quirks: Some(Chip8Quirks::cosmac()),
+            tickrate: Some(20),
+            colors: Some((String::from("#FF0000"), String::from("#0000FF"))),
+            ..Default::default()
+        };
+        let known = config.settings_for(Some("def"), Some(&info));
+        assert_eq!(known.speed, 20);
+        assert_eq!(known.quirks, Chip8Quirks::cosmac());
+        assert_eq!(known.bg, parse_color("#0000FF").unwrap());
+
+        // ...but not the ROM's own table, and the command line beats both.
+        let own = config.settings_for(Some("abc"), Some(&info));
+        assert_eq!(own.speed, 30);
+        assert_eq!(
+            own.quirks,
+            Chip8Quirks {
+                logic_resets_vf: true,
+                ..Chip8Quirks::default()
+            }
+        );
+        assert_eq!(own.fg, parse_color("#FF0000").unwrap());
+        let overrides = Chip8Overrides {
+            speed: Some(5),
+            fg: Some(green),
+            ..Default::default()
+        };
+        assert_eq!(overrides.apply(own).speed, 5);
+        assert_eq!(overrides.apply(own).fg, green);
+        assert_eq!(overrides.apply(own).quirks, own.quirks);
+
+        let empty = Chip8Config::default().settings_for(None, None);
+        assert_eq!(empty.speed, DEFAULT_INSTRS_PER_FRAME);
+        assert_eq!(empty.quirks, Chip8Quirks::default());
+    }
+
+    #[test]
+    fn test_config_save_and_validate() {
+        use crate::core::quirks::Chip8Quirks;
+        let mut config = Chip8Config {
+            speed: Some(12),
+            persistence: Some(Chip8PersistenceMode::OrLastTwo),
+            quirks: Some(Chip8Quirks::cosmac()),
+            rom_dirs: vec![std::path::PathBuf::from("/roms")],
+            ..Default::default()
+        };
+        config
+            .hotkeys
+            .insert(String::from("pause"), String::from("Space"));
+        config.roms.entry(String::from("abc")).or_default().quirks = Some(Chip8Quirks {
+            jump_uses_v0: false,
+            ..Chip8Quirks::default()
+        });
+        config.set_mapping(Some("abc"), &KeyMapping::cosmac());
+        assert!(config.has_rom_mapping("abc"));
+        assert!(!config.has_rom_mapping("def"));
+
+        let path = std::env::temp_dir().join("chiprust8_test_config.toml");
+        config.save(&path).unwrap();
+        let text: String = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("quirks = \"cosmac\""));
+        assert!(text.contains("persistence = \"or\""));
+        assert_eq!(Chip8Config::load(&path).unwrap(), config);
+
+        std::fs::write(&path, "fg = \"green\"").unwrap();
+        assert!(Chip8Config::load(&path).is_err());
+        std::fs::write(&path, "[roms.abc]\nkeys = { 1 = \"NoSuchKey\" }").unwrap();
+        assert!(Chip8Config::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_debug_repl() {
+        use crate::core::Chip8Core;
+        use crate::graphics::debug_repl::Chip8DebugRepl;
+        // LD V0, 1; ADD V0, 1; ADD V0, 1; JP 0x202
+        let path = std::env::temp_dir().join("chiprust8_test_repl.ch8");
+        std::fs::write(&path, [0x60, 0x01, 0x70, 0x01, 0x70, 0x01, 0x12, 0x02]).unwrap();
+        let mut core = Chip8Core::new(path.to_str().unwrap(), true, &GraphicsAdapter::new());
+        std::fs::remove_file(&path).unwrap();
+        let mut repl = Chip8DebugRepl::default();
+        let mut run = |line: &str| repl.execute(&mut core, line).unwrap().unwrap();
+
+        assert!(run("step").starts_with("0x202: 7001  ADD V0, 0x01"));
+        // An empty line repeats the last command
+        assert!(run("").starts_with("0x204:"));
+        assert_eq!(run("b 206"), "Breakpoint at 0x206");
+        assert!(run("c").starts_with("Breakpoint\n0x206: 1202"));
+        assert!(run("c").starts_with("Breakpoint\n0x206:"));
+        assert!(run("regs").starts_with("V0:05 V1:00"));
+        assert_eq!(run("mem 200 4"), "0x200: 60 01 70 01");
+        assert_eq!(
+            run("list 204 2"),
+            " 0x204: 7001  ADD V0, 0x01\n*0x206: 1202  JP 0x202"
+        );
+        assert_eq!(run("d"), "Deleted all breakpoints");
+        assert!(run("c 3").starts_with("No breakpoint after 3 instructions"));
+        assert!(run("reset").starts_with("0x200:"));
+        assert!(repl.execute(&mut core, "bogus").is_err());
+        assert_eq!(repl.execute(&mut core, "q"), Ok(None));
+    }
+}
@@ -0,0 +1,383 @@
+use crate::{
+    core::{keypad::Chip8KeyEvent, Chip8DisplayData},
+    graphics::control_adapter::Chip8Command,
+    graphics::frontend::{Chip8Frontend, Chip8FrontendDriver},
+    graphics::hotkeys::{Chip8HotkeyAction, Chip8Hotkeys},
+    graphics::key_mapping::{key_from_name, key_name, KeyMapping},
+    graphics::phosphor::{Chip8PersistenceMode, Chip8Phosphor},
+    graphics::renderer::Chip8DisplayRenderer,
+};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::Print,
+    terminal::{self, ClearType},
+};
+use eframe::egui::{Key, Modifiers};
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+/// Terminals only report key presses (and auto-repeats), never releases, so
+/// a key counts as held until this long after its last press...
+const INITIAL_HOLD: Duration = Duration::from_millis(300);
+/// ...or this long after its last auto-repeat, once repeats start arriving.
+const REPEAT_HOLD: Duration = Duration::from_millis(100);
+const STATUS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Renders `data` two pixel rows per line with Unicode half blocks, so
+/// 64x32 fits in 64x16 character cells.
+pub fn half_block_rows(data: &Chip8DisplayData) -> Vec<String> {
+    (0..data.height())
+        .step_by(2)
+        .map(|y| {
+            (0..data.width())
+                .map(|x| {
+                    let top: bool = data.pixel(x, y);
+                    let bottom: bool = y + 1 < data.height() && data.pixel(x, y + 1);
+                    match (top, bottom) {
+                        (false, false) => ' ',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (true, true) => '█',
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The terminal key as the egui key of the same name, so the TUI can share
+/// key mapping and hotkey settings with the window.
+fn egui_key(code: KeyCode) -> Option<Key> {
+    match code {
+        KeyCode::Char(' ') => Some(Key::Space),
+        KeyCode::Char(c) if c.is_ascii_digit() => key_from_name(&format!("Num{}", c)),
+        KeyCode::Char(c) => key_from_name(&c.to_ascii_uppercase().to_string()),
+        KeyCode::Tab => Some(Key::Tab),
+        KeyCode::Enter => Some(Key::Enter),
+        KeyCode::Backspace => Some(Key::Backspace),
+        KeyCode::Esc => Some(Key::Escape),
+        KeyCode::Up => Some(Key::ArrowUp),
+        KeyCode::Down => Some(Key::ArrowDown),
+        KeyCode::Left => Some(Key::ArrowLeft),
+        KeyCode::Right => Some(Key::ArrowRight),
+        KeyCode::Home => Some(Key::Home),
+        KeyCode::End => Some(Key::End),
+        KeyCode::PageUp => Some(Key::PageUp),
+        KeyCode::PageDown => Some(Key::PageDown),
+        KeyCode::Insert => Some(Key::Insert),
+        KeyCode::Delete => Some(Key::Delete),
+        _ => None,
+    }
+}
+
+/// A terminal key event as a key and modifiers to match hotkeys and the key
+/// mapping against. Terminals can't report every combination the window can;
+/// `Chip8Hotkeys::terminal` has defaults they can all send.
+pub(crate) fn terminal_key(event: &KeyEvent) -> Option<(Key, Modifiers)> {
+    let key: Key = egui_key(event.code)?;
+    let modifiers = Modifiers {
+        ctrl: event.modifiers.contains(KeyModifiers::CONTROL),
+        shift: event.modifiers.contains(KeyModifiers::SHIFT),
+        alt: event.modifiers.contains(KeyModifiers::ALT),
+        ..Default::default()
+    };
+    Some((key, modifiers))
+}
+
+#[derive(Clone, Copy)]
+struct Chip8HeldKey {
+    last_seen: Instant,
+    repeating: bool,
+}
+
+/// Runs the emulator in a terminal, e.g. over SSH.
+pub struct Chip8TuiApp {
+    fname: String,
+    out: Stdout,
+    display_data: Chip8DisplayData,
+    redraw: bool,
+    key_mapping: KeyMapping,
+    hotkeys: Chip8Hotkeys,
+    key_hints: Vec<(String, u8)>,
+    held: [Option<Chip8HeldKey>; 16],
+    pending_keys: Vec<Chip8KeyEvent>,
+    pending_commands: Vec<Chip8Command>,
+    renderer: Chip8DisplayRenderer,
+    tone: bool,
+    /// The core's pause state, as last reported by the frontend driver.
+    paused: bool,
+    fast_forward: bool,
+    show_help: bool,
+    quit: bool,
+    status: String,
+    vblanks: u32,
+    speed: f32,
+    last_status: Instant,
+}
+
+impl Chip8TuiApp {
+    pub fn new(fname: &str) -> Chip8TuiApp {
+        Chip8TuiApp {
+            fname: String::from(fname),
+            out: io::stdout(),
+            display_data: Chip8DisplayData::default(),
+            redraw: true,
+            key_mapping: KeyMapping::default(),
+            hotkeys: Chip8Hotkeys::terminal(),
+            key_hints: Vec::new(),
+            held: [None; 16],
+            pending_keys: Vec::new(),
+            pending_commands: Vec::new(),
+            renderer: Chip8DisplayRenderer::default(),
+            tone: false,
+            paused: false,
+            fast_forward: false,
+            show_help: false,
+            quit: false,
+            status: String::new(),
+            vblanks: 0,
+            speed: 0.0,
+            last_status: Instant::now(),
+        }
+    }
+
+    pub fn set_key_mapping(&mut self, mapping: KeyMapping) {
+        self.key_mapping = mapping;
+    }
+
+    pub fn set_hotkeys(&mut self, hotkeys: Chip8Hotkeys) {
+        self.hotkeys = hotkeys;
+    }
+
+    /// Replaces the file name in the status line, e.g. with the ROM's title.
+    pub fn set_title(&mut self, title: &str) {
+        self.fname = String::from(title);
+    }
+
+    /// What the ROM's keypad keys do, listed after the hotkeys in the help view.
+    pub fn set_key_hints(&mut self, hints: Vec<(String, u8)>) {
+        self.key_hints = hints;
+    }
+
+    /// Takes over the terminal until the quit hotkey is pressed.
+    pub fn run(&mut self, driver: &mut Chip8FrontendDriver) -> io::Result<()> {
+        terminal::enable_raw_mode()?;
+        execute!(
+            self.out,
+            terminal::EnterAlternateScreen,
+            cursor::Hide,
+            terminal::Clear(ClearType::All)
+        )?;
+        let result: io::Result<()> = self.main_loop(driver);
+        execute!(self.out, cursor::Show, terminal::LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()?;
+        result
+    }
+
+    fn main_loop(&mut self, driver: &mut Chip8FrontendDriver) -> io::Result<()> {
+        while !self.quit {
+            if driver.wait_for_vblank(Duration::from_millis(16)) {
+                self.vblanks += 1;
+            }
+            while event::poll(Duration::from_secs(0))? {
+                if let Event::Key(key) = event::read()? {
+                    self.handle_key(key);
+                }
+            }
+            self.release_stale_keys();
+            driver.pump(self);
+
+            let elapsed: Duration = self.last_status.elapsed();
+            if elapsed >= STATUS_INTERVAL {
+                self.speed = self.vblanks as f32 / elapsed.as_secs_f32() / 60.0;
+                self.vblanks = 0;
+                self.last_status = Instant::now();
+                self.redraw = true;
+            }
+            if self.redraw {
+                self.draw()?;
+                self.redraw = false;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_key(&mut self, event: KeyEvent) {
+        let (key, modifiers): (Key, Modifiers) = match terminal_key(&event) {
+            Some(k) => k,
+            None => return,
+        };
+        if let Some(action) = self.hotkeys.action_for(key, &modifiers) {
+            self.run_hotkey(action);
+            return;
+        }
+        if modifiers.ctrl || modifiers.alt {
+            return;
+        }
+        let chip8_key: usize = match self.key_mapping.keys().iter().position(|k| *k == key) {
+            Some(i) => i,
+            None => return,
+        };
+        let now: Instant = Instant::now();
+        self.held[chip8_key] = match self.held[chip8_key] {
+            Some(_) => Some(Chip8HeldKey {
+                last_seen: now,
+                repeating: true,
+            }),
+            None => {
+                self.pending_keys
+                    .push(Chip8KeyEvent::new(chip8_key as u8, true));
+                Some(Chip8HeldKey {
+                    last_seen: now,
+                    repeating: false,
+                })
+            }
+        };
+    }
+
+    fn release_stale_keys(&mut self) {
+        for (k, held) in self.held.iter_mut().enumerate() {
+            if let Some(h) = held {
+                let hold: Duration = match h.repeating {
+                    true => REPEAT_HOLD,
+                    false => INITIAL_HOLD,
+                };
+                if h.last_seen.elapsed() > hold {
+                    *held = None;
+                    self.pending_keys.push(Chip8KeyEvent::new(k as u8, false));
+                }
+            }
+        }
+    }
+
+    fn run_hotkey(&mut self, action: Chip8HotkeyAction) {
+        match action {
+            Chip8HotkeyAction::Pause => {
+                self.pending_commands.push(match self.paused {
+                    true => Chip8Command::Resume,
+                    false => Chip8Command::Pause,
+                });
+            }
+            Chip8HotkeyAction::Reset => self.pending_commands.push(Chip8Command::Reset),
+            Chip8HotkeyAction::FrameAdvance => {
+                if !self.paused {
+                    self.pending_commands.push(Chip8Command::Pause);
+                }
+                self.pending_commands.push(Chip8Command::FrameAdvance);
+            }
+            // No release events to end a hold, so it toggles here.
+            Chip8HotkeyAction::FastForward => {
+                self.fast_forward = !self.fast_forward;
+                self.pending_commands
+                    .push(Chip8Command::FastForward(self.fast_forward));
+            }
+            Chip8HotkeyAction::SaveState(slot) => {
+                self.pending_commands.push(Chip8Command::SaveState(slot));
+                self.status = format!("Saved state {}", slot + 1);
+            }
+            Chip8HotkeyAction::LoadState(slot) => {
+                self.pending_commands.push(Chip8Command::LoadState(slot));
+                self.status = format!("Loaded state {}", slot + 1);
+            }
+            Chip8HotkeyAction::Screenshot => {
+                let mut phosphor = Chip8Phosphor::new(Chip8PersistenceMode::Off, 0.0);
+                let frame = phosphor.process(&self.display_data, true, 1);
+                self.status = match self.renderer.save_screenshot(frame) {
+                    Ok(path) => format!("Wrote {}", path),
+                    Err(e) => format!("Failed to write screenshot: {}", e),
+                };
+            }
+            Chip8HotkeyAction::Quit => self.quit = true,
+            Chip8HotkeyAction::Help => {
+                self.show_help = !self.show_help;
+                queue!(self.out, terminal::Clear(ClearType::All)).ok();
+            }
+        }
+        self.redraw = true;
+    }
+
+    fn draw(&mut self) -> io::Result<()> {
+        let mut lines: Vec<String> = match self.show_help {
+            true => self
+                .hotkeys
+                .bindings()
+                .iter()
+                .map(|(action, hotkey)| match hotkey {
+                    Some(h) => format!("{:<24}{}", action.description(), h),
+                    None => format!("{:<24}unbound", action.description()),
+                })
+                .chain(self.key_hints.iter().map(|(action, chip8_key)| {
+                    format!(
+                        "{:<24}{:X} ({})",
+                        action,
+                        chip8_key,
+                        key_name(self.key_mapping.key(*chip8_key))
+                    )
+                }))
+                .collect(),
+            false => half_block_rows(&self.display_data),
+        };
+
+        let mut status: String = format!("{} | {:3.0}%", self.fname, self.speed * 100.0);
+        if self.paused {
+            status.push_str(" | Paused");
+        }
+        if self.fast_forward {
+            status.push_str(" | >>");
+        }
+        if self.tone {
+            status.push_str(" | ♪");
+        }
+        if let Some(h) = self.hotkeys.binding(Chip8HotkeyAction::Help) {
+            status.push_str(&format!(" | {} for help", h));
+        }
+        if !self.status.is_empty() {
+            status.push_str(&format!(" | {}", self.status));
+        }
+        lines.push(status);
+
+        for (row, line) in lines.iter().enumerate() {
+            queue!(
+                self.out,
+                cursor::MoveTo(0, row as u16),
+                Print(line),
+                terminal::Clear(ClearType::UntilNewLine)
+            )?;
+        }
+        self.out.flush()
+    }
+}
+
+impl Chip8Frontend for Chip8TuiApp {
+    fn present_frame(&mut self, frame: &Chip8DisplayData) {
+        self.display_data = *frame;
+        self.redraw = true;
+    }
+
+    fn poll_input(&mut self) -> Vec<Chip8KeyEvent> {
+        std::mem::take(&mut self.pending_keys)
+    }
+
+    fn play_tone(&mut self) {
+        // The terminal bell is the closest thing to a buzzer available.
+        queue!(self.out, Print('\x07')).ok();
+        self.tone = true;
+        self.redraw = true;
+    }
+
+    fn stop_tone(&mut self) {
+        self.tone = false;
+        self.redraw = true;
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.redraw = true;
+    }
+
+    fn poll_control(&mut self) -> Vec<Chip8Command> {
+        std::mem::take(&mut self.pending_commands)
+    }
+}
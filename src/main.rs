@@ -1,38 +1,771 @@
-use chiprust8::{core::Chip8Core, graphics};
-use clap::Parser;
+use chiprust8::{
+    config::{Chip8Config, Chip8Overrides, Chip8RomSettings},
+    core::{
+        analysis::Chip8Analysis,
+        asm::assemble,
+        bench::{run_bench, Chip8BenchMix, Chip8BenchReport},
+        blocks::Chip8Engine,
+        quirks::Chip8Quirks,
+        rom_db::{sha1_hex, Chip8RomDatabase, Chip8RomInfo},
+        trace::{parse_addr, Chip8TraceFormat, Chip8TraceRange, Chip8TraceTemplate, Chip8Tracer},
+        vectors::{generate_vectors, vectors_to_json},
+        Chip8Core, MAX_ROM_SIZE, PROGRAM_OFFSET,
+    },
+    graphics::control_adapter::Chip8Command,
+    graphics::{
+        self, debug_repl::Chip8DebugRepl, graphics_adapter::GraphicsAdapter, hotkeys::Chip8Hotkeys,
+        renderer::format_color, rom_browser::Chip8RecentRoms,
+    },
+};
+use clap::{ArgEnum, Args, Parser, Subcommand};
+use eframe::egui::Color32;
+use log::{info, warn};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[clap(about, version, author)]
 struct Chip8LauncherArgs {
-    #[clap(short, long, default_value_t=String::from("testfile"))]
-    fname: String,
+    /// Log debug output
+    #[clap(short, long, global = true)]
+    verbose: bool,
+    #[clap(subcommand)]
+    command: Chip8Subcommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum Chip8Subcommand {
+    /// Play a ROM in a window, or in the terminal with --tui
+    Run(RunArgs),
+    /// Run a ROM flat out with no display, e.g. to profile it or capture its screen
+    Headless(HeadlessArgs),
+    /// Disassemble a ROM
+    Disasm(DisasmArgs),
+    /// Assemble a source file into a ROM
+    Asm(AsmArgs),
+    /// Run a ROM with no display, writing a line per executed instruction
+    Trace(TraceArgs),
+    /// Show what's known about a ROM and the settings it would run with
+    Info(InfoArgs),
+    /// Step through a ROM at a debugger prompt
+    Debug(DebugArgs),
+    /// Run a ROM flat out and report how fast the core goes
+    Bench(BenchArgs),
+    /// Write single-step test vectors for every opcode, one JSON file each
+    Vectors(VectorsArgs),
+}
+
+/// Flags shared by every subcommand that runs a ROM.
+#[derive(Args, Debug)]
+struct CoreArgs {
+    /// Interpreter quirks to emulate: default, cosmac or modern (default: from the ROM database)
+    #[clap(long)]
+    quirks: Option<Chip8Quirks>,
+    /// Instructions per frame (default: from the ROM database, or 11)
+    #[clap(long)]
+    speed: Option<u32>,
+    /// Seed for CXNN's random numbers, so runs can be repeated exactly
+    #[clap(long)]
+    seed: Option<u64>,
+    /// How to run instructions: interpreter, or compiled into blocks of closures
+    #[clap(long, default_value_t)]
+    engine: Chip8Engine,
+    /// Settings file (default: ~/.config/chiprust8/config.toml)
+    #[clap(long)]
+    config: Option<PathBuf>,
+    /// Extra ROM database, in the community programs.json format
+    /// (default: ~/.config/chiprust8/programs.json)
+    #[clap(long)]
+    rom_db: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ProfileArgs {
+    /// Profile the ROM and write a hot spot report to this file when the core stops
+    #[clap(long)]
+    profile: Option<PathBuf>,
+    /// Also write the profile as folded stacks, for flamegraph generation
+    #[clap(long)]
+    folded: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
+    /// ROM file
+    rom: PathBuf,
+    #[clap(flatten)]
+    core: CoreArgs,
+    /// Run in the terminal instead of a window
+    #[clap(long)]
+    tui: bool,
+    /// Reload the ROM whenever the file changes
+    #[clap(long)]
+    watch: bool,
+    /// Directory listed in the ROM browser (default: the ROM's directory)
+    #[clap(long)]
+    rom_dir: Option<PathBuf>,
+    /// Colour of lit pixels, as #RRGGBB
+    #[clap(long, parse(try_from_str = graphics::renderer::parse_color))]
+    fg: Option<Color32>,
+    /// Colour of unlit pixels, as #RRGGBB
+    #[clap(long, parse(try_from_str = graphics::renderer::parse_color))]
+    bg: Option<Color32>,
+    /// Anti-flicker post-processing: off, decay or or (OR the last two frames)
+    #[clap(long)]
+    persistence: Option<graphics::phosphor::Chip8PersistenceMode>,
+    /// Fraction of brightness a pixel keeps per frame with --persistence decay
+    #[clap(long)]
+    decay: Option<f32>,
+    #[clap(flatten)]
+    profile: ProfileArgs,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ScreenFormat {
+    Text,
+    Ppm,
+}
+
+#[derive(Args, Debug)]
+struct HeadlessArgs {
+    /// ROM file
+    rom: PathBuf,
+    #[clap(flatten)]
+    core: CoreArgs,
+    /// Stop after this many instructions
+    #[clap(long, default_value_t = 1_000_000)]
+    cycles: u64,
+    /// Write the display as it is when the core stops to this file, - for stdout
+    #[clap(long)]
+    screen: Option<PathBuf>,
+    #[clap(long, arg_enum, default_value = "text")]
+    screen_format: ScreenFormat,
+    #[clap(flatten)]
+    profile: ProfileArgs,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DisasmFormat {
+    /// Assembly that `asm` reads back
+    Asm,
+    /// Control-flow graph for Graphviz
+    Dot,
+}
+
+#[derive(Args, Debug)]
+struct DisasmArgs {
+    /// ROM file
+    rom: PathBuf,
+    /// Output file (default: stdout)
     #[clap(short, long)]
-    no_eframe: bool,
+    output: Option<PathBuf>,
+    #[clap(long, arg_enum, default_value = "asm")]
+    format: DisasmFormat,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum AsmFormat {
+    /// A ROM image
+    Rom,
+    /// Opcodes as hex text, eight words to a line
+    Hex,
+}
+
+#[derive(Args, Debug)]
+struct AsmArgs {
+    /// Source file
+    source: PathBuf,
+    /// Output file (default: the source with a .ch8 or .hex extension)
     #[clap(short, long)]
-    verbose: bool,
+    output: Option<PathBuf>,
+    #[clap(long, arg_enum, default_value = "rom")]
+    format: AsmFormat,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum TraceFormat {
+    Text,
+    Json,
+}
+
+#[derive(Args, Debug)]
+struct TraceArgs {
+    /// ROM file
+    rom: PathBuf,
+    #[clap(flatten)]
+    core: CoreArgs,
+    /// Stop after this many instructions
+    #[clap(long, default_value_t = 100_000)]
+    cycles: u64,
+    /// Only trace instructions whose address is in this range, e.g. 0x200-0x2FF
+    #[clap(long)]
+    range: Option<Chip8TraceRange>,
+    /// Output file (default: stdout)
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    #[clap(long, arg_enum, default_value = "text")]
+    format: TraceFormat,
+    /// Lay lines out like another emulator's trace instead, e.g. "{pc:x} {opcode:x} {v}"
+    #[clap(long, conflicts_with = "format")]
+    template: Option<Chip8TraceTemplate>,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ReportFormat {
+    Text,
+    Json,
+}
+
+#[derive(Args, Debug)]
+struct InfoArgs {
+    /// ROM file
+    rom: PathBuf,
+    #[clap(flatten)]
+    core: CoreArgs,
+    #[clap(long, arg_enum, default_value = "text")]
+    format: ReportFormat,
+}
+
+#[derive(Args, Debug)]
+struct DebugArgs {
+    /// ROM file
+    rom: PathBuf,
+    #[clap(flatten)]
+    core: CoreArgs,
+    /// Set a breakpoint at this address; can be repeated
+    #[clap(short, long = "break", parse(try_from_str = parse_addr))]
+    breakpoints: Vec<u16>,
+}
+
+#[derive(Args, Debug)]
+struct BenchArgs {
+    /// ROM file (default: the synthetic instruction mixes)
+    rom: Option<PathBuf>,
+    /// Only run this synthetic mix: alu, draw, memory, calls or game
+    #[clap(long, conflicts_with = "rom")]
+    mix: Option<Chip8BenchMix>,
+    #[clap(flatten)]
+    core: CoreArgs,
+    /// Instructions to run for each ROM or mix
+    #[clap(long, default_value_t = 10_000_000)]
+    cycles: u64,
+    #[clap(long, arg_enum, default_value = "text")]
+    format: ReportFormat,
+}
+
+#[derive(Args, Debug)]
+struct VectorsArgs {
+    /// Directory to write the files to
+    #[clap(short, long, default_value = "tests/vectors")]
+    output: PathBuf,
+    /// Seed for the random states
+    #[clap(long, default_value_t = 0)]
+    seed: u64,
+    /// Vectors for each opcode under each combination of quirks
+    #[clap(long, default_value_t = 2)]
+    count: usize,
+}
+
+/// A core loaded with a ROM and the settings it runs with.
+struct Chip8Session {
+    core: Chip8Core,
+    adapter: GraphicsAdapter,
+    config: Chip8Config,
+    config_path: Option<PathBuf>,
+    rom_db: Chip8RomDatabase,
+    rom: Vec<u8>,
+    rom_hash: String,
+    rom_info: Option<Chip8RomInfo>,
+    overrides: Chip8Overrides,
+    settings: Chip8RomSettings,
+}
+
+impl CoreArgs {
+    /// Loads the ROM at `path`, layering the settings file, ROM database and
+    /// flags.
+    fn load(&self, path: &Path) -> Chip8Session {
+        let rom: Vec<u8> =
+            fs::read(path).unwrap_or_else(|e| fail(format!("{}: {}", path.display(), e)));
+        info!("Loaded {}", path.display());
+        self.load_rom(rom)
+    }
+
+    fn load_rom(&self, rom: Vec<u8>) -> Chip8Session {
+        let adapter = GraphicsAdapter::default();
+        let mut core: Chip8Core = Chip8Core::from_rom(rom, true, &adapter);
+
+        let config_path: Option<PathBuf> = self.config.clone().or_else(Chip8Config::default_path);
+        let config: Chip8Config = match &config_path {
+            Some(path) => Chip8Config::load(path).unwrap_or_else(|e| {
+                warn!("{}; using the default settings", e);
+                Chip8Config::default()
+            }),
+            None => Chip8Config::default(),
+        };
+        let rom: Vec<u8> = core.rom().to_vec();
+        let rom_hash: String = sha1_hex(&rom);
+        let rom_db: Chip8RomDatabase = load_rom_database(self.rom_db.clone());
+        let rom_info: Option<Chip8RomInfo> = rom_db.lookup(&rom).cloned();
+        if let Some(info) = &rom_info {
+            info!("Recognised {}", info.title);
+            for warning in info.warnings.iter() {
+                warn!("{} may not run properly: {}", info.title, warning);
+            }
+        }
+        let overrides = Chip8Overrides {
+            quirks: self.quirks,
+            speed: self.speed,
+            fg: None,
+            bg: None,
+        };
+        let settings: Chip8RomSettings =
+            overrides.apply(config.settings_for(Some(&rom_hash), rom_info.as_ref()));
+        core.set_quirks(settings.quirks);
+        core.set_instrs_per_frame(settings.speed);
+        if let Some(seed) = self.seed {
+            core.set_seed(seed);
+        }
+        core.set_engine(self.engine);
+        Chip8Session {
+            core,
+            adapter,
+            config,
+            config_path,
+            rom_db,
+            rom,
+            rom_hash,
+            rom_info,
+            overrides,
+            settings,
+        }
+    }
 }
 
 fn main() {
     let args = Chip8LauncherArgs::parse();
-    let log_level: log::LevelFilter = match args.verbose {
-        true => log::LevelFilter::Debug,
-        false => log::LevelFilter::Info,
+    let log_level: log::LevelFilter = match &args.command {
+        _ if args.verbose => log::LevelFilter::Debug,
+        // Log lines would be drawn over the display
+        Chip8Subcommand::Run(run) if run.tui => log::LevelFilter::Off,
+        Chip8Subcommand::Run(_) | Chip8Subcommand::Headless(_) => log::LevelFilter::Info,
+        // Logging would be measured along with the core
+        Chip8Subcommand::Bench(_) => log::LevelFilter::Off,
+        // The rest have output of their own to keep clean
+        _ => log::LevelFilter::Warn,
     };
     let _ = env_logger::builder()
         .filter_level(log_level)
         .is_test(true)
         .try_init();
 
-    let adapter = graphics::graphics_adapter::GraphicsAdapter::default();
-    let mut core: Chip8Core = Chip8Core::new(&args.fname, true, &adapter);
+    match args.command {
+        Chip8Subcommand::Run(args) => run(args),
+        Chip8Subcommand::Headless(args) => headless(args),
+        Chip8Subcommand::Disasm(args) => disasm(args),
+        Chip8Subcommand::Asm(args) => asm(args),
+        Chip8Subcommand::Trace(args) => trace(args),
+        Chip8Subcommand::Info(args) => rom_info(args),
+        Chip8Subcommand::Debug(args) => debug(args),
+        Chip8Subcommand::Bench(args) => bench(args),
+        Chip8Subcommand::Vectors(args) => vectors(args),
+    }
+}
+
+fn fail(msg: String) -> ! {
+    eprintln!("Error: {}", msg);
+    std::process::exit(1);
+}
+
+/// Opens `path` for writing, or stdout if there's no path or it's `-`.
+fn output(path: Option<&Path>) -> Box<dyn Write + Send> {
+    match path {
+        Some(p) if p != Path::new("-") => match fs::File::create(p) {
+            Ok(f) => Box::new(BufWriter::new(f)),
+            Err(e) => fail(format!("Failed to create {}: {}", p.display(), e)),
+        },
+        _ => Box::new(io::stdout()),
+    }
+}
+
+fn run(args: RunArgs) {
+    let session: Chip8Session = args.core.load(&args.rom);
+    let Chip8Session {
+        mut core,
+        adapter,
+        config,
+        config_path,
+        rom_db,
+        rom,
+        rom_hash,
+        rom_info,
+        mut overrides,
+        ..
+    } = session;
+    overrides.fg = args.fg;
+    overrides.bg = args.bg;
 
-    std::thread::spawn(move || {
+    let debug_adapter = graphics::debug_adapter::DebugAdapter::default();
+    let control_adapter = graphics::control_adapter::ControlAdapter::default();
+    core.attach_debugger(&debug_adapter);
+    core.attach_control(&control_adapter);
+    if args.profile.profile.is_some() || args.profile.folded.is_some() {
+        core.enable_profiler();
+    }
+
+    let rom_path: &Path = &args.rom;
+    let rom_watcher = match args.watch {
+        true => Some(graphics::rom_watcher::Chip8RomWatcher::new(rom_path).spawn(
+            &control_adapter,
+            graphics::rom_watcher::DEFAULT_POLL_INTERVAL,
+        )),
+        false => None,
+    };
+
+    let core_thread = std::thread::spawn(move || {
         core.run_loop();
+        core
     });
+    let profile: ProfileArgs = args.profile;
+    let control = control_adapter.clone();
+    let stop_core = move || {
+        if let Err(e) = control.send(Chip8Command::Shutdown) {
+            warn!("{}", e);
+        }
+        let core: Chip8Core = core_thread.join().expect("Core thread panicked");
+        write_profile(&core, &profile);
+    };
+
+    let rom_name: Option<String> = rom_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned());
+    let default_hotkeys = || match args.tui {
+        true => Chip8Hotkeys::terminal(),
+        false => Chip8Hotkeys::default(),
+    };
+    let mut hotkeys = default_hotkeys();
+    if let Err(e) = hotkeys.apply(&config.hotkeys) {
+        warn!("{}; using the default hotkeys", e);
+        hotkeys = default_hotkeys();
+    }
+
+    if args.tui {
+        let mut app = graphics::tui_runner::Chip8TuiApp::new(rom_name.as_deref().unwrap_or(""));
+        if let Some(info) = &rom_info {
+            app.set_title(&info.title);
+            app.set_key_hints(info.keys.clone());
+        }
+        match config.mapping_for(Some(&rom_hash)) {
+            Ok(mapping) => app.set_key_mapping(mapping),
+            Err(e) => warn!("{}; using the default key mapping", e),
+        }
+        app.set_hotkeys(hotkeys);
+        let mut driver = graphics::frontend::Chip8FrontendDriver::new(&adapter);
+        driver.attach_control(&control_adapter);
+        driver.set_muted(!config.audio.enabled);
+        let result = app.run(&mut driver);
+        stop_core();
+        result.expect("Terminal error");
+        return;
+    }
+
+    let mut app = graphics::eframe_runner::Chip8EframeApp::new(&adapter);
+    app.attach_debugger(&debug_adapter);
+    app.attach_control(&control_adapter);
+    let rom_dirs: Vec<PathBuf> = match args.rom_dir {
+        Some(dir) => vec![dir],
+        None if !config.rom_dirs.is_empty() => config.rom_dirs.clone(),
+        None => fs::canonicalize(rom_path)
+            .ok()
+            .and_then(|full| full.parent().map(Path::to_path_buf))
+            .into_iter()
+            .collect(),
+    };
+    let scale: usize = config.scale.unwrap_or(graphics::renderer::DEFAULT_SCALE);
+    let persistence = args
+        .persistence
+        .or(config.persistence)
+        .unwrap_or(graphics::phosphor::Chip8PersistenceMode::Off);
+    let decay: f32 = args
+        .decay
+        .or(config.decay)
+        .unwrap_or(graphics::phosphor::DEFAULT_DECAY);
+    app.set_config(config, config_path);
+    app.set_persistence(persistence, decay);
+    app.set_overrides(overrides);
+    app.set_rom_database(rom_db);
+    app.set_rom(rom_path, &rom);
+    app.set_rom_browser(rom_dirs, Chip8RecentRoms::default_path(), rom_path);
+    if let Some(watcher) = rom_watcher {
+        app.set_rom_watcher(watcher);
+    }
+    app.set_hotkeys(hotkeys);
+    app.set_on_exit(Box::new(stop_core));
+    // Room for the menu bar above the display
+    let native_options = eframe::NativeOptions {
+        initial_window_size: Some(eframe::egui::vec2(
+            (64 * scale) as f32,
+            (32 * scale) as f32 + 40.0,
+        )),
+        ..Default::default()
+    };
+    eframe::run_native(Box::new(app), native_options);
+}
+
+fn headless(args: HeadlessArgs) {
+    let mut core: Chip8Core = args.core.load(&args.rom).core;
+    if args.profile.profile.is_some() || args.profile.folded.is_some() {
+        core.enable_profiler();
+    }
+    // Nobody's watching, so don't hold the core to 60 frames a second.
+    core.set_fast_forward(true);
+    core.set_cycle_limit(args.cycles);
+    core.run_loop();
+    write_profile(&core, &args.profile);
+
+    if let Some(path) = &args.screen {
+        let mut out = output(Some(path));
+        let result = match args.screen_format {
+            ScreenFormat::Text => write!(out, "{}", core.display()),
+            ScreenFormat::Ppm => {
+                let mut phosphor = graphics::phosphor::Chip8Phosphor::new(
+                    graphics::phosphor::Chip8PersistenceMode::Off,
+                    graphics::phosphor::DEFAULT_DECAY,
+                );
+                graphics::renderer::Chip8DisplayRenderer::default().write_ppm(
+                    phosphor.process(core.display(), true, 1),
+                    graphics::renderer::DEFAULT_SCALE,
+                    &mut out,
+                )
+            }
+        };
+        if let Err(e) = result.and_then(|_| out.flush()) {
+            fail(format!("Failed to write the screen: {}", e));
+        }
+    }
+}
+
+fn disasm(args: DisasmArgs) {
+    let rom: Vec<u8> =
+        fs::read(&args.rom).unwrap_or_else(|e| fail(format!("{}: {}", args.rom.display(), e)));
+    let analysis = Chip8Analysis::analyze(&rom);
+    let text: String = match args.format {
+        DisasmFormat::Asm => analysis.disassemble(),
+        DisasmFormat::Dot => analysis.to_dot(),
+    };
+    let mut out = output(args.output.as_deref());
+    if let Err(e) = out.write_all(text.as_bytes()).and_then(|_| out.flush()) {
+        fail(format!("Failed to write the disassembly: {}", e));
+    }
+}
+
+fn asm(args: AsmArgs) {
+    let source: String = fs::read_to_string(&args.source)
+        .unwrap_or_else(|e| fail(format!("{}: {}", args.source.display(), e)));
+    let rom: Vec<u8> =
+        assemble(&source).unwrap_or_else(|e| fail(format!("{}: {}", args.source.display(), e)));
+    let (bytes, extension): (Vec<u8>, &str) = match args.format {
+        AsmFormat::Rom => (rom, "ch8"),
+        AsmFormat::Hex => {
+            let words: Vec<String> = rom
+                .chunks(2)
+                .map(|w| w.iter().map(|b| format!("{:02X}", b)).collect())
+                .collect();
+            let lines: Vec<String> = words.chunks(8).map(|l| l.join(" ")).collect();
+            ((lines.join("\n") + "\n").into_bytes(), "hex")
+        }
+    };
+    let path: PathBuf = args
+        .output
+        .unwrap_or_else(|| args.source.with_extension(extension));
+    let mut out = output(Some(&path));
+    if let Err(e) = out.write_all(&bytes).and_then(|_| out.flush()) {
+        fail(format!("Failed to write {}: {}", path.display(), e));
+    }
+}
+
+fn trace(args: TraceArgs) {
+    let mut core: Chip8Core = args.core.load(&args.rom).core;
+    let format: Chip8TraceFormat = match (args.template, args.format) {
+        (Some(template), _) => Chip8TraceFormat::Template(template),
+        (None, TraceFormat::Text) => Chip8TraceFormat::Text,
+        (None, TraceFormat::Json) => Chip8TraceFormat::Json,
+    };
+    let mut tracer = Chip8Tracer::new(output(args.output.as_deref())).with_format(format);
+    if let Some(range) = args.range {
+        tracer = tracer.with_range(range);
+    }
+    core.set_tracer(tracer);
+    core.set_fast_forward(true);
+    core.set_cycle_limit(args.cycles);
+    core.run_loop();
+}
+
+fn rom_info(args: InfoArgs) {
+    let session: Chip8Session = args.core.load(&args.rom);
+    let analysis = Chip8Analysis::analyze(&session.rom);
+    let end: u16 = PROGRAM_OFFSET + session.rom.len().min(MAX_ROM_SIZE) as u16;
+    let code_bytes: usize = (PROGRAM_OFFSET..end)
+        .filter(|a| analysis.kind(*a) == chiprust8::core::analysis::Chip8ByteKind::Code)
+        .count();
+    let settings: Chip8RomSettings = session.settings;
+    let info: Option<&Chip8RomInfo> = session.rom_info.as_ref();
+    let quirks: String = settings
+        .quirks
+        .preset_name()
+        .map(String::from)
+        .unwrap_or_else(|| format!("{:?}", settings.quirks));
+
+    match args.format {
+        ReportFormat::Json => {
+            let report = serde_json::json!({
+                "path": args.rom,
+                "size": session.rom.len(),
+                "sha1": session.rom_hash,
+                "title": info.map(|i| &i.title),
+                "authors": info.map(|i| &i.authors),
+                "platform": info.and_then(|i| i.platform.as_ref()),
+                "warnings": info.map(|i| &i.warnings),
+                "quirks": settings.quirks,
+                "speed": settings.speed,
+                "fg": format_color(settings.fg),
+                "bg": format_color(settings.bg),
+                "blocks": analysis.blocks().count(),
+                "code_bytes": code_bytes,
+                "computed_jumps": analysis.computed_jumps(),
+            });
+            println!("{:#}", report);
+        }
+        ReportFormat::Text => {
+            println!("File:      {}", args.rom.display());
+            println!("Size:      {} bytes", session.rom.len());
+            println!("SHA-1:     {}", session.rom_hash);
+            match info {
+                Some(info) => {
+                    println!("Title:     {}", info.title);
+                    if !info.authors.is_empty() {
+                        println!("Authors:   {}", info.authors.join(", "));
+                    }
+                    if let Some(platform) = &info.platform {
+                        println!("Platform:  {}", platform);
+                    }
+                    for warning in info.warnings.iter() {
+                        println!("Warning:   {}", warning);
+                    }
+                }
+                None => println!("Title:     (not in the ROM database)"),
+            }
+            println!("Quirks:    {}", quirks);
+            println!("Speed:     {} instructions per frame", settings.speed);
+            println!(
+                "Colours:   {} on {}",
+                format_color(settings.fg),
+                format_color(settings.bg)
+            );
+            println!(
+                "Code:      {} bytes in {} blocks, {} computed jumps",
+                code_bytes,
+                analysis.blocks().count(),
+                analysis.computed_jumps().len()
+            );
+        }
+    }
+}
+
+fn debug(args: DebugArgs) {
+    let mut core: Chip8Core = args.core.load(&args.rom).core;
+    let mut repl = Chip8DebugRepl::default();
+    for addr in args.breakpoints {
+        repl.set_breakpoint(addr);
+    }
+    let stdin = io::stdin();
+    let result = repl.run(&mut core, &mut stdin.lock(), &mut io::stdout());
+    if let Err(e) = result {
+        fail(format!("{}", e));
+    }
+}
+
+fn bench(args: BenchArgs) {
+    let runs: Vec<(String, Chip8Core)> = match (&args.rom, args.mix) {
+        (Some(rom), _) => vec![(rom.display().to_string(), args.core.load(rom).core)],
+        (None, Some(mix)) => vec![(mix.to_string(), args.core.load_rom(mix.rom()).core)],
+        (None, None) => Chip8BenchMix::all()
+            .into_iter()
+            .map(|mix| (mix.to_string(), args.core.load_rom(mix.rom()).core))
+            .collect(),
+    };
+    let mut results = Vec::new();
+    for (name, mut core) in runs {
+        let report: Chip8BenchReport = run_bench(&mut core, args.cycles);
+        match args.format {
+            ReportFormat::Json => results.push(serde_json::json!({
+                "name": name,
+                "instructions": report.instructions,
+                "frames": report.frames,
+                "seconds": report.elapsed.as_secs_f64(),
+                "instructions_per_second": report.instructions_per_second(),
+                "microseconds_per_frame": report.frame_time().as_secs_f64() * 1e6,
+            })),
+            ReportFormat::Text => println!(
+                "{:<10} {:>12.0} instructions/s {:>9.2} us/frame ({} instructions in {:.3} s)",
+                name,
+                report.instructions_per_second(),
+                report.frame_time().as_secs_f64() * 1e6,
+                report.instructions,
+                report.elapsed.as_secs_f64()
+            ),
+        }
+    }
+    if args.format == ReportFormat::Json {
+        println!("{:#}", serde_json::Value::Array(results));
+    }
+}
+
+/// Writes one JSON file of generated test vectors per opcode.
+fn vectors(args: VectorsArgs) {
+    if let Err(e) = fs::create_dir_all(&args.output) {
+        fail(format!("Failed to create {}: {}", args.output.display(), e));
+    }
+    let mut total: usize = 0;
+    for (name, vectors) in generate_vectors(args.seed, args.count) {
+        let path: PathBuf = args.output.join(format!("{}.json", name));
+        if let Err(e) = fs::write(&path, vectors_to_json(&vectors)) {
+            fail(format!("Failed to write {}: {}", path.display(), e));
+        }
+        total += vectors.len();
+    }
+    println!("Wrote {} vectors to {}", total, args.output.display());
+}
+
+/// The bundled ROM database, with the user's entries on top.
+fn load_rom_database(path: Option<PathBuf>) -> Chip8RomDatabase {
+    let mut db = Chip8RomDatabase::bundled();
+    if let Some(path) = path.or_else(Chip8RomDatabase::default_path) {
+        match Chip8RomDatabase::load(&path) {
+            Ok(user_db) => db.merge(user_db),
+            Err(e) => warn!("{}", e),
+        }
+    }
+    db
+}
 
-    if !args.no_eframe {
-        let app = graphics::eframe_runner::Chip8EframeApp::new(&adapter);
-        let native_options = eframe::NativeOptions::default();
-        eframe::run_native(Box::new(app), native_options);
+/// Writes out the core's profile, if it has one. Tries both outputs before
+/// failing, so one bad path doesn't lose the other.
+fn write_profile(core: &Chip8Core, args: &ProfileArgs) {
+    if let Some(profiler) = core.profiler() {
+        let mut errors: Vec<String> = Vec::new();
+        if let Some(path) = &args.profile {
+            if let Err(e) = fs::write(path, profiler.report()) {
+                errors.push(format!("Failed to write {}: {}", path.display(), e));
+            }
+        }
+        if let Some(path) = &args.folded {
+            if let Err(e) = fs::File::create(path).and_then(|mut f| profiler.write_folded(&mut f)) {
+                errors.push(format!("Failed to write {}: {}", path.display(), e));
+            }
+        }
+        if !errors.is_empty() {
+            fail(errors.join("\n"));
+        }
     }
 }
@@ -0,0 +1,184 @@
+//! Golden-framebuffer tests. Every `*.toml` script in `tests/golden/` names
+//! a ROM (`.ch8`, or `.asm` to be assembled), how many frames to run it for
+//! and what keys to press along the way. The display after the last frame
+//! must match the script's `.golden` file, on both engines.
+//!
+//! `CHIPRUST8_BLESS=1 cargo test --test golden` writes the goldens from the
+//! current core instead of checking them. Review the diff before committing.
+
+use chiprust8::core::{
+    asm::assemble, blocks::Chip8Engine, keypad::Chip8KeyEvent, quirks::Chip8Quirks, Chip8Core,
+    Chip8DisplayData,
+};
+use chiprust8::graphics::graphics_adapter::GraphicsAdapter;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const BLESS_VAR: &str = "CHIPRUST8_BLESS";
+
+/// How to run one ROM.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Chip8GoldenScript {
+    /// The ROM, relative to the script. Defaults to the script's name with
+    /// `.ch8` or `.asm`.
+    rom: Option<PathBuf>,
+    frames: u32,
+    /// Defaults to the core's own default quirks.
+    quirks: Option<Chip8Quirks>,
+    /// Instructions per frame; defaults to the core's.
+    speed: Option<u32>,
+    #[serde(default)]
+    seed: u64,
+    #[serde(default)]
+    input: Vec<Chip8GoldenInput>,
+}
+
+/// Keys pressed and released at the start of a frame.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Chip8GoldenInput {
+    frame: u32,
+    #[serde(default)]
+    press: Vec<u8>,
+    #[serde(default)]
+    release: Vec<u8>,
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+}
+
+fn load_rom(script_path: &Path, script: &Chip8GoldenScript) -> Result<Vec<u8>, String> {
+    let dir: &Path = script_path.parent().unwrap_or_else(|| Path::new("."));
+    let path: PathBuf = match &script.rom {
+        Some(rom) => dir.join(rom),
+        None => ["ch8", "asm"]
+            .iter()
+            .map(|ext| script_path.with_extension(ext))
+            .find(|p| p.exists())
+            .ok_or("No ROM next to the script, and none named in it")?,
+    };
+    let bytes: Vec<u8> = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("asm") => {
+            let source: String = String::from_utf8(bytes).map_err(|e| e.to_string())?;
+            assemble(&source).map_err(|e| format!("{}: {}", path.display(), e))
+        }
+        _ => Ok(bytes),
+    }
+}
+
+/// The display as text, `#` for lit pixels and `.` for dark.
+fn screen_text(display: &Chip8DisplayData) -> String {
+    let mut text: String = String::new();
+    for y in 0..display.height() {
+        for x in 0..display.width() {
+            text.push(if display.pixel(x, y) { '#' } else { '.' });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn run_script(rom: &[u8], script: &Chip8GoldenScript, engine: Chip8Engine) -> String {
+    let mut core: Chip8Core = Chip8Core::from_rom(rom.to_vec(), true, &GraphicsAdapter::new());
+    if let Some(quirks) = script.quirks {
+        core.set_quirks(quirks);
+    }
+    if let Some(speed) = script.speed {
+        core.set_instrs_per_frame(speed);
+    }
+    core.set_seed(script.seed);
+    core.set_engine(engine);
+    for frame in 0..script.frames {
+        for input in script.input.iter().filter(|i| i.frame == frame) {
+            for key in input.press.iter() {
+                core.queue_key(Chip8KeyEvent::new(*key, true));
+            }
+            for key in input.release.iter() {
+                core.queue_key(Chip8KeyEvent::new(*key, false));
+            }
+        }
+        core.run_frame();
+    }
+    screen_text(core.display())
+}
+
+/// Both screens next to each other, with the rows that differ marked.
+fn side_by_side(expected: &str, actual: &str) -> String {
+    let mut out: String = format!("{:<64}   {}\n", "expected", "actual");
+    for (e, a) in expected.lines().zip(actual.lines()) {
+        let mark: &str = if e == a { " " } else { "*" };
+        out.push_str(&format!("{} {} {}\n", e, mark, a));
+    }
+    out
+}
+
+/// Runs one script, returning why it failed, if it did.
+fn check(script_path: &Path, bless: bool) -> Result<(), String> {
+    let text: String = fs::read_to_string(script_path).map_err(|e| e.to_string())?;
+    let script: Chip8GoldenScript = toml::from_str(&text).map_err(|e| e.to_string())?;
+    let rom: Vec<u8> = load_rom(script_path, &script)?;
+
+    let actual: String = run_script(&rom, &script, Chip8Engine::Interpreter);
+    let compiled: String = run_script(&rom, &script, Chip8Engine::Compiled);
+    if compiled != actual {
+        return Err(format!(
+            "the engines disagree\n{}",
+            side_by_side(&actual, &compiled)
+        ));
+    }
+
+    let golden_path: PathBuf = script_path.with_extension("golden");
+    if bless {
+        return fs::write(&golden_path, &actual).map_err(|e| e.to_string());
+    }
+    let expected: String = fs::read_to_string(&golden_path).map_err(|e| {
+        format!(
+            "{}: {}; run with {}=1 to create it",
+            golden_path.display(),
+            e,
+            BLESS_VAR
+        )
+    })?;
+    match expected == actual {
+        true => Ok(()),
+        false => Err(format!(
+            "doesn't match {}\n{}",
+            golden_path.display(),
+            side_by_side(&expected, &actual)
+        )),
+    }
+}
+
+#[test]
+fn golden_framebuffers() {
+    let bless: bool = std::env::var_os(BLESS_VAR).is_some_and(|v| v != "0");
+    let mut scripts: Vec<PathBuf> = fs::read_dir(golden_dir())
+        .expect("No tests/golden directory")
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|e| e == "toml"))
+        .collect();
+    scripts.sort();
+    assert!(!scripts.is_empty(), "No scripts in tests/golden");
+
+    let failures: Vec<String> = scripts
+        .iter()
+        .filter_map(|path| {
+            check(path, bless)
+                .err()
+                .map(|e| format!("{}: {}", path.display(), e))
+        })
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "{} of {} golden tests failed:\n\n{}",
+        failures.len(),
+        scripts.len(),
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,72 @@
+; 8XYN results, each drawn as a three digit result then a one digit VF.
+; The shifts and OR depend on the quirks; the last ADD checks that VF ends
+; up holding the carry, not the sum, when it's the destination.
+        LD V6, 1
+        LD V7, 1
+        LD V3, 200
+        LD V4, 100
+        ADD V3, V4      ; 44, carry
+        CALL report
+        LD V3, 5
+        LD V4, 10
+        SUB V3, V4      ; 251, borrow
+        CALL report
+        LD V3, 10
+        LD V4, 5
+        SUBN V3, V4     ; 251, borrow
+        CALL report
+        LD V3, 7
+        LD V4, 7
+        SUB V3, V4      ; 0, no borrow
+        CALL report
+        LD V3, 0x81
+        LD V4, 0x06
+        SHR V3, V4      ; VX or VY shifted
+        CALL report
+        LD V3, 0x81
+        LD V4, 0x06
+        SHL V3, V4
+        CALL report
+        LD VF, 7
+        LD V3, 0x0C
+        LD V4, 0x03
+        OR V3, V4       ; 15, VF kept or reset
+        CALL report
+        LD V3, 255
+        LD V4, 1
+        ADD V3, V4      ; 0, carry
+        CALL report
+        LD VF, 7
+        LD V3, 3
+        ADD V3, 0xFF    ; 2, 7XNN leaves VF alone
+        CALL report
+        LD VF, 200
+        LD V4, 100
+        ADD VF, V4
+        LD V3, VF       ; 1 in both
+        CALL report
+hang:   JP hang
+
+; Draws V3 and then VF at V6, V7, moving along and down
+report: LD V8, VF
+        LD I, digits
+        LD B, V3
+        LD V2, [I]
+        LD F, V0
+        DRW V6, V7, 5
+        ADD V6, 5
+        LD F, V1
+        DRW V6, V7, 5
+        ADD V6, 5
+        LD F, V2
+        DRW V6, V7, 5
+        ADD V6, 7
+        LD F, V8
+        DRW V6, V7, 5
+        ADD V6, 9
+        SE V6, 53
+        RET
+        LD V6, 1
+        ADD V7, 6
+        RET
+digits: db 0, 0, 0
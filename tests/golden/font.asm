@@ -0,0 +1,15 @@
+; The built-in font: 0-7 on the top row, 8-F below.
+        LD V0, 0        ; digit
+        LD V1, 1        ; x
+        LD V2, 1        ; y
+loop:   LD F, V0
+        DRW V1, V2, 5
+        ADD V0, 1
+        ADD V1, 8
+        SE V0, 8
+        JP next
+        LD V1, 1
+        LD V2, 8
+next:   SE V0, 16
+        JP loop
+hang:   JP hang
@@ -0,0 +1,27 @@
+; Draws each key waited for with FX0A, then after a short delay a row of
+; marks for the keys held down, 0 on the left to F on the right.
+        LD V1, 1
+        LD V2, 1
+wait:   LD V0, K
+        LD F, V0
+        DRW V1, V2, 5
+        ADD V1, 6
+        SE V1, 25
+        JP wait
+        LD V6, 10
+        LD DT, V6
+delay:  LD V6, DT
+        SE V6, 0
+        JP delay
+        LD V3, 0        ; key
+        LD V4, 1        ; x
+        LD V5, 20       ; y
+        LD I, mark
+row:    SKNP V3
+        DRW V4, V5, 2
+        ADD V3, 1
+        ADD V4, 4
+        SE V3, 16
+        JP row
+hang:   JP hang
+mark:   db 0xE0, 0xE0
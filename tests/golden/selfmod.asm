@@ -0,0 +1,27 @@
+; Rewrites the immediate of an ADD it calls, ten times, then draws the
+; total. 0 + 1 + ... + 9 = 045.
+        LD V1, 0
+loop:   CALL target
+        ADD V1, 1
+        LD V0, 0x72
+        LD I, target
+        LD [I], V1      ; target becomes ADD V2, V1
+        SE V1, 10
+        JP loop
+        LD I, digits
+        LD B, V2
+        LD V2, [I]
+        LD V3, 1
+        LD V4, 1
+        LD F, V0
+        DRW V3, V4, 5
+        ADD V3, 5
+        LD F, V1
+        DRW V3, V4, 5
+        ADD V3, 5
+        LD F, V2
+        DRW V3, V4, 5
+hang:   JP hang
+target: ADD V2, 0
+        RET
+digits: db 0, 0, 0
@@ -0,0 +1,31 @@
+; Clipping at the right and bottom edges, wrapped start coordinates, and
+; VF after drawing inside a box's hole (0) and over the box itself (1).
+        LD I, box
+        LD V0, 60
+        LD V1, 2
+        DRW V0, V1, 8   ; clipped on the right
+        LD V0, 10
+        LD V1, 28
+        DRW V0, V1, 8   ; clipped at the bottom
+        LD V0, 70
+        LD V1, 34
+        DRW V0, V1, 8   ; starts at 6, 2
+        LD V0, 20
+        LD V1, 10
+        DRW V0, V1, 8
+        LD I, hole
+        DRW V0, V1, 8
+        LD V2, VF
+        LD I, box
+        DRW V0, V1, 8   ; erases the box, leaving the hole's contents
+        LD V3, VF
+        LD V0, 40
+        LD V1, 20
+        LD F, V2
+        DRW V0, V1, 5
+        ADD V0, 6
+        LD F, V3
+        DRW V0, V1, 5
+hang:   JP hang
+box:    db 0xFF, 0x81, 0x81, 0x81, 0x81, 0x81, 0x81, 0xFF
+hole:   db 0x00, 0x00, 0x3C, 0x3C, 0x3C, 0x3C, 0x00, 0x00
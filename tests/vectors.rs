@@ -0,0 +1,68 @@
+//! Single-step test vectors. Every `*.json` file in `tests/vectors/` holds an
+//! array of vectors, each one instruction run from a given state, and the
+//! core must end up in the expected state for all of them.
+//!
+//! Some vectors put I or the PC at the very end of memory. Loads, stores and
+//! sprites that would run past it must fail, and `BNNN` may jump past it.
+//! Fetching from past the end isn't covered here, as a vector starts from an
+//! opcode that's already been fetched; `src/core/tests.rs` checks that.
+//!
+//! `cargo run -- vectors` regenerates the files from the reference model in
+//! `core::vectors`. Review the diff before committing.
+
+use chiprust8::core::vectors::{check_vector, Chip8TestVector};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn vectors_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("vectors")
+}
+
+/// Runs every vector in one file, returning why each failure failed.
+fn check_file(path: &Path) -> Result<usize, Vec<String>> {
+    let text: String = fs::read_to_string(path).map_err(|e| vec![e.to_string()])?;
+    let vectors: Vec<Chip8TestVector> =
+        serde_json::from_str(&text).map_err(|e| vec![e.to_string()])?;
+    let failures: Vec<String> = vectors
+        .iter()
+        .enumerate()
+        .filter_map(|(n, vector)| {
+            check_vector(vector)
+                .err()
+                .map(|e| format!("{}[{}] {:04X}: {}", path.display(), n, vector.opcode, e))
+        })
+        .collect();
+    match failures.is_empty() {
+        true => Ok(vectors.len()),
+        false => Err(failures),
+    }
+}
+
+#[test]
+fn single_step_vectors() {
+    let mut files: Vec<PathBuf> = fs::read_dir(vectors_dir())
+        .expect("No tests/vectors directory")
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|e| e == "json"))
+        .collect();
+    files.sort();
+    assert!(!files.is_empty(), "No vectors in tests/vectors");
+
+    let mut total: usize = 0;
+    let mut failures: Vec<String> = Vec::new();
+    for path in files.iter() {
+        match check_file(path) {
+            Ok(count) => total += count,
+            Err(mut errs) => failures.append(&mut errs),
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} vectors failed ({} passed):\n{}",
+        failures.len(),
+        total,
+        failures.join("\n")
+    );
+}